@@ -5,70 +5,311 @@
 
 extern crate libc;
 
+use bincode::serialize;
 use chrono::prelude::*;
 use entry::Entry;
-use hash::Hash;
+use hash::{hash, Hash};
 use mint::Mint;
 use payment_plan::{Payment, PaymentPlan, Witness};
+use rayon;
 use rayon::prelude::*;
 use signature::{KeyPair, PublicKey, Signature};
 use std::collections::hash_map::Entry::Occupied;
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::iter;
 use std::result;
 use std::sync::atomic::{AtomicIsize, AtomicUsize, Ordering};
-use std::sync::RwLock;
-use transaction::{Instruction, Plan, Transaction};
+use std::sync::{Mutex, RwLock};
+use transaction::{Contract, Instruction, Plan, Transaction};
 
 pub const MAX_ENTRY_IDS: usize = 1024 * 4;
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
 pub enum BankError {
     AccountNotFound(PublicKey),
     InsufficientFunds(PublicKey),
     DuplicateSiganture(Signature),
     LastIdNotFound(Hash),
     NegativeTokens,
+    DuplicateApproval(PublicKey),
+    TickHeightNotReached(u64),
+    /// Crediting the account would overflow its balance.
+    TokenOverflow(PublicKey),
+    /// The transaction would create or destroy tokens, such as a contract
+    /// whose plan doesn't spend exactly what it holds.
+    SupplyViolation,
+    /// The batch contains instructions that can't be applied atomically.
+    InvalidBatch,
+    /// The transaction was signed for the ledger with the given genesis.
+    WrongGenesis(Hash),
+    /// The instruction is only valid in the genesis entry.
+    GenesisOnly,
 }
 
 pub type Result<T> = result::Result<T, BankError>;
 
+/// What the bank knows about a transaction signature.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub enum SignatureStatus {
+    /// The transaction was processed and any tokens it moved have been paid.
+    Confirmed,
+    /// The transaction created a contract that is waiting for a witness.
+    PendingWitness,
+    /// The transaction was rejected after its signature was recorded, such
+    /// as for lack of funds. It may be resent once the cause is fixed. Only
+    /// the leader reports this, since failed transactions aren't replicated.
+    Failed(BankError),
+    /// The signature is unknown, or its `last_id` has been booted.
+    NotFound,
+}
+
+/// A contract waiting on witnesses, along with what's needed to refund it.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct PendingContract {
+    /// The sender, who is refunded if the contract expires.
+    pub from: PublicKey,
+    /// The tokens held by the contract that haven't been paid out yet.
+    pub tokens: i64,
+    pub expires: Option<DateTime<Utc>>,
+    pub plan: Plan,
+}
+
+impl PendingContract {
+    /// Return the earliest time at which a timestamp can change the contract,
+    /// either by reducing its plan or by expiring it.
+    fn deadline(&self) -> Option<DateTime<Utc>> {
+        match (self.plan.next_deadline(), self.expires) {
+            (Some(dt), Some(expires)) => Some(dt.min(expires)),
+            (dt, expires) => dt.or(expires),
+        }
+    }
+}
+
+/// A serializable copy of the `Bank` state, taken at an entry boundary.
+/// Restoring it and processing the entries that follow `last_id()` yields the
+/// same state as processing the entire ledger.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct Snapshot {
+    pub genesis: Hash,
+    pub balances: Vec<(PublicKey, i64)>,
+    pub pending: Vec<(Signature, PendingContract)>,
+    pub last_ids: Vec<(Hash, Vec<(Signature, Result<()>)>)>,
+    pub time_sources: Vec<(PublicKey, DateTime<Utc>)>,
+    pub time_source_votes: Vec<(PublicKey, Vec<PublicKey>)>,
+    pub last_time: DateTime<Utc>,
+    pub transaction_count: u64,
+    pub tick_height: u64,
+    pub fee_collector: PublicKey,
+    pub entry_fees: Vec<(Hash, i64)>,
+    pub nonces: Vec<(PublicKey, Hash)>,
+    /// The number of tokens created at genesis, which `verify_supply` checks
+    /// the restored balances and contracts against.
+    pub supply: i64,
+}
+
+impl Snapshot {
+    /// Return the ID of the last Entry processed before the snapshot was
+    /// taken, or None if the snapshot holds no entry IDs.
+    pub fn last_id(&self) -> Option<Hash> {
+        self.last_ids.last().map(|(id, _)| *id)
+    }
+}
+
 pub struct Bank {
+    /// The ID of the first entry of the ledger. Transactions must be signed
+    /// for it, so that they can't be replayed on another ledger.
+    genesis: Hash,
+
     balances: RwLock<HashMap<PublicKey, AtomicIsize>>,
-    pending: RwLock<HashMap<Signature, Plan>>,
-    last_ids: RwLock<VecDeque<(Hash, RwLock<HashSet<Signature>>)>>,
-    time_sources: RwLock<HashSet<PublicKey>>,
+    pending: RwLock<HashMap<Signature, PendingContract>>,
+
+    /// Pending contracts ordered by their deadlines, so that a timestamp only
+    /// visits the ones it can change. Only modified while holding the
+    /// 'pending' write lock.
+    deadlines: Mutex<BTreeSet<(DateTime<Utc>, Signature)>>,
+    /// The signatures seen with each recent Entry ID, and whether their
+    /// transactions succeeded.
+    last_ids: RwLock<VecDeque<(Hash, RwLock<HashMap<Signature, Result<()>>>)>>,
+    /// The trusted time sources, and the latest time each has reported.
+    time_sources: RwLock<HashMap<PublicKey, DateTime<Utc>>>,
+
+    /// The set of time sources each current source has voted to rotate to.
+    time_source_votes: RwLock<HashMap<PublicKey, Vec<PublicKey>>>,
+
+    /// The latest time reported by a majority of the time sources.
     last_time: RwLock<DateTime<Utc>>,
     transaction_count: AtomicUsize,
+
+    /// The number of tokens in existence, set by the genesis deposit. The
+    /// balances and pending contracts must always add up to it.
+    supply: i64,
+
+    /// The number of ticks, entries without transactions, registered so far.
+    tick_height: AtomicUsize,
+
+    /// The account credited with the fee of each new contract.
+    fee_collector: RwLock<PublicKey>,
+
+    /// The total fees collected from the transactions of each Entry, keyed by
+    /// Entry ID. Pruned along with `last_ids`.
+    entry_fees: RwLock<HashMap<Hash, i64>>,
+
+    /// The current nonce of each account that has one. A transaction may use
+    /// its sender's nonce in place of a recent `last_id`, and the nonce
+    /// advances when it does, so that the transaction can't be replayed.
+    nonces: RwLock<HashMap<PublicKey, Hash>>,
+
+    /// The XOR of the hashes of every balance, pending plan and nonce as of
+    /// the last entry, and the transaction count at the time it was computed.
+    state_hash: RwLock<(Hash, u64)>,
+
+    /// The balances changed since `state_hash` was last updated, along with
+    /// their values at that time, or None for new accounts.
+    dirty_balances: Mutex<HashMap<PublicKey, Option<i64>>>,
+
+    /// The pending plans changed since `state_hash` was last updated, along
+    /// with the hashes of their values at that time, or None for new plans.
+    dirty_pending: Mutex<HashMap<Signature, Option<Hash>>>,
+
+    /// The nonces changed since `state_hash` was last updated, along with
+    /// their values at that time, or None for new nonces.
+    dirty_nonces: Mutex<HashMap<PublicKey, Option<Hash>>>,
 }
 
 impl Bank {
     /// Create an Bank using a deposit.
-    pub fn new_from_deposit(deposit: &Payment) -> Self {
+    pub fn new_from_deposit(genesis: Hash, deposit: &Payment) -> Self {
         let bank = Bank {
+            genesis,
             balances: RwLock::new(HashMap::new()),
             pending: RwLock::new(HashMap::new()),
+            deadlines: Mutex::new(BTreeSet::new()),
             last_ids: RwLock::new(VecDeque::new()),
-            time_sources: RwLock::new(HashSet::new()),
+            time_sources: RwLock::new(
+                vec![(deposit.to, Utc.timestamp(0, 0))]
+                    .into_iter()
+                    .collect(),
+            ),
+            time_source_votes: RwLock::new(HashMap::new()),
             last_time: RwLock::new(Utc.timestamp(0, 0)),
             transaction_count: AtomicUsize::new(0),
+            supply: deposit.tokens,
+            tick_height: AtomicUsize::new(0),
+            fee_collector: RwLock::new(deposit.to),
+            entry_fees: RwLock::new(HashMap::new()),
+            nonces: RwLock::new(HashMap::new()),
+            state_hash: RwLock::new((Hash::default(), 0)),
+            dirty_balances: Mutex::new(HashMap::new()),
+            dirty_pending: Mutex::new(HashMap::new()),
+            dirty_nonces: Mutex::new(HashMap::new()),
         };
-        bank.apply_payment(deposit);
+        bank.apply_payment(deposit)
+            .expect("'apply_payment' of the deposit to an empty bank");
+        bank.update_state_hash();
         bank
     }
 
+    /// Create a Bank from a Snapshot. Entries following `snapshot.last_id()`
+    /// must be processed to bring it up to date with the ledger. Returns None
+    /// if the snapshot holds no entry IDs.
+    pub fn from_snapshot(snapshot: &Snapshot) -> Option<Self> {
+        snapshot.last_id()?;
+        let balances = snapshot
+            .balances
+            .iter()
+            .map(|(key, tokens)| (*key, AtomicIsize::new(*tokens as isize)))
+            .collect();
+        let last_ids = snapshot
+            .last_ids
+            .iter()
+            .map(|(id, sigs)| (*id, RwLock::new(sigs.iter().cloned().collect())))
+            .collect();
+        let bank = Bank {
+            genesis: snapshot.genesis,
+            balances: RwLock::new(balances),
+            pending: RwLock::new(snapshot.pending.iter().cloned().collect()),
+            deadlines: Mutex::new(
+                snapshot
+                    .pending
+                    .iter()
+                    .filter_map(|(sig, contract)| contract.deadline().map(|dt| (dt, *sig)))
+                    .collect(),
+            ),
+            last_ids: RwLock::new(last_ids),
+            time_sources: RwLock::new(snapshot.time_sources.iter().cloned().collect()),
+            time_source_votes: RwLock::new(snapshot.time_source_votes.iter().cloned().collect()),
+            last_time: RwLock::new(snapshot.last_time),
+            transaction_count: AtomicUsize::new(snapshot.transaction_count as usize),
+            supply: snapshot.supply,
+            tick_height: AtomicUsize::new(snapshot.tick_height as usize),
+            fee_collector: RwLock::new(snapshot.fee_collector),
+            entry_fees: RwLock::new(snapshot.entry_fees.iter().cloned().collect()),
+            nonces: RwLock::new(snapshot.nonces.iter().cloned().collect()),
+            state_hash: RwLock::new((Hash::default(), 0)),
+            dirty_balances: Mutex::new(HashMap::new()),
+            dirty_pending: Mutex::new(HashMap::new()),
+            dirty_nonces: Mutex::new(HashMap::new()),
+        };
+        *bank.state_hash
+            .write()
+            .expect("'state_hash' write lock in from_snapshot") =
+            (bank.compute_state_hash(), snapshot.transaction_count);
+        Some(bank)
+    }
+
+    /// Create a Bank from the transactions of the genesis entry: a deposit to
+    /// the mint, optionally followed by the mint's choice of time sources and
+    /// of fee collector. Until then, the mint is the only time source and
+    /// collects the fees. The ledger's genesis is
+    /// the one the deposit was signed for. Returns None if the first
+    /// transaction isn't a deposit.
+    pub fn new_from_genesis(txs: &[Transaction]) -> Option<Self> {
+        let tx = txs.first()?;
+        let deposit = match &tx.instruction {
+            Instruction::NewContract(contract) => contract.plan.final_payment()?,
+            _ => return None,
+        };
+        let bank = Self::new_from_deposit(tx.genesis, &deposit);
+        for tx in &txs[1..] {
+            match &tx.instruction {
+                Instruction::SetTimeSources(sources) => {
+                    bank.apply_time_source_vote(tx.from, sources)
+                }
+                Instruction::SetFeeCollector(collector) if tx.from == deposit.to => {
+                    bank.set_fee_collector(*collector)
+                }
+                _ => {}
+            }
+        }
+        Some(bank)
+    }
+
     /// Create an Bank with only a Mint. Typically used by unit tests.
     pub fn new(mint: &Mint) -> Self {
-        let deposit = Payment {
-            to: mint.pubkey(),
-            tokens: mint.tokens,
-        };
-        let bank = Self::new_from_deposit(&deposit);
+        let bank =
+            Self::new_from_genesis(&mint.create_transactions()).expect("deposit in Mint genesis");
         bank.register_entry_id(&mint.last_id());
         bank
     }
 
+    /// Set the account that collects transaction fees. Defaults to the
+    /// recipient of the genesis deposit. Every node replaying the ledger must
+    /// use the same collector, so outside of tests it's only set from the
+    /// genesis entry, and is kept in snapshots.
+    pub fn set_fee_collector(&self, collector: PublicKey) {
+        *self.fee_collector
+            .write()
+            .expect("'fee_collector' write lock in set_fee_collector") = collector;
+    }
+
+    pub fn fee_collector(&self) -> PublicKey {
+        *self.fee_collector
+            .read()
+            .expect("'fee_collector' read lock")
+    }
+
     /// Commit funds to the 'to' party.
-    fn apply_payment(&self, payment: &Payment) {
+    fn apply_payment(&self, payment: &Payment) -> Result<()> {
         // First we check balances with a read lock to maximize potential parallelization.
         if self.balances
             .read()
@@ -76,19 +317,134 @@ impl Bank {
             .contains_key(&payment.to)
         {
             let bals = self.balances.read().expect("'balances' read lock");
-            bals[&payment.to].fetch_add(payment.tokens as isize, Ordering::Relaxed);
+            let old = Self::checked_credit(&bals[&payment.to], &payment.to, payment.tokens)?;
+            self.mark_balance_dirty(&payment.to, Some(old));
         } else {
             // Now we know the key wasn't present a nanosecond ago, but it might be there
             // by the time we aquire a write lock, so we'll have to check again.
             let mut bals = self.balances.write().expect("'balances' write lock");
             if bals.contains_key(&payment.to) {
-                bals[&payment.to].fetch_add(payment.tokens as isize, Ordering::Relaxed);
+                let old = Self::checked_credit(&bals[&payment.to], &payment.to, payment.tokens)?;
+                self.mark_balance_dirty(&payment.to, Some(old));
             } else {
-                bals.insert(payment.to, AtomicIsize::new(payment.tokens as isize));
+                let bal = AtomicIsize::new(0);
+                Self::checked_credit(&bal, &payment.to, payment.tokens)?;
+                bals.insert(payment.to, bal);
+                self.mark_balance_dirty(&payment.to, None);
+            }
+        }
+        Ok(())
+    }
+
+    /// Add `tokens` to a balance, and return its old value. Fails instead of
+    /// wrapping if the new balance doesn't fit in an i64, or in the isize
+    /// the balance is stored as.
+    fn checked_credit(bal: &AtomicIsize, key: &PublicKey, tokens: i64) -> Result<i64> {
+        loop {
+            let current = bal.load(Ordering::Relaxed);
+            let new = match (current as i64).checked_add(tokens) {
+                Some(new) if new as isize as i64 == new => new as isize,
+                _ => return Err(BankError::TokenOverflow(*key)),
+            };
+            if bal.compare_exchange(current, new, Ordering::Relaxed, Ordering::Relaxed)
+                .is_ok()
+            {
+                return Ok(current as i64);
+            }
+        }
+    }
+
+    /// Commit the funds of every Payment, once a plan has been reduced to them.
+    fn apply_payments(&self, payments: &[Payment]) -> Result<()> {
+        for payment in payments {
+            self.apply_payment(payment)?;
+        }
+        Ok(())
+    }
+
+    /// Commit the funds of every Payment, or of none of them if any balance
+    /// would overflow. The 'balances' write lock keeps other credits out
+    /// between the check and the commit.
+    fn apply_payments_or_none(&self, payments: &[Payment]) -> Result<()> {
+        let mut bals = self.balances
+            .write()
+            .expect("'balances' write lock in apply_payments_or_none");
+        let mut totals: HashMap<PublicKey, i64> = HashMap::new();
+        for payment in payments {
+            let total = totals.entry(payment.to).or_insert_with(|| {
+                bals.get(&payment.to)
+                    .map_or(0, |bal| bal.load(Ordering::Relaxed) as i64)
+            });
+            *total = match total.checked_add(payment.tokens) {
+                Some(new) if new as isize as i64 == new => new,
+                _ => return Err(BankError::TokenOverflow(payment.to)),
+            };
+        }
+        for (key, tokens) in totals {
+            if let Some(bal) = bals.get(&key) {
+                let old = bal.swap(tokens as isize, Ordering::Relaxed);
+                self.mark_balance_dirty(&key, Some(old as i64));
+                continue;
             }
+            bals.insert(key, AtomicIsize::new(tokens as isize));
+            self.mark_balance_dirty(&key, None);
+        }
+        Ok(())
+    }
+
+    /// Remember the value a balance had when `state_hash` was last updated.
+    /// Only the first change after an update is recorded.
+    fn mark_balance_dirty(&self, key: &PublicKey, old: Option<i64>) {
+        self.dirty_balances
+            .lock()
+            .expect("'dirty_balances' lock")
+            .entry(*key)
+            .or_insert(old);
+    }
+
+    /// Remember the hash a pending plan had when `state_hash` was last updated.
+    /// Only the first change after an update is recorded.
+    fn mark_plan_dirty(&self, sig: &Signature, old: Option<Hash>) {
+        self.dirty_pending
+            .lock()
+            .expect("'dirty_pending' lock")
+            .entry(*sig)
+            .or_insert(old);
+    }
+
+    /// Remember the nonce an account had when `state_hash` was last updated.
+    /// Only the first change after an update is recorded.
+    fn mark_nonce_dirty(&self, key: &PublicKey, old: Option<Hash>) {
+        self.dirty_nonces
+            .lock()
+            .expect("'dirty_nonces' lock")
+            .entry(*key)
+            .or_insert(old);
+    }
+
+    fn balance_hash(key: &PublicKey, tokens: i64) -> Hash {
+        hash(&serialize(&(key, tokens)).expect("serialize balance in balance_hash"))
+    }
+
+    fn plan_hash(sig: &Signature, contract: &PendingContract) -> Hash {
+        hash(&serialize(&(sig, contract)).expect("serialize contract in plan_hash"))
+    }
+
+    fn nonce_hash(key: &PublicKey, nonce: &Hash) -> Hash {
+        hash(&serialize(&(key, nonce)).expect("serialize nonce in nonce_hash"))
+    }
+
+    fn xor_hash(acc: &mut Hash, val: &Hash) {
+        for (a, v) in acc.iter_mut().zip(val.iter()) {
+            *a ^= *v;
         }
     }
 
+    /// Return the ID of the first entry of the ledger.
+    pub fn genesis(&self) -> Hash {
+        self.genesis
+    }
+
     /// Return the last entry ID registered
     pub fn last_id(&self) -> Hash {
         let last_ids = self.last_ids.read().expect("'last_ids' read lock");
@@ -96,37 +452,78 @@ impl Bank {
         last_item.0
     }
 
-    fn reserve_signature(signatures: &RwLock<HashSet<Signature>>, sig: &Signature) -> Result<()> {
-        if signatures
+    /// Reserve a signature. Signatures of failed transactions may be reused.
+    fn reserve_signature(
+        signatures: &RwLock<HashMap<Signature, Result<()>>>,
+        sig: &Signature,
+    ) -> Result<()> {
+        if let Some(Ok(())) = signatures
             .read()
             .expect("'signatures' read lock")
-            .contains(sig)
+            .get(sig)
         {
             return Err(BankError::DuplicateSiganture(*sig));
         }
-        signatures
-            .write()
-            .expect("'signatures' write lock")
-            .insert(*sig);
+        let mut signatures = signatures.write().expect("'signatures' write lock");
+        if let Some(Ok(())) = signatures.get(sig) {
+            return Err(BankError::DuplicateSiganture(*sig));
+        }
+        signatures.insert(*sig, Ok(()));
         Ok(())
     }
 
-    fn forget_signature(signatures: &RwLock<HashSet<Signature>>, sig: &Signature) {
+    /// Record why a transaction failed.
+    fn fail_signature(
+        signatures: &RwLock<HashMap<Signature, Result<()>>>,
+        sig: &Signature,
+        err: &BankError,
+    ) {
         signatures
             .write()
-            .expect("'signatures' write lock in forget_signature")
-            .remove(sig);
+            .expect("'signatures' write lock in fail_signature")
+            .insert(*sig, Err(err.clone()));
     }
 
-    fn forget_signature_with_last_id(&self, sig: &Signature, last_id: &Hash) {
+    fn fail_signature_with_last_id(&self, sig: &Signature, last_id: &Hash, err: &BankError) {
         if let Some(entry) = self.last_ids
             .read()
-            .expect("'last_ids' read lock in forget_signature_with_last_id")
+            .expect("'last_ids' read lock in fail_signature_with_last_id")
             .iter()
             .rev()
             .find(|x| x.0 == *last_id)
         {
-            Self::forget_signature(&entry.1, sig);
+            Self::fail_signature(&entry.1, sig, err);
+        }
+    }
+
+    /// Return true if `last_id` is one of the entry IDs the bank still
+    /// accepts.
+    fn has_last_id(&self, last_id: &Hash) -> bool {
+        self.last_ids
+            .read()
+            .expect("'last_ids' read lock in has_last_id")
+            .iter()
+            .any(|x| x.0 == *last_id)
+    }
+
+    /// Record why a transaction failed. A durable transaction, which was
+    /// signed with a nonce, is found by its signature, since that's recorded
+    /// with whichever entry ID was newest when it was processed.
+    fn fail_transaction(&self, tx: &Transaction, err: &BankError) {
+        if self.has_last_id(&tx.last_id) {
+            return self.fail_signature_with_last_id(&tx.sig, &tx.last_id, err);
+        }
+        if let Some(entry) = self.last_ids
+            .read()
+            .expect("'last_ids' read lock in fail_transaction")
+            .iter()
+            .rev()
+            .find(|x| {
+                x.1.read()
+                    .expect("'signatures' read lock in fail_transaction")
+                    .contains_key(&tx.sig)
+            }) {
+            Self::fail_signature(&entry.1, &tx.sig, err);
         }
     }
 
@@ -152,112 +549,458 @@ impl Bank {
             .write()
             .expect("'last_ids' write lock in register_entry_id");
         if last_ids.len() >= MAX_ENTRY_IDS {
-            last_ids.pop_front();
+            if let Some((id, _)) = last_ids.pop_front() {
+                self.entry_fees
+                    .write()
+                    .expect("'entry_fees' write lock in register_entry_id")
+                    .remove(&id);
+            }
+        }
+        last_ids.push_back((*last_id, RwLock::new(HashMap::new())));
+    }
+
+    /// Register the ID of an Entry whose transactions have been processed,
+    /// and record the fees they paid to the collector. Entries without
+    /// transactions are ticks, and advance the tick height. The transactions
+    /// must have been processed by this bank, so that their fees, which were
+    /// debited from its balances, can't overflow.
+    pub fn register_entry(&self, entry: &Entry) {
+        let fees = Self::total_fees(&entry.transactions)
+            .expect("fees of processed transactions overflow in register_entry");
+        self.register_entry_id_with_fees(&entry.id, fees, entry.transactions.is_empty());
+    }
+
+    fn register_entry_id_with_fees(&self, last_id: &Hash, fees: i64, is_tick: bool) {
+        if is_tick {
+            self.tick_height.fetch_add(1, Ordering::Relaxed);
+        }
+        self.entry_fees
+            .write()
+            .expect("'entry_fees' write lock in register_entry_id_with_fees")
+            .insert(*last_id, fees);
+        self.register_entry_id(last_id);
+    }
+
+    /// Sum the fees of the transactions that create contracts, the only ones
+    /// that debit the sender. Returns None if the sum overflows, which only
+    /// transactions that haven't been validated can make it do.
+    fn total_fees(txs: &[Transaction]) -> Option<i64> {
+        let mut total = 0i64;
+        for tx in txs.iter().filter(|tx| tx.contract().is_some()) {
+            total = total.checked_add(tx.fee)?;
         }
-        last_ids.push_back((*last_id, RwLock::new(HashSet::new())));
+        Some(total)
     }
 
     /// Deduct tokens from the 'from' address the account has sufficient
-    /// funds and isn't a duplicate.
-    fn apply_debits(&self, tx: &Transaction) -> Result<()> {
-        if let Instruction::NewContract(contract) = &tx.instruction {
+    /// funds and isn't a duplicate. A failure is only recorded with the
+    /// transaction's signature once the signature has been reserved, so
+    /// that transactions rejected before then, which cost their senders
+    /// nothing, take up no memory. `unsettled_nonces` holds the accounts
+    /// whose nonces were advanced by durable transactions whose credits
+    /// haven't been applied yet.
+    fn apply_debits(
+        &self,
+        tx: &Transaction,
+        unsettled_nonces: &mut HashSet<PublicKey>,
+    ) -> Result<()> {
+        if tx.genesis != self.genesis {
+            return Err(BankError::WrongGenesis(tx.genesis));
+        }
+        if let Instruction::Batch(instructions) = &tx.instruction {
+            if !Transaction::verify_batch(instructions) {
+                return Err(BankError::InvalidBatch);
+            }
+        }
+        if let Some(contract) = tx.contract() {
             trace!("Transaction {}", contract.tokens);
             if contract.tokens < 0 {
                 return Err(BankError::NegativeTokens);
             }
+            // Entries from the ledger haven't necessarily been checked by the
+            // banking stage, and an unbalanced plan would mint tokens.
+            if !tx.verify_plan() {
+                return Err(BankError::SupplyViolation);
+            }
+        } else if tx.fee != 0 {
+            // Without a contract to pay it out of, a fee would mint tokens.
+            return Err(BankError::SupplyViolation);
         }
+        let debits = tx.debits().ok_or(BankError::TokenOverflow(tx.from))?;
         let bals = self.balances
             .read()
             .expect("'balances' read lock in apply_debits");
-        let option = bals.get(&tx.from);
-
-        if option.is_none() {
-            return Err(BankError::AccountNotFound(tx.from));
+        let keys = iter::once(&tx.from).chain(tx.cosigners.iter().map(|cosigner| &cosigner.key));
+        for key in keys {
+            if !bals.contains_key(key) {
+                return Err(BankError::AccountNotFound(*key));
+            }
         }
 
-        self.reserve_signature_with_last_id(&tx.sig, &tx.last_id)?;
-
-        loop {
-            let result = if let Instruction::NewContract(contract) = &tx.instruction {
-                let bal = option.expect("assignment of option to bal");
-                let current = bal.load(Ordering::Relaxed) as i64;
+        let mut durable = false;
+        if let Err(err) = self.reserve_signature_with_last_id(&tx.sig, &tx.last_id) {
+            match err {
+                // A durable transaction's signature is recorded with the
+                // newest entry ID instead, so that its outcome can be looked
+                // up. It can't be replayed, since its nonce moves on once
+                // the debits succeed. A nonce that may yet be restored by
+                // its transaction's failing credits can't be used, since a
+                // validator replaying the ledger never sees it.
+                BankError::LastIdNotFound(_)
+                    if self.is_signed_with_nonce(tx) && !unsettled_nonces.contains(&tx.from) =>
+                {
+                    self.reserve_signature_with_last_id(&tx.sig, &self.last_id())?;
+                    durable = true;
+                }
+                err => return Err(err),
+            }
+        }
 
-                if current < contract.tokens {
-                    self.forget_signature_with_last_id(&tx.sig, &tx.last_id);
-                    return Err(BankError::InsufficientFunds(tx.from));
+        // Debit every account, or none of them.
+        for (i, (key, tokens)) in debits.iter().enumerate() {
+            match Self::checked_debit(&bals[key], key, *tokens) {
+                Ok(old) => self.mark_balance_dirty(key, Some(old)),
+                Err(err) => {
+                    for (key, tokens) in &debits[..i] {
+                        Self::checked_credit(&bals[key], key, *tokens)?;
+                    }
+                    self.fail_transaction(tx, &err);
+                    return Err(err);
                 }
+            }
+        }
 
-                bal.compare_exchange(
-                    current as isize,
-                    (current - contract.tokens) as isize,
-                    Ordering::Relaxed,
-                    Ordering::Relaxed,
-                )
+        // Nonces are advanced in the same phase they're used in, so that a
+        // transaction signed with a nonce sees the AdvanceNonce before it in
+        // the ledger. Both are grouped by sender, which keeps them in order.
+        // A transaction that failed never reaches the ledger, so it must not
+        // move the nonce.
+        if durable || tx.instruction == Instruction::AdvanceNonce {
+            let old = self.nonces
+                .write()
+                .expect("'nonces' write lock in apply_debits")
+                .insert(tx.from, hash(&tx.sig));
+            self.mark_nonce_dirty(&tx.from, old);
+            // Advancing a nonce can't fail, but other credits can.
+            if tx.instruction == Instruction::AdvanceNonce {
+                unsettled_nonces.remove(&tx.from);
             } else {
-                Ok(0)
-            };
+                unsettled_nonces.insert(tx.from);
+            }
+        }
+        self.transaction_count.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
 
-            match result {
-                Ok(_) => {
-                    self.transaction_count.fetch_add(1, Ordering::Relaxed);
-                    return Ok(());
-                }
-                Err(_) => continue,
-            };
+    /// Return true if the transaction was signed with its sender's nonce in
+    /// place of a recent `last_id`.
+    fn is_signed_with_nonce(&self, tx: &Transaction) -> bool {
+        self.get_nonce(&tx.from) == Some(tx.last_id)
+    }
+
+    /// Undo the nonce advance of a durable transaction whose credits failed,
+    /// since it won't reach the ledger either.
+    fn restore_nonce(&self, tx: &Transaction) {
+        if self.has_last_id(&tx.last_id) {
+            return;
+        }
+        let mut nonces = self.nonces
+            .write()
+            .expect("'nonces' write lock in restore_nonce");
+        if let Some(nonce) = nonces.get_mut(&tx.from) {
+            if *nonce == hash(&tx.sig) {
+                self.mark_nonce_dirty(&tx.from, Some(*nonce));
+                *nonce = tx.last_id;
+            }
+        }
+    }
+
+    /// Subtract `tokens` from a balance, and return its old value. Fails
+    /// if the balance is too low.
+    fn checked_debit(bal: &AtomicIsize, key: &PublicKey, tokens: i64) -> Result<i64> {
+        loop {
+            let current = bal.load(Ordering::Relaxed);
+            if (current as i64) < tokens {
+                return Err(BankError::InsufficientFunds(*key));
+            }
+            let new = (current as i64 - tokens) as isize;
+            if bal.compare_exchange(current, new, Ordering::Relaxed, Ordering::Relaxed)
+                .is_ok()
+            {
+                return Ok(current as i64);
+            }
         }
     }
 
-    fn apply_credits(&self, tx: &Transaction) {
+    /// Apply the effects of a transaction whose debits succeeded. Witnesses
+    /// don't debit any tokens, so there's nothing to undo when they fail.
+    /// A failed batch returns what it debited. Credits can only overflow if
+    /// the supply invariant was already broken.
+    fn apply_credits(&self, tx: &Transaction) -> Result<()> {
         match &tx.instruction {
             Instruction::NewContract(contract) => {
-                let mut plan = contract.plan.clone();
-                plan.apply_witness(&Witness::Timestamp(*self.last_time
+                self.apply_fee(tx)?;
+                let mut contract = Self::new_pending_contract(tx, contract)?;
+                let last_time = *self.last_time
                     .read()
-                    .expect("timestamp creation in apply_credits")));
+                    .expect("timestamp creation in apply_credits");
+                contract.plan.apply_witness(&Witness::Timestamp(last_time));
 
-                if let Some(payment) = plan.final_payment() {
-                    self.apply_payment(&payment);
-                } else {
+                let (done, payments) = Self::settle_contract(&mut contract, last_time)?;
+                self.apply_payments(&payments)?;
+                if !done {
                     let mut pending = self.pending
                         .write()
                         .expect("'pending' write lock in apply_credits");
-                    pending.insert(tx.sig, plan);
+                    self.mark_plan_dirty(&tx.sig, None);
+                    self.update_deadline(&tx.sig, None, contract.deadline());
+                    pending.insert(tx.sig, contract);
                 }
+                Ok(())
+            }
+            Instruction::ApplyTimestamp(dt) => self.apply_timestamp(tx.from, *dt),
+            Instruction::SetTimeSources(sources) => {
+                self.apply_time_source_vote(tx.from, sources);
+                Ok(())
+            }
+            Instruction::ApplySignature(tx_sig) => self.apply_signature(tx.from, *tx_sig),
+            Instruction::ApplyPreimage(tx_sig, preimage) => {
+                self.apply_preimage(*tx_sig, preimage.clone())
+            }
+            Instruction::ApplyTickHeight(tx_sig, height) => {
+                self.apply_tick_height(*tx_sig, *height)
             }
-            Instruction::ApplyTimestamp(dt) => {
-                let _ = self.apply_timestamp(tx.from, *dt);
+            Instruction::Batch(instructions) => {
+                let result = self.apply_batch(tx, instructions);
+                if result.is_err() {
+                    let debits = tx.debits().ok_or(BankError::TokenOverflow(tx.from))?;
+                    for (to, tokens) in debits {
+                        self.apply_payment(&Payment { tokens, to })?;
+                    }
+                }
+                result
             }
-            Instruction::ApplySignature(tx_sig) => {
-                let _ = self.apply_signature(tx.from, *tx_sig);
+            // The nonce was advanced along with the debits.
+            Instruction::AdvanceNonce => Ok(()),
+            Instruction::SetFeeCollector(_) => Err(BankError::GenesisOnly),
+        }
+    }
+
+    /// Credit the fee of a transaction that creates a contract to the collector.
+    fn apply_fee(&self, tx: &Transaction) -> Result<()> {
+        if tx.fee == 0 {
+            return Ok(());
+        }
+        let fee = Payment {
+            tokens: tx.fee,
+            to: self.fee_collector(),
+        };
+        self.apply_payment(&fee)
+    }
+
+    /// Create the pending state of a contract, after its fee is paid.
+    fn new_pending_contract(tx: &Transaction, contract: &Contract) -> Result<PendingContract> {
+        Ok(PendingContract {
+            from: tx.from,
+            tokens: contract
+                .tokens
+                .checked_sub(tx.fee)
+                .ok_or(BankError::SupplyViolation)?,
+            expires: contract.expires,
+            plan: contract.plan.clone(),
+        })
+    }
+
+    /// Apply the instructions of a batch in order, or none of them. They're
+    /// applied to copies of the contracts they touch, which replace the
+    /// pending contracts only once every instruction has succeeded and every
+    /// payment, including the fee, has been made.
+    fn apply_batch(&self, tx: &Transaction, instructions: &[Instruction]) -> Result<()> {
+        // Hold 'pending' write lock until the copies are committed, so that no
+        // other witness can apply to the contracts in between.
+        let mut pending = self.pending
+            .write()
+            .expect("'pending' write lock in apply_batch");
+        let last_time = *self.last_time
+            .read()
+            .expect("'last_time' read lock in apply_batch");
+
+        // The copies of the contracts touched so far, or None once settled.
+        let mut staged: HashMap<Signature, Option<PendingContract>> = HashMap::new();
+        let mut payments = vec![];
+        for instruction in instructions {
+            let (tx_sig, witness) = match instruction {
+                Instruction::NewContract(contract) => {
+                    let mut contract = Self::new_pending_contract(tx, contract)?;
+                    contract.plan.apply_witness(&Witness::Timestamp(last_time));
+                    let (done, due) = Self::settle_contract(&mut contract, last_time)?;
+                    payments.extend(due);
+                    staged.insert(tx.sig, if done { None } else { Some(contract) });
+                    continue;
+                }
+                Instruction::ApplySignature(tx_sig) => (*tx_sig, Witness::Signature(tx.from)),
+                Instruction::ApplyPreimage(tx_sig, preimage) => {
+                    (*tx_sig, Witness::Preimage(preimage.clone()))
+                }
+                Instruction::ApplyTickHeight(tx_sig, height) => {
+                    if *height > self.tick_height() {
+                        return Err(BankError::TickHeightNotReached(*height));
+                    }
+                    (*tx_sig, Witness::TickHeight(*height))
+                }
+                _ => return Err(BankError::InvalidBatch),
+            };
+            let contract = match staged.get(&tx_sig) {
+                Some(contract) => contract.clone(),
+                None => pending.get(&tx_sig).cloned(),
+            };
+            if let Some(mut contract) = contract {
+                let (done, due) = Self::witness_contract(&mut contract, &witness, last_time)?;
+                payments.extend(due);
+                staged.insert(tx_sig, if done { None } else { Some(contract) });
             }
         }
+
+        if tx.contract().is_some() && tx.fee != 0 {
+            payments.push(Payment {
+                tokens: tx.fee,
+                to: self.fee_collector(),
+            });
+        }
+        self.apply_payments_or_none(&payments)?;
+
+        for (sig, contract) in staged {
+            let old = pending.get(&sig).map(|old| (Self::plan_hash(&sig, old), old.deadline()));
+            self.mark_plan_dirty(&sig, old.map(|(hash, _)| hash));
+            let old_deadline = old.and_then(|(_, deadline)| deadline);
+            let new_deadline = contract.as_ref().and_then(|contract| contract.deadline());
+            self.update_deadline(&sig, old_deadline, new_deadline);
+            match contract {
+                Some(contract) => pending.insert(sig, contract),
+                None => pending.remove(&sig),
+            };
+        }
+        Ok(())
+    }
+
+    /// Apply the credits of a transaction, rejecting it if they fail.
+    fn apply_credits_or_reject(&self, tx: &Transaction) -> Result<()> {
+        let result = self.apply_credits(tx);
+        if let Err(err) = &result {
+            self.fail_transaction(tx, err);
+            self.restore_nonce(tx);
+            self.transaction_count.fetch_sub(1, Ordering::Relaxed);
+        }
+        result
     }
 
     /// Process a Transaction.
     fn process_transaction(&self, tx: &Transaction) -> Result<()> {
-        self.apply_debits(tx)?;
-        self.apply_credits(tx);
-        Ok(())
+        self.apply_debits(tx, &mut HashSet::new())?;
+        self.apply_credits_or_reject(tx)
     }
 
-    /// Process a batch of transactions.
+    /// Group the indexes of `txs` so that transactions debiting the same
+    /// account are in the same group, keeping ledger order within each group.
+    /// A transaction with co-signers joins the groups of every account it
+    /// debits. Transactions in different groups can't compete for the same
+    /// balance.
+    fn group_by_debit_account(txs: &[Transaction]) -> Vec<Vec<usize>> {
+        // A union-find over the transactions, where each root is the first
+        // transaction of its group.
+        fn find(parents: &mut [usize], mut i: usize) -> usize {
+            while parents[i] != i {
+                parents[i] = parents[parents[i]];
+                i = parents[i];
+            }
+            i
+        }
+
+        let mut parents: Vec<usize> = (0..txs.len()).collect();
+        let mut first_debits = HashMap::new();
+        for (i, tx) in txs.iter().enumerate() {
+            let keys = iter::once(tx.from).chain(tx.cosigners.iter().map(|cosigner| cosigner.key));
+            for key in keys {
+                let j = *first_debits.entry(key).or_insert(i);
+                let (root_i, root_j) = (find(&mut parents, i), find(&mut parents, j));
+                parents[root_i.max(root_j)] = root_i.min(root_j);
+            }
+        }
+
+        let mut group_ids = HashMap::new();
+        let mut groups: Vec<Vec<usize>> = vec![];
+        for i in 0..txs.len() {
+            let root = find(&mut parents, i);
+            let num_groups = groups.len();
+            let group_id = *group_ids.entry(root).or_insert(num_groups);
+            if group_id == num_groups {
+                groups.push(vec![]);
+            }
+            groups[group_id].push(i);
+        }
+        groups
+    }
+
+    /// Return true if crediting `tx` only adds tokens to balances, which
+    /// gives the same result in any order. Anything that touches 'pending'
+    /// or the bank's clock must be credited in ledger order.
+    fn is_commutative_credit(tx: &Transaction) -> bool {
+        match &tx.instruction {
+            Instruction::NewContract(contract) => contract.plan.final_payments().is_some(),
+            _ => false,
+        }
+    }
+
+    /// Process a batch of transactions. The results are the same as processing
+    /// the debits of every transaction in ledger order, and then their credits
+    /// in ledger order, no matter how the work is scheduled across threads.
     pub fn process_transactions(&self, txs: Vec<Transaction>) -> Vec<Result<Transaction>> {
-        // Run all debits first to filter out any transactions that can't be processed
-        // in parallel deterministically.
+        // Run all debits first to filter out any transactions that can't be processed.
+        // Transactions debiting the same account are applied one at a time in ledger
+        // order, so which of them runs out of funds doesn't depend on thread scheduling.
         info!("processing Transactions {}", txs.len());
-        let results: Vec<_> = txs.into_par_iter()
-            .map(|tx| self.apply_debits(&tx).map(|_| tx))
+        let mut debits: Vec<(usize, Result<()>)> = Self::group_by_debit_account(&txs)
+            .into_par_iter()
+            .flat_map(|group| {
+                let mut unsettled_nonces = HashSet::new();
+                group
+                    .into_iter()
+                    .map(|i| (i, self.apply_debits(&txs[i], &mut unsettled_nonces)))
+                    .collect::<Vec<_>>()
+            })
             .collect(); // Calling collect() here forces all debits to complete before moving on.
+        debits.sort_by_key(|(i, _)| *i);
+
+        let mut results: Vec<_> = txs.into_iter()
+            .zip(debits)
+            .map(|(tx, (_, result))| result.map(|_| tx))
+            .collect();
+
+        let (overflowed, rejected) = rayon::join(
+            || {
+                results
+                    .par_iter()
+                    .enumerate()
+                    .filter_map(|(i, result)| result.as_ref().ok().map(|tx| (i, tx)))
+                    .filter(|(_, tx)| Self::is_commutative_credit(tx))
+                    .filter_map(|(i, tx)| self.apply_credits_or_reject(tx).err().map(|e| (i, e)))
+                    .collect::<Vec<_>>()
+            },
+            || {
+                results
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(i, result)| result.as_ref().ok().map(|tx| (i, tx)))
+                    .filter(|(_, tx)| !Self::is_commutative_credit(tx))
+                    .filter_map(|(i, tx)| self.apply_credits_or_reject(tx).err().map(|e| (i, e)))
+                    .collect::<Vec<_>>()
+            },
+        );
+        for (i, err) in overflowed.into_iter().chain(rejected) {
+            results[i] = Err(err);
+        }
 
         results
-            .into_par_iter()
-            .map(|result| {
-                result.map(|tx| {
-                    self.apply_credits(&tx);
-                    tx
-                })
-            })
-            .collect()
     }
 
     pub fn process_entries<I>(&self, entries: I) -> Result<()>
@@ -265,109 +1008,266 @@ impl Bank {
         I: IntoIterator<Item = Entry>,
     {
         for entry in entries {
+            let fees =
+                Self::total_fees(&entry.transactions).ok_or(BankError::SupplyViolation)?;
+            let is_tick = entry.transactions.is_empty();
             for result in self.process_transactions(entry.transactions) {
                 result?;
             }
-            self.register_entry_id(&entry.id);
+            self.update_state_hash();
+            self.register_entry_id_with_fees(&entry.id, fees, is_tick);
         }
         Ok(())
     }
 
-    /// Process a Witness Signature.
+    /// Process a Witness Signature. A signer may approve a plan only once.
     fn apply_signature(&self, from: PublicKey, tx_sig: Signature) -> Result<()> {
+        self.apply_witness_to_plan(tx_sig, &Witness::Signature(from))
+    }
+
+    /// Process a Witness Preimage. Anyone may reveal it.
+    fn apply_preimage(&self, tx_sig: Signature, preimage: Vec<u8>) -> Result<()> {
+        self.apply_witness_to_plan(tx_sig, &Witness::Preimage(preimage))
+    }
+
+    /// Process a Witness TickHeight. Anyone may send it, but it fails unless
+    /// the bank has registered at least `height` ticks. Since a leader
+    /// registers ticks no sooner than they land in the ledger, a validator
+    /// replaying the ledger accepts whatever its leader accepted.
+    fn apply_tick_height(&self, tx_sig: Signature, height: u64) -> Result<()> {
+        if height > self.tick_height() {
+            return Err(BankError::TickHeightNotReached(height));
+        }
+        self.apply_witness_to_plan(tx_sig, &Witness::TickHeight(height))
+    }
+
+    /// Apply a Witness to the pending plan created by the transaction with
+    /// signature `tx_sig`, and pay out the plan if it's been reduced.
+    fn apply_witness_to_plan(&self, tx_sig: Signature, witness: &Witness) -> Result<()> {
         if let Occupied(mut e) = self.pending
             .write()
-            .expect("write() in apply_signature")
+            .expect("write() in apply_witness_to_plan")
             .entry(tx_sig)
         {
-            e.get_mut().apply_witness(&Witness::Signature(from));
-            if let Some(payment) = e.get().final_payment() {
-                self.apply_payment(&payment);
+            self.mark_plan_dirty(&tx_sig, Some(Self::plan_hash(&tx_sig, e.get())));
+            let old_deadline = e.get().deadline();
+            let last_time = *self.last_time
+                .read()
+                .expect("'last_time' read lock in apply_witness_to_plan");
+            let (done, payments) = Self::witness_contract(e.get_mut(), witness, last_time)?;
+            self.apply_payments(&payments)?;
+            if done {
+                self.update_deadline(&tx_sig, old_deadline, None);
                 e.remove_entry();
+            } else {
+                self.update_deadline(&tx_sig, old_deadline, e.get().deadline());
             }
         };
 
         Ok(())
     }
 
-    /// Process a Witness Timestamp.
-    fn apply_timestamp(&self, from: PublicKey, dt: DateTime<Utc>) -> Result<()> {
-        // If this is the first timestamp we've seen, it probably came from the genesis block,
-        // so we'll trust it.
-        if *self.last_time
-            .read()
-            .expect("'last_time' read lock on first timestamp check")
-            == Utc.timestamp(0, 0)
-        {
-            self.time_sources
-                .write()
-                .expect("'time_sources' write lock on first timestamp")
-                .insert(from);
+    /// Apply a Witness to a contract, and settle it. A signer may approve a
+    /// plan only once.
+    fn witness_contract(
+        contract: &mut PendingContract,
+        witness: &Witness,
+        last_time: DateTime<Utc>,
+    ) -> Result<(bool, Vec<Payment>)> {
+        if contract.plan.is_duplicate_witness(witness) {
+            if let Witness::Signature(from) = witness {
+                return Err(BankError::DuplicateApproval(*from));
+            }
         }
+        contract.plan.apply_witness(witness);
+        Self::settle_contract(contract, last_time)
+    }
 
-        if self.time_sources
-            .read()
-            .expect("'time_sources' read lock")
-            .contains(&from)
-        {
-            if dt > *self.last_time.read().expect("'last_time' read lock") {
-                *self.last_time.write().expect("'last_time' write lock") = dt;
-            }
-        } else {
-            return Ok(());
+    /// Take whatever the contract's plan has released, or the refund to the
+    /// sender if the contract expired first. Return true if the contract
+    /// is done and should no longer be pending, along with the payments
+    /// to make.
+    fn settle_contract(
+        contract: &mut PendingContract,
+        last_time: DateTime<Utc>,
+    ) -> Result<(bool, Vec<Payment>)> {
+        if let Some(payments) = contract.plan.final_payments() {
+            return Ok((true, payments));
         }
 
-        // Check to see if any timelocked transactions can be completed.
-        let mut completed = vec![];
+        let mut payments = contract.plan.take_due_payments();
+        for payment in &payments {
+            contract.tokens = contract
+                .tokens
+                .checked_sub(payment.tokens)
+                .ok_or(BankError::SupplyViolation)?;
+        }
 
-        // Hold 'pending' write lock until the end of this function. Otherwise another thread can
-        // double-spend if it enters before the modified plan is removed from 'pending'.
-        let mut pending = self.pending
-            .write()
-            .expect("'pending' write lock in apply_timestamp");
-        for (key, plan) in pending.iter_mut() {
-            plan.apply_witness(&Witness::Timestamp(*self.last_time
-                .read()
-                .expect("'last_time' read lock when creating timestamp")));
-            if let Some(payment) = plan.final_payment() {
-                self.apply_payment(&payment);
-                completed.push(key.clone());
+        match contract.expires {
+            Some(expires) if expires <= last_time => {
+                payments.push(Payment {
+                    tokens: contract.tokens,
+                    to: contract.from,
+                });
+                Ok((true, payments))
             }
+            _ => Ok((false, payments)),
         }
+    }
 
-        for key in completed {
-            pending.remove(&key);
+    /// Move a pending contract within the deadline index.
+    fn update_deadline(
+        &self,
+        sig: &Signature,
+        old: Option<DateTime<Utc>>,
+        new: Option<DateTime<Utc>>,
+    ) {
+        if old == new {
+            return;
+        }
+        let mut deadlines = self.deadlines
+            .lock()
+            .expect("'deadlines' lock in update_deadline");
+        if let Some(dt) = old {
+            deadlines.remove(&(dt, *sig));
+        }
+        if let Some(dt) = new {
+            deadlines.insert((dt, *sig));
         }
+    }
 
-        Ok(())
+    /// Return the latest time that a majority of the time sources have
+    /// reached. With a single source, that's the time it last reported.
+    fn majority_time(reports: &HashMap<PublicKey, DateTime<Utc>>) -> Option<DateTime<Utc>> {
+        let mut times: Vec<_> = reports.values().cloned().collect();
+        times.sort_by(|a, b| b.cmp(a));
+        times.get(times.len() / 2).cloned()
     }
 
-    /// Create, sign, and process a Transaction from `keypair` to `to` of
-    /// `n` tokens where `last_id` is the last Entry ID observed by the client.
-    pub fn transfer(
-        &self,
-        n: i64,
-        keypair: &KeyPair,
-        to: PublicKey,
-        last_id: Hash,
-    ) -> Result<Signature> {
-        let tx = Transaction::new(keypair, to, n, last_id);
-        let sig = tx.sig;
-        self.process_transaction(&tx).map(|_| sig)
+    /// Record a time source's vote to replace the set of time sources. Votes
+    /// from anyone else are ignored. The new set takes effect once a majority
+    /// of the current sources have voted for it. Sources that stay in the set
+    /// keep their latest reports.
+    fn apply_time_source_vote(&self, from: PublicKey, sources: &[PublicKey]) {
+        let mut time_sources = self.time_sources
+            .write()
+            .expect("'time_sources' write lock in apply_time_source_vote");
+        if !time_sources.contains_key(&from) || sources.is_empty() {
+            return;
+        }
+        let mut proposal = sources.to_vec();
+        proposal.sort();
+        proposal.dedup();
+
+        let mut votes = self.time_source_votes
+            .write()
+            .expect("'time_source_votes' write lock in apply_time_source_vote");
+        votes.insert(from, proposal.clone());
+        let num_votes = votes.values().filter(|vote| **vote == proposal).count();
+        if num_votes * 2 > time_sources.len() {
+            let new_sources = proposal
+                .iter()
+                .map(|key| {
+                    let report = time_sources.get(key).cloned();
+                    (*key, report.unwrap_or_else(|| Utc.timestamp(0, 0)))
+                })
+                .collect();
+            *time_sources = new_sources;
+            votes.clear();
+        }
     }
 
-    /// Create, sign, and process a postdated Transaction from `keypair`
-    /// to `to` of `n` tokens on `dt` where `last_id` is the last Entry ID
-    /// observed by the client.
-    pub fn transfer_on_date(
-        &self,
+    /// Process a Witness Timestamp. Only the time sources are trusted, and
+    /// the bank's clock only advances to a time that most of them have
+    /// reported, so that a minority can't unlock contracts early.
+    fn apply_timestamp(&self, from: PublicKey, dt: DateTime<Utc>) -> Result<()> {
+        {
+            let mut time_sources = self.time_sources
+                .write()
+                .expect("'time_sources' write lock in apply_timestamp");
+            match time_sources.get_mut(&from) {
+                Some(report) => if dt > *report {
+                    *report = dt;
+                },
+                None => return Ok(()),
+            }
+            if let Some(majority_time) = Self::majority_time(&time_sources) {
+                let mut last_time = self.last_time.write().expect("'last_time' write lock");
+                if majority_time > *last_time {
+                    *last_time = majority_time;
+                }
+            }
+        }
+
+        // Hold 'pending' write lock until the end of this function. Otherwise another thread can
+        // double-spend if it enters before the modified plan is removed from 'pending'.
+        let mut pending = self.pending
+            .write()
+            .expect("'pending' write lock in apply_timestamp");
+        let last_time = *self.last_time
+            .read()
+            .expect("'last_time' read lock when creating timestamp");
+
+        // Only the contracts whose deadlines have passed can be completed. Each
+        // is visited once per timestamp, even if its new deadline has passed too.
+        let due: Vec<_> = self.deadlines
+            .lock()
+            .expect("'deadlines' lock in apply_timestamp")
+            .iter()
+            .take_while(|(dt, _)| *dt <= last_time)
+            .cloned()
+            .collect();
+
+        for (dt, sig) in due {
+            let done = match pending.get_mut(&sig) {
+                Some(contract) => {
+                    self.mark_plan_dirty(&sig, Some(Self::plan_hash(&sig, contract)));
+                    contract.plan.apply_witness(&Witness::Timestamp(last_time));
+                    let (done, payments) = Self::settle_contract(contract, last_time)?;
+                    self.apply_payments(&payments)?;
+                    let new_deadline = if done { None } else { contract.deadline() };
+                    self.update_deadline(&sig, Some(dt), new_deadline);
+                    done
+                }
+                None => {
+                    self.update_deadline(&sig, Some(dt), None);
+                    false
+                }
+            };
+            if done {
+                pending.remove(&sig);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Create, sign, and process a Transaction from `keypair` to `to` of
+    /// `n` tokens where `last_id` is the last Entry ID observed by the client.
+    pub fn transfer(
+        &self,
+        n: i64,
+        keypair: &KeyPair,
+        to: PublicKey,
+        last_id: Hash,
+    ) -> Result<Signature> {
+        let tx = Transaction::new(keypair, to, n, self.genesis, last_id);
+        let sig = tx.sig;
+        self.process_transaction(&tx).map(|_| sig)
+    }
+
+    /// Create, sign, and process a postdated Transaction from `keypair`
+    /// to `to` of `n` tokens on `dt` where `last_id` is the last Entry ID
+    /// observed by the client.
+    pub fn transfer_on_date(
+        &self,
         n: i64,
         keypair: &KeyPair,
         to: PublicKey,
         dt: DateTime<Utc>,
         last_id: Hash,
     ) -> Result<Signature> {
-        let tx = Transaction::new_on_date(keypair, to, dt, n, last_id);
+        let tx = Transaction::new_on_date(keypair, to, dt, n, self.genesis, last_id);
         let sig = tx.sig;
         self.process_transaction(&tx).map(|_| sig)
     }
@@ -379,17 +1279,307 @@ impl Bank {
         bals.get(pubkey).map(|x| x.load(Ordering::Relaxed) as i64)
     }
 
+    /// Return the current nonce of `pubkey`, if it has one.
+    pub fn get_nonce(&self, pubkey: &PublicKey) -> Option<Hash> {
+        self.nonces
+            .read()
+            .expect("'nonces' read lock in get_nonce")
+            .get(pubkey)
+            .cloned()
+    }
+
     pub fn transaction_count(&self) -> usize {
         self.transaction_count.load(Ordering::Relaxed)
     }
+
+    /// Return the number of tokens held by every balance and pending
+    /// contract. Only meaningful between batches of transactions, since
+    /// debits are applied before their credits. Fails if the total doesn't
+    /// fit in an i64, which can only happen if tokens were created.
+    pub fn total_supply(&self) -> Result<i64> {
+        let pending = self.pending
+            .read()
+            .expect("'pending' read lock in total_supply");
+        let bals = self.balances
+            .read()
+            .expect("'balances' read lock in total_supply");
+        let mut total = 0i64;
+        for contract in pending.values() {
+            total = total
+                .checked_add(contract.tokens)
+                .ok_or(BankError::TokenOverflow(contract.from))?;
+        }
+        for (key, bal) in bals.iter() {
+            total = total
+                .checked_add(bal.load(Ordering::Relaxed) as i64)
+                .ok_or(BankError::TokenOverflow(*key))?;
+        }
+        Ok(total)
+    }
+
+    /// Check that no tokens were created or destroyed since genesis.
+    pub fn verify_supply(&self) -> Result<()> {
+        if self.total_supply()? == self.supply {
+            Ok(())
+        } else {
+            Err(BankError::SupplyViolation)
+        }
+    }
+
+    /// Return the number of ticks registered so far.
+    pub fn tick_height(&self) -> u64 {
+        self.tick_height.load(Ordering::Relaxed) as u64
+    }
+
+    /// Return what the bank knows about the transaction with signature `sig`.
+    /// Failed transactions never reach the ledger, so only the bank that
+    /// processed them, the leader's, reports them as `Failed`. Validators
+    /// report `NotFound` instead.
+    pub fn get_signature_status(&self, sig: &Signature) -> SignatureStatus {
+        let result = self.last_ids
+            .read()
+            .expect("'last_ids' read lock in get_signature_status")
+            .iter()
+            .rev()
+            .filter_map(|(_, sigs)| {
+                sigs.read()
+                    .expect("'signatures' read lock in get_signature_status")
+                    .get(sig)
+                    .cloned()
+            })
+            .next();
+        match result {
+            Some(Ok(())) => if self.pending
+                .read()
+                .expect("'pending' read lock in get_signature_status")
+                .contains_key(sig)
+            {
+                SignatureStatus::PendingWitness
+            } else {
+                SignatureStatus::Confirmed
+            },
+            Some(Err(err)) => SignatureStatus::Failed(err),
+            None => SignatureStatus::NotFound,
+        }
+    }
+
+    /// Return the contracts waiting on witnesses that were sent by `pubkey`,
+    /// may pay it, or can be witnessed by it, ordered by signature.
+    pub fn get_pending_contracts(&self, pubkey: &PublicKey) -> Vec<(Signature, PendingContract)> {
+        let mut contracts: Vec<_> = self.pending
+            .read()
+            .expect("'pending' read lock in get_pending_contracts")
+            .iter()
+            .filter(|(_, contract)| contract.from == *pubkey || contract.plan.involves(pubkey))
+            .map(|(sig, contract)| (*sig, contract.clone()))
+            .collect();
+        contracts.sort_by(|a, b| a.0.cmp(&b.0));
+        contracts
+    }
+
+    /// Return the state hash as of the last entry, along with the transaction
+    /// count when it was computed. Nodes that processed the same transactions
+    /// report the same hash for a given count.
+    pub fn state_hash(&self) -> (Hash, u64) {
+        *self.state_hash.read().expect("'state_hash' read lock")
+    }
+
+    /// Fold the balances, plans and nonces changed since the last call into
+    /// the state hash. Call once the transactions of an Entry have been processed, and
+    /// before processing the next one.
+    pub fn update_state_hash(&self) -> Hash {
+        // Acquire 'pending' before 'balances', same as apply_timestamp.
+        let pending = self.pending
+            .read()
+            .expect("'pending' read lock in update_state_hash");
+        let balances = self.balances
+            .read()
+            .expect("'balances' read lock in update_state_hash");
+        let nonces = self.nonces
+            .read()
+            .expect("'nonces' read lock in update_state_hash");
+        let mut state_hash = self.state_hash
+            .write()
+            .expect("'state_hash' write lock in update_state_hash");
+
+        let mut dirty_balances = self.dirty_balances
+            .lock()
+            .expect("'dirty_balances' lock in update_state_hash");
+        for (key, old) in dirty_balances.drain() {
+            if let Some(old) = old {
+                Self::xor_hash(&mut state_hash.0, &Self::balance_hash(&key, old));
+            }
+            if let Some(bal) = balances.get(&key) {
+                let tokens = bal.load(Ordering::Relaxed) as i64;
+                Self::xor_hash(&mut state_hash.0, &Self::balance_hash(&key, tokens));
+            }
+        }
+
+        let mut dirty_pending = self.dirty_pending
+            .lock()
+            .expect("'dirty_pending' lock in update_state_hash");
+        for (sig, old) in dirty_pending.drain() {
+            if let Some(old) = old {
+                Self::xor_hash(&mut state_hash.0, &old);
+            }
+            if let Some(contract) = pending.get(&sig) {
+                Self::xor_hash(&mut state_hash.0, &Self::plan_hash(&sig, contract));
+            }
+        }
+
+        let mut dirty_nonces = self.dirty_nonces
+            .lock()
+            .expect("'dirty_nonces' lock in update_state_hash");
+        for (key, old) in dirty_nonces.drain() {
+            if let Some(old) = old {
+                Self::xor_hash(&mut state_hash.0, &Self::nonce_hash(&key, &old));
+            }
+            if let Some(nonce) = nonces.get(&key) {
+                Self::xor_hash(&mut state_hash.0, &Self::nonce_hash(&key, nonce));
+            }
+        }
+
+        state_hash.1 = self.transaction_count() as u64;
+        state_hash.0
+    }
+
+    /// Hash every balance, pending plan and nonce from scratch.
+    fn compute_state_hash(&self) -> Hash {
+        let pending = self.pending
+            .read()
+            .expect("'pending' read lock in compute_state_hash");
+        let balances = self.balances
+            .read()
+            .expect("'balances' read lock in compute_state_hash");
+        let nonces = self.nonces
+            .read()
+            .expect("'nonces' read lock in compute_state_hash");
+        let mut state_hash = Hash::default();
+        for (key, bal) in balances.iter() {
+            let tokens = bal.load(Ordering::Relaxed) as i64;
+            Self::xor_hash(&mut state_hash, &Self::balance_hash(key, tokens));
+        }
+        for (sig, contract) in pending.iter() {
+            Self::xor_hash(&mut state_hash, &Self::plan_hash(sig, contract));
+        }
+        for (key, nonce) in nonces.iter() {
+            Self::xor_hash(&mut state_hash, &Self::nonce_hash(key, nonce));
+        }
+        state_hash
+    }
+
+    /// Return the total fees paid by the transactions in the Entry with ID
+    /// `id`, or None if that Entry isn't one of the last MAX_ENTRY_IDS.
+    pub fn get_entry_fees(&self, id: &Hash) -> Option<i64> {
+        self.entry_fees
+            .read()
+            .expect("'entry_fees' read lock in get_entry_fees")
+            .get(id)
+            .cloned()
+    }
+
+    /// Copy the bank's state into a Snapshot. The caller is responsible for
+    /// not processing transactions concurrently, so that the snapshot lands
+    /// on an entry boundary. Collections are sorted so that equal states
+    /// produce identical snapshots.
+    pub fn snapshot(&self) -> Snapshot {
+        // Acquire 'pending' before 'balances', same as apply_timestamp.
+        let pending = self.pending.read().expect("'pending' read lock in snapshot");
+        let balances = self.balances
+            .read()
+            .expect("'balances' read lock in snapshot");
+        let last_ids = self.last_ids
+            .read()
+            .expect("'last_ids' read lock in snapshot");
+
+        let mut balances: Vec<_> = balances
+            .iter()
+            .map(|(key, bal)| (*key, bal.load(Ordering::Relaxed) as i64))
+            .collect();
+        balances.sort();
+
+        let mut pending: Vec<_> = pending
+            .iter()
+            .map(|(sig, contract)| (*sig, contract.clone()))
+            .collect();
+        pending.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let entry_fees = {
+            let fees = self.entry_fees
+                .read()
+                .expect("'entry_fees' read lock in snapshot");
+            last_ids
+                .iter()
+                .filter_map(|(id, _)| fees.get(id).map(|fee| (*id, *fee)))
+                .collect()
+        };
+
+        let last_ids = last_ids
+            .iter()
+            .map(|(id, sigs)| {
+                let mut sigs: Vec<_> = sigs.read()
+                    .expect("'signatures' read lock in snapshot")
+                    .iter()
+                    .map(|(sig, result)| (*sig, result.clone()))
+                    .collect();
+                sigs.sort_by(|a, b| a.0.cmp(&b.0));
+                (*id, sigs)
+            })
+            .collect();
+
+        let mut time_sources: Vec<_> = self.time_sources
+            .read()
+            .expect("'time_sources' read lock in snapshot")
+            .iter()
+            .map(|(key, report)| (*key, *report))
+            .collect();
+        time_sources.sort();
+
+        let mut time_source_votes: Vec<_> = self.time_source_votes
+            .read()
+            .expect("'time_source_votes' read lock in snapshot")
+            .iter()
+            .map(|(key, vote)| (*key, vote.clone()))
+            .collect();
+        time_source_votes.sort();
+
+        let mut nonces: Vec<_> = self.nonces
+            .read()
+            .expect("'nonces' read lock in snapshot")
+            .iter()
+            .map(|(key, nonce)| (*key, *nonce))
+            .collect();
+        nonces.sort();
+
+        Snapshot {
+            genesis: self.genesis,
+            balances,
+            pending,
+            last_ids,
+            time_sources,
+            time_source_votes,
+            last_time: *self.last_time.read().expect("'last_time' read lock in snapshot"),
+            transaction_count: self.transaction_count() as u64,
+            tick_height: self.tick_height(),
+            fee_collector: self.fee_collector(),
+            entry_fees,
+            nonces,
+            supply: self.supply,
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use bincode::serialize;
+    use bincode::{deserialize, serialize};
+    use budget::Budget;
+    use chrono::Duration;
+    use entry::next_entry;
     use hash::hash;
+    use ledger::next_entries;
     use signature::KeyPairUtil;
+    use vesting::Vesting;
 
     #[test]
     fn test_bank() {
@@ -430,6 +1620,14 @@ mod tests {
             Err(BankError::AccountNotFound(keypair.pubkey()))
         );
         assert_eq!(bank.transaction_count(), 0);
+
+        // An unfunded sender costs the bank nothing, not even a status.
+        let tx = Transaction::new(&keypair, mint.pubkey(), 1, mint.seed(), mint.last_id());
+        assert_eq!(
+            bank.process_transactions(vec![tx.clone()])[0],
+            Err(BankError::AccountNotFound(keypair.pubkey()))
+        );
+        assert_eq!(bank.get_signature_status(&tx.sig), SignatureStatus::NotFound);
     }
 
     #[test]
@@ -451,6 +1649,31 @@ mod tests {
         assert_eq!(bank.get_balance(&pubkey).unwrap(), 1_000);
     }
 
+    #[test]
+    fn test_wrong_genesis() {
+        // Two ledgers that share a last_id, but not a genesis.
+        let mint = Mint::new(1);
+        let other_mint = Mint::new(1);
+        let bank = Bank::new(&mint);
+        assert_eq!(bank.genesis(), mint.seed());
+        let other_genesis = other_mint.seed();
+        bank.register_entry_id(&other_mint.last_id());
+
+        let pubkey = KeyPair::new().pubkey();
+        let tx = Transaction::new(&mint.keypair(), pubkey, 1, other_genesis, mint.last_id());
+        assert_eq!(
+            bank.process_transaction(&tx),
+            Err(BankError::WrongGenesis(other_genesis))
+        );
+        assert_eq!(bank.get_signature_status(&tx.sig), SignatureStatus::NotFound);
+        assert_eq!(bank.get_balance(&pubkey), None);
+        assert_eq!(bank.transaction_count(), 0);
+
+        let tx = Transaction::new(&mint.keypair(), pubkey, 1, mint.seed(), mint.last_id());
+        bank.process_transaction(&tx).unwrap();
+        assert_eq!(bank.get_balance(&pubkey), Some(1));
+    }
+
     #[test]
     fn test_transfer_to_newb() {
         let mint = Mint::new(10_000);
@@ -461,6 +1684,193 @@ mod tests {
         assert_eq!(bank.get_balance(&pubkey).unwrap(), 500);
     }
 
+    #[test]
+    fn test_hash_time_locked_payment() {
+        let mint = Mint::new(2);
+        let bank = Bank::new(&mint);
+        let keypair = KeyPair::new();
+        let pubkey = keypair.pubkey();
+        let dt = Utc::now();
+        let preimage = b"secret".to_vec();
+
+        let tx = Transaction::new_hash_time_locked(
+            &mint.keypair(),
+            pubkey,
+            hash(&preimage),
+            dt,
+            1,
+            mint.seed(),
+            mint.last_id(),
+        );
+        bank.process_transaction(&tx).unwrap();
+        assert_eq!(bank.get_balance(&mint.pubkey()), Some(1));
+        assert_eq!(bank.get_balance(&pubkey), None);
+
+        // The wrong preimage doesn't unlock the tokens.
+        let guess = b"guess".to_vec();
+        let wrong = Transaction::new_preimage(
+            &mint.keypair(),
+            tx.sig,
+            guess,
+            mint.seed(),
+            mint.last_id(),
+        );
+        bank.process_transaction(&wrong).unwrap();
+        assert_eq!(bank.get_balance(&pubkey), None);
+
+        // Anyone holding the preimage can reveal it to release the tokens.
+        let claim = Transaction::new_preimage(
+            &mint.keypair(),
+            tx.sig,
+            preimage,
+            mint.seed(),
+            mint.last_id(),
+        );
+        bank.process_transaction(&claim).unwrap();
+        assert_eq!(bank.get_balance(&pubkey), Some(1));
+        assert_eq!(bank.get_balance(&mint.pubkey()), Some(1));
+
+        // Once claimed, the timeout has no effect.
+        bank.apply_timestamp(mint.pubkey(), dt).unwrap();
+        assert_eq!(bank.get_balance(&mint.pubkey()), Some(1));
+        assert_ne!(bank.get_signature_status(&tx.sig), SignatureStatus::PendingWitness);
+    }
+
+    #[test]
+    fn test_hash_time_locked_refund() {
+        let mint = Mint::new(2);
+        let bank = Bank::new(&mint);
+        let keypair = KeyPair::new();
+        let pubkey = keypair.pubkey();
+        let dt = Utc::now();
+        let preimage = b"secret".to_vec();
+
+        let tx = Transaction::new_hash_time_locked(
+            &mint.keypair(),
+            pubkey,
+            hash(&preimage),
+            dt,
+            1,
+            mint.seed(),
+            mint.last_id(),
+        );
+        bank.process_transaction(&tx).unwrap();
+        assert_eq!(bank.get_balance(&mint.pubkey()), Some(1));
+
+        // The timeout refunds the sender.
+        bank.apply_timestamp(mint.pubkey(), dt).unwrap();
+        assert_eq!(bank.get_balance(&mint.pubkey()), Some(2));
+
+        // Revealing the preimage too late pays nothing.
+        let claim = Transaction::new_preimage(
+            &mint.keypair(),
+            tx.sig,
+            preimage,
+            mint.seed(),
+            mint.last_id(),
+        );
+        bank.process_transaction(&claim).unwrap();
+        assert_eq!(bank.get_balance(&pubkey), None);
+        assert_eq!(bank.get_balance(&mint.pubkey()), Some(2));
+    }
+
+    #[test]
+    fn test_multisig_payment() {
+        let mint = Mint::new(10);
+        let bank = Bank::new(&mint);
+        let officers: Vec<_> = (0..3).map(|_| KeyPair::new()).collect();
+        let signers = officers.iter().map(|keypair| keypair.pubkey()).collect();
+        let pubkey = KeyPair::new().pubkey();
+
+        // Fund the officers so they can sign witness transactions.
+        for officer in &officers {
+            bank.transfer(1, &mint.keypair(), officer.pubkey(), mint.last_id())
+                .unwrap();
+        }
+
+        let tx = Transaction::new_multisig(
+            &mint.keypair(),
+            pubkey,
+            2,
+            signers,
+            5,
+            mint.seed(),
+            mint.last_id(),
+        );
+        assert!(tx.verify_plan());
+        bank.process_transaction(&tx).unwrap();
+
+        let approval = Transaction::new_signature(
+            &officers[0],
+            tx.sig,
+            mint.seed(),
+            mint.last_id(),
+        );
+        bank.process_transaction(&approval).unwrap();
+        assert_eq!(bank.get_balance(&pubkey), None);
+        assert_eq!(
+            bank.get_signature_status(&tx.sig),
+            SignatureStatus::PendingWitness
+        );
+
+        // A second approval from the same officer is rejected.
+        let last_id = hash(b"next entry");
+        bank.register_entry_id(&last_id);
+        let duplicate = Transaction::new_signature(&officers[0], tx.sig, mint.seed(), last_id);
+        let err = BankError::DuplicateApproval(officers[0].pubkey());
+        assert_eq!(bank.process_transaction(&duplicate), Err(err.clone()));
+        assert_eq!(
+            bank.get_signature_status(&duplicate.sig),
+            SignatureStatus::Failed(err)
+        );
+        assert_eq!(bank.transaction_count(), 5);
+        assert_eq!(bank.get_balance(&pubkey), None);
+
+        let approval = Transaction::new_signature(
+            &officers[2],
+            tx.sig,
+            mint.seed(),
+            mint.last_id(),
+        );
+        bank.process_transaction(&approval).unwrap();
+        assert_eq!(bank.get_balance(&pubkey), Some(5));
+        assert_eq!(bank.get_signature_status(&tx.sig), SignatureStatus::Confirmed);
+    }
+
+    #[test]
+    fn test_split_payment() {
+        let mint = Mint::new(10);
+        let bank = Bank::new(&mint);
+        let alice = KeyPair::new().pubkey();
+        let bob = KeyPair::new().pubkey();
+        let payments = vec![
+            Payment {
+                tokens: 3,
+                to: alice,
+            },
+            Payment { tokens: 4, to: bob },
+            Payment {
+                tokens: 1,
+                to: alice,
+            },
+        ];
+        let tx = Transaction::new_split(&mint.keypair(), payments, mint.seed(), mint.last_id());
+        assert!(tx.verify_plan());
+        bank.process_transaction(&tx).unwrap();
+        assert_eq!(bank.get_balance(&mint.pubkey()), Some(2));
+        assert_eq!(bank.get_balance(&alice), Some(4));
+        assert_eq!(bank.get_balance(&bob), Some(4));
+
+        // Either every recipient is paid, or none are.
+        let payments = vec![Payment { tokens: 2, to: bob }, Payment { tokens: 1, to: bob }];
+        let tx = Transaction::new_split(&mint.keypair(), payments, mint.seed(), mint.last_id());
+        assert_eq!(
+            bank.process_transaction(&tx),
+            Err(BankError::InsufficientFunds(mint.pubkey()))
+        );
+        assert_eq!(bank.get_balance(&bob), Some(4));
+    }
+
     #[test]
     fn test_transfer_on_date() {
         let mint = Mint::new(1);
@@ -494,49 +1904,762 @@ mod tests {
     }
 
     #[test]
-    fn test_transfer_after_date() {
-        let mint = Mint::new(1);
+    fn test_vesting_payment() {
+        let mint = Mint::new(10);
         let bank = Bank::new(&mint);
         let pubkey = KeyPair::new().pubkey();
-        let dt = Utc::now();
-        bank.apply_timestamp(mint.pubkey(), dt).unwrap();
+        let cliff = Utc::now();
+        let day = Duration::days(1);
+        let vesting = Vesting::new(pubkey, 10, cliff, 4, day, 3);
+        let tx = Transaction::new_vesting(&mint.keypair(), vesting, mint.seed(), mint.last_id());
+        bank.process_transaction(&tx).unwrap();
+        assert_eq!(bank.get_balance(&mint.pubkey()), Some(0));
+        assert_eq!(bank.get_balance(&pubkey), None);
 
-        // It's now past now, so this transfer should be processed immediately.
-        bank.transfer_on_date(1, &mint.keypair(), pubkey, dt, mint.last_id())
-            .unwrap();
+        // The cliff releases its tranche, and the rest stays pending.
+        bank.apply_timestamp(mint.pubkey(), cliff).unwrap();
+        assert_eq!(bank.get_balance(&pubkey), Some(4));
+        assert_eq!(
+            bank.get_signature_status(&tx.sig),
+            SignatureStatus::PendingWitness
+        );
 
-        assert_eq!(bank.get_balance(&mint.pubkey()), Some(0));
-        assert_eq!(bank.get_balance(&pubkey), Some(1));
+        // Replaying a timestamp doesn't release the tranche twice.
+        bank.apply_timestamp(mint.pubkey(), cliff).unwrap();
+        assert_eq!(bank.get_balance(&pubkey), Some(4));
+
+        bank.apply_timestamp(mint.pubkey(), cliff + day).unwrap();
+        assert_eq!(bank.get_balance(&pubkey), Some(6));
+
+        bank.apply_timestamp(mint.pubkey(), cliff + day * 3).unwrap();
+        assert_eq!(bank.get_balance(&pubkey), Some(10));
+        assert_eq!(bank.get_signature_status(&tx.sig), SignatureStatus::Confirmed);
     }
 
     #[test]
-    fn test_cancel_transfer() {
-        let mint = Mint::new(1);
+    fn test_contract_expiry() {
+        let mint = Mint::new(2);
         let bank = Bank::new(&mint);
+        let signer = KeyPair::new().pubkey();
         let pubkey = KeyPair::new().pubkey();
         let dt = Utc::now();
-        let sig = bank.transfer_on_date(1, &mint.keypair(), pubkey, dt, mint.last_id())
-            .unwrap();
-
-        // Assert the debit counts as a transaction.
-        assert_eq!(bank.transaction_count(), 1);
-
-        // Mint's balance will be zero because all funds are locked up.
-        assert_eq!(bank.get_balance(&mint.pubkey()), Some(0));
+        bank.apply_timestamp(mint.pubkey(), dt).unwrap();
 
-        // pubkey's balance will be None because the funds have not been
-        // sent.
-        assert_eq!(bank.get_balance(&pubkey), None);
+        // The signer never shows up, so the contract expires.
+        let budget = Budget::new_authorized_payment(signer, 1, pubkey);
+        let expires = dt + Duration::days(1);
+        let tx = Transaction::new_expiring_budget(
+            &mint.keypair(),
+            budget.clone(),
+            1,
+            Some(expires),
+            mint.seed(),
+            mint.last_id(),
+        );
+        bank.process_transaction(&tx).unwrap();
+        assert_eq!(bank.get_balance(&mint.pubkey()), Some(1));
+        assert_eq!(bank.get_pending_contracts(&signer).len(), 1);
 
-        // Now, cancel the trancaction. Mint gets her funds back, pubkey never sees them.
-        bank.apply_signature(mint.pubkey(), sig).unwrap();
+        bank.apply_timestamp(mint.pubkey(), dt + Duration::hours(1))
+            .unwrap();
         assert_eq!(bank.get_balance(&mint.pubkey()), Some(1));
-        assert_eq!(bank.get_balance(&pubkey), None);
 
-        // Assert cancel doesn't cause count to go backward.
-        assert_eq!(bank.transaction_count(), 1);
+        bank.apply_timestamp(mint.pubkey(), expires).unwrap();
+        assert_eq!(bank.get_balance(&mint.pubkey()), Some(2));
+        assert_eq!(bank.get_balance(&pubkey), None);
+        assert_eq!(bank.get_signature_status(&tx.sig), SignatureStatus::Confirmed);
+        assert!(bank.deadlines.lock().unwrap().is_empty());
+
+        // A contract that's already expired is refunded right away.
+        let tx = Transaction::new_expiring_budget(
+            &mint.keypair(),
+            budget,
+            1,
+            Some(dt),
+            mint.seed(),
+            mint.last_id(),
+        );
+        bank.process_transaction(&tx).unwrap();
+        assert_eq!(bank.get_balance(&mint.pubkey()), Some(2));
+        assert!(bank.get_pending_contracts(&signer).is_empty());
+    }
 
-        bank.apply_signature(mint.pubkey(), sig).unwrap(); // <-- Attack! Attempt to cancel completed transaction.
+    #[test]
+    fn test_vesting_expiry_refunds_remainder() {
+        let mint = Mint::new(10);
+        let bank = Bank::new(&mint);
+        let pubkey = KeyPair::new().pubkey();
+        let cliff = Utc::now();
+        let day = Duration::days(1);
+        bank.apply_timestamp(mint.pubkey(), cliff - day).unwrap();
+
+        let vesting = Vesting::new(pubkey, 10, cliff, 4, day, 3);
+        let mut tx = Transaction::new_vesting(
+            &mint.keypair(),
+            vesting,
+            mint.seed(),
+            mint.last_id(),
+        );
+        if let Instruction::NewContract(contract) = &mut tx.instruction {
+            contract.expires = Some(cliff + day);
+        }
+        tx.sign(&mint.keypair());
+        bank.process_transaction(&tx).unwrap();
+
+        bank.apply_timestamp(mint.pubkey(), cliff).unwrap();
+        assert_eq!(bank.get_balance(&pubkey), Some(4));
+        assert_eq!(bank.get_pending_contracts(&pubkey)[0].1.tokens, 6);
+
+        // The tranche due at expiry is paid, and the rest is refunded.
+        bank.apply_timestamp(mint.pubkey(), cliff + day).unwrap();
+        assert_eq!(bank.get_balance(&pubkey), Some(6));
+        assert_eq!(bank.get_balance(&mint.pubkey()), Some(4));
+        assert!(bank.get_pending_contracts(&pubkey).is_empty());
+    }
+
+    #[test]
+    fn test_get_pending_contracts() {
+        let mint = Mint::new(2);
+        let bank = Bank::new(&mint);
+        let signer = KeyPair::new().pubkey();
+        let pubkey = KeyPair::new().pubkey();
+        let tx = Transaction::new_budget(
+            &mint.keypair(),
+            Budget::new_authorized_payment(signer, 1, pubkey),
+            1,
+            mint.seed(),
+            mint.last_id(),
+        );
+        bank.process_transaction(&tx).unwrap();
+        bank.transfer(1, &mint.keypair(), pubkey, mint.last_id())
+            .unwrap();
+
+        let contracts = bank.get_pending_contracts(&pubkey);
+        assert_eq!(contracts.len(), 1);
+        assert_eq!(contracts[0].0, tx.sig);
+        assert_eq!(contracts[0].1.from, mint.pubkey());
+        assert_eq!(contracts[0].1.tokens, 1);
+        assert_eq!(bank.get_pending_contracts(&mint.pubkey()), contracts);
+        assert_eq!(bank.get_pending_contracts(&signer), contracts);
+        assert!(bank.get_pending_contracts(&KeyPair::new().pubkey()).is_empty());
+    }
+
+    #[test]
+    fn test_time_sources_from_genesis() {
+        let mut mint = Mint::new(1);
+        let keypairs: Vec<_> = (0..3).map(|_| KeyPair::new()).collect();
+        mint.time_sources = keypairs.iter().map(|keypair| keypair.pubkey()).collect();
+        let bank = Bank::new(&mint);
+        let last_time = || *bank.last_time.read().unwrap();
+        let epoch = Utc.timestamp(0, 0);
+        let dt = Utc::now();
+
+        // The mint isn't a time source, so it can't take over the clock.
+        bank.apply_timestamp(mint.pubkey(), dt).unwrap();
+        assert_eq!(last_time(), epoch);
+
+        // One of three sources isn't a majority.
+        bank.apply_timestamp(keypairs[0].pubkey(), dt).unwrap();
+        assert_eq!(last_time(), epoch);
+
+        // The clock advances to the time a majority of the sources agree on.
+        let later = dt + Duration::hours(1);
+        bank.apply_timestamp(keypairs[1].pubkey(), later).unwrap();
+        assert_eq!(last_time(), dt);
+        bank.apply_timestamp(keypairs[2].pubkey(), later).unwrap();
+        assert_eq!(last_time(), later);
+    }
+
+    #[test]
+    fn test_rotate_time_sources() {
+        let mint = Mint::new(1);
+        let bank = Bank::new(&mint);
+        let keypairs: Vec<_> = (0..3).map(|_| KeyPair::new()).collect();
+        let sources: Vec<_> = keypairs.iter().map(|keypair| keypair.pubkey()).collect();
+        let last_time = || *bank.last_time.read().unwrap();
+        let dt = Utc::now();
+
+        // Votes from anyone but a time source are ignored.
+        let tx = Transaction::new_time_sources(
+            &keypairs[0],
+            sources.clone(),
+            mint.seed(),
+            mint.last_id(),
+        );
+        bank.apply_credits(&tx).unwrap();
+        bank.apply_timestamp(keypairs[0].pubkey(), dt).unwrap();
+        assert_eq!(last_time(), Utc.timestamp(0, 0));
+
+        // The mint is the only source, so its vote is a majority.
+        let tx = Transaction::new_time_sources(
+            &mint.keypair(),
+            sources.clone(),
+            mint.seed(),
+            mint.last_id(),
+        );
+        bank.apply_credits(&tx).unwrap();
+        bank.apply_timestamp(mint.pubkey(), dt).unwrap();
+        assert_eq!(last_time(), Utc.timestamp(0, 0));
+
+        // Rotating to a single source takes two of the three current sources.
+        let new_source = KeyPair::new().pubkey();
+        bank.apply_time_source_vote(sources[0], &[new_source]);
+        bank.apply_timestamp(new_source, dt).unwrap();
+        assert_eq!(last_time(), Utc.timestamp(0, 0));
+        bank.apply_time_source_vote(sources[1], &[new_source]);
+        bank.apply_timestamp(new_source, dt).unwrap();
+        assert_eq!(last_time(), dt);
+    }
+
+    #[test]
+    fn test_tick_height_payment() {
+        let mint = Mint::new(1);
+        let bank = Bank::new(&mint);
+        let pubkey = KeyPair::new().pubkey();
+        let budget = Budget::new_tick_height_payment(2, 1, pubkey);
+        let tx = Transaction::new_budget(&mint.keypair(), budget, 1, mint.seed(), mint.last_id());
+        bank.process_transaction(&tx).unwrap();
+        assert_eq!(bank.get_balance(&pubkey), None);
+
+        // Entries with transactions aren't ticks.
+        let entry = next_entry(&mint.last_id(), 1, vec![]);
+        let tx_entry = next_entry(&entry.id, 1, vec![tx.clone()]);
+        bank.register_entry(&entry);
+        bank.register_entry(&tx_entry);
+        assert_eq!(bank.tick_height(), 1);
+
+        // Nobody can claim a height the bank hasn't reached.
+        let witness = Transaction::new_tick_height(
+            &mint.keypair(),
+            tx.sig,
+            2,
+            mint.seed(),
+            bank.last_id(),
+        );
+        assert_eq!(
+            bank.process_transaction(&witness),
+            Err(BankError::TickHeightNotReached(2))
+        );
+        assert_eq!(bank.get_balance(&pubkey), None);
+
+        let ticks = next_entries(&tx_entry.id, 1, vec![vec![]]);
+        bank.process_entries(ticks).unwrap();
+        assert_eq!(bank.tick_height(), 2);
+        bank.process_transaction(&witness).unwrap();
+        assert_eq!(bank.get_balance(&pubkey), Some(1));
+    }
+
+    #[test]
+    fn test_durable_nonce() {
+        let mint = Mint::new(3);
+        let bank = Bank::new(&mint);
+        let keypair = mint.keypair();
+        let pubkey = KeyPair::new().pubkey();
+        assert_eq!(bank.get_nonce(&mint.pubkey()), None);
+
+        let tx = Transaction::new_advance_nonce(&keypair, mint.seed(), mint.last_id());
+        bank.process_transaction(&tx).unwrap();
+        let nonce = bank.get_nonce(&mint.pubkey()).unwrap();
+
+        // Signed offline, and submitted after its last_id would have expired.
+        let durable = Transaction::new(&keypair, pubkey, 1, mint.seed(), nonce);
+        for i in 0..MAX_ENTRY_IDS {
+            bank.register_entry_id(&hash(&serialize(&i).unwrap()));
+        }
+        bank.process_transaction(&durable).unwrap();
+        assert_eq!(bank.get_balance(&pubkey), Some(1));
+        assert_eq!(bank.get_nonce(&mint.pubkey()), Some(hash(&durable.sig)));
+        assert_eq!(
+            bank.get_signature_status(&durable.sig),
+            SignatureStatus::Confirmed
+        );
+
+        // The nonce has moved on, so the transaction can't be replayed.
+        assert_eq!(
+            bank.process_transaction(&durable),
+            Err(BankError::LastIdNotFound(nonce))
+        );
+        assert_eq!(bank.get_balance(&pubkey), Some(1));
+
+        // Advancing the nonce cancels a transaction signed with it.
+        let nonce = bank.get_nonce(&mint.pubkey()).unwrap();
+        let cancelled = Transaction::new(&keypair, pubkey, 1, mint.seed(), nonce);
+        let tx = Transaction::new_advance_nonce(&keypair, mint.seed(), nonce);
+        bank.process_transaction(&tx).unwrap();
+        assert_eq!(
+            bank.process_transaction(&cancelled),
+            Err(BankError::LastIdNotFound(nonce))
+        );
+        assert_eq!(bank.get_balance(&pubkey), Some(1));
+
+        // Only the account's own transactions may use its nonce.
+        let alice = KeyPair::new();
+        bank.transfer(1, &keypair, alice.pubkey(), bank.last_id())
+            .unwrap();
+        let nonce = bank.get_nonce(&mint.pubkey()).unwrap();
+        let other = Transaction::new(&alice, pubkey, 1, mint.seed(), nonce);
+        assert_eq!(
+            bank.process_transaction(&other),
+            Err(BankError::LastIdNotFound(nonce))
+        );
+        let snapshot = bank.snapshot();
+        assert_eq!(snapshot.nonces, vec![(mint.pubkey(), nonce)]);
+        assert_eq!(Bank::from_snapshot(&snapshot).unwrap().get_nonce(&mint.pubkey()), Some(nonce));
+
+        // A durable transaction that fails leaves the nonce as it was, since
+        // it never reaches the ledger, but its failure is recorded.
+        let failed = Transaction::new(&keypair, pubkey, 5, mint.seed(), nonce);
+        let err = BankError::InsufficientFunds(mint.pubkey());
+        assert_eq!(bank.process_transaction(&failed), Err(err.clone()));
+        assert_eq!(bank.get_nonce(&mint.pubkey()), Some(nonce));
+        assert_eq!(
+            bank.get_signature_status(&failed.sig),
+            SignatureStatus::Failed(err)
+        );
+
+        // So does one whose credits fail.
+        let witness =
+            Transaction::new_tick_height(&keypair, Signature::default(), 100, mint.seed(), nonce);
+        assert_eq!(
+            bank.process_transaction(&witness),
+            Err(BankError::TickHeightNotReached(100))
+        );
+        assert_eq!(bank.get_nonce(&mint.pubkey()), Some(nonce));
+    }
+
+    #[test]
+    fn test_durable_nonce_replay() {
+        let mint = Mint::new(3);
+        let keypair = mint.keypair();
+        let pubkey = KeyPair::new().pubkey();
+        let advance = Transaction::new_advance_nonce(&keypair, mint.seed(), mint.last_id());
+        let nonce = hash(&advance.sig);
+        let sig = Signature::default();
+        let batches = vec![
+            vec![advance],
+            vec![
+                Transaction::new(&keypair, pubkey, 5, mint.seed(), nonce),
+                Transaction::new_tick_height(&keypair, sig, 100, mint.seed(), nonce),
+            ],
+            vec![Transaction::new(&keypair, pubkey, 1, mint.seed(), nonce)],
+        ];
+
+        // The leader drops the transactions that fail, as the banking stage
+        // does, and records the rest.
+        let leader = Bank::new(&mint);
+        let mut entries = vec![];
+        let mut last_id = mint.last_id();
+        for txs in batches {
+            let results = leader.process_transactions(txs);
+            leader.update_state_hash();
+            let txs = results.into_iter().filter_map(|x| x.ok()).collect();
+            let entry = next_entry(&last_id, 1, txs);
+            leader.register_entry(&entry);
+            last_id = entry.id;
+            entries.push(entry);
+        }
+        assert_eq!(entries[1].transactions, vec![]);
+        assert_eq!(leader.get_balance(&pubkey), Some(1));
+
+        let validator = Bank::new(&mint);
+        validator.process_entries(entries).unwrap();
+        assert_eq!(validator.get_nonce(&mint.pubkey()), leader.get_nonce(&mint.pubkey()));
+        assert_eq!(validator.state_hash(), leader.state_hash());
+    }
+
+    #[test]
+    fn test_durable_nonce_same_entry() {
+        let mint = Mint::new(3);
+        let bank = Bank::new(&mint);
+        let keypair = mint.keypair();
+        let pubkey = KeyPair::new().pubkey();
+
+        // A nonce created earlier in the same entry can be used right away.
+        let advance = Transaction::new_advance_nonce(&keypair, mint.seed(), mint.last_id());
+        let nonce = hash(&advance.sig);
+        let durable = Transaction::new(&keypair, pubkey, 1, mint.seed(), nonce);
+        let entry = next_entry(&mint.last_id(), 1, vec![advance, durable.clone()]);
+        bank.process_entries(vec![entry]).unwrap();
+        assert_eq!(bank.get_balance(&pubkey), Some(1));
+        assert_eq!(bank.get_nonce(&mint.pubkey()), Some(hash(&durable.sig)));
+
+        // And advancing it later in the same entry doesn't cancel a
+        // transaction that came before.
+        let nonce = hash(&durable.sig);
+        let durable = Transaction::new(&keypair, pubkey, 1, mint.seed(), nonce);
+        let advance = Transaction::new_advance_nonce(&keypair, mint.seed(), bank.last_id());
+        let results = bank.process_transactions(vec![durable, advance.clone()]);
+        assert!(results.iter().all(|result| result.is_ok()));
+        assert_eq!(bank.get_balance(&pubkey), Some(2));
+        assert_eq!(bank.get_nonce(&mint.pubkey()), Some(hash(&advance.sig)));
+    }
+
+    #[test]
+    fn test_durable_nonce_failed_credits_same_entry() {
+        let mint = Mint::new(3);
+        let keypair = mint.keypair();
+        let pubkey = KeyPair::new().pubkey();
+        let advance = Transaction::new_advance_nonce(&keypair, mint.seed(), mint.last_id());
+        let nonce = hash(&advance.sig);
+        let advance_entry = next_entry(&mint.last_id(), 1, vec![advance]);
+
+        // The batch's credits fail, so its nonce advance is undone, and the
+        // transaction signed with the nonce it would have left can't be
+        // accepted either.
+        let payment = Transaction::new(&keypair, pubkey, 1, mint.seed(), nonce);
+        let instructions = vec![
+            payment.instruction,
+            Instruction::ApplyTickHeight(Signature::default(), 100),
+        ];
+        let batch = Transaction::new_batch(&keypair, instructions, mint.seed(), nonce, 0);
+        let next_nonce = hash(&batch.sig);
+        let durable = Transaction::new(&keypair, pubkey, 1, mint.seed(), next_nonce);
+
+        let leader = Bank::new(&mint);
+        leader.process_entries(vec![advance_entry.clone()]).unwrap();
+        let results = leader.process_transactions(vec![batch, durable]);
+        assert_eq!(results[0], Err(BankError::TickHeightNotReached(100)));
+        assert_eq!(results[1], Err(BankError::LastIdNotFound(next_nonce)));
+        assert_eq!(leader.get_nonce(&mint.pubkey()), Some(nonce));
+        leader.update_state_hash();
+        let txs = results.into_iter().filter_map(|x| x.ok()).collect();
+        let entry = next_entry(&advance_entry.id, 1, txs);
+        leader.register_entry(&entry);
+
+        let validator = Bank::new(&mint);
+        validator.process_entries(vec![advance_entry, entry]).unwrap();
+        assert_eq!(validator.get_nonce(&mint.pubkey()), leader.get_nonce(&mint.pubkey()));
+        assert_eq!(validator.get_balance(&pubkey), leader.get_balance(&pubkey));
+        assert_eq!(validator.state_hash(), leader.state_hash());
+    }
+
+    #[test]
+    fn test_transfer_after_date() {
+        let mint = Mint::new(1);
+        let bank = Bank::new(&mint);
+        let pubkey = KeyPair::new().pubkey();
+        let dt = Utc::now();
+        bank.apply_timestamp(mint.pubkey(), dt).unwrap();
+
+        // It's now past now, so this transfer should be processed immediately.
+        bank.transfer_on_date(1, &mint.keypair(), pubkey, dt, mint.last_id())
+            .unwrap();
+
+        assert_eq!(bank.get_balance(&mint.pubkey()), Some(0));
+        assert_eq!(bank.get_balance(&pubkey), Some(1));
+    }
+
+    #[test]
+    fn test_batch() {
+        let mint = Mint::new(10);
+        let bank = Bank::new(&mint);
+        let collector = KeyPair::new().pubkey();
+        bank.set_fee_collector(collector);
+        let bob = KeyPair::new().pubkey();
+        let carol = KeyPair::new().pubkey();
+        let dt = Utc::now();
+        let sig = bank.transfer_on_date(3, &mint.keypair(), bob, dt, mint.last_id())
+            .unwrap();
+
+        // Cancel the postdated transfer and pay carol in one transaction.
+        let payment = Transaction::new_taxed(
+            &mint.keypair(),
+            carol,
+            4,
+            1,
+            mint.seed(),
+            mint.last_id(),
+        );
+        let instructions = vec![payment.instruction, Instruction::ApplySignature(sig)];
+        let tx = Transaction::new_batch(
+            &mint.keypair(),
+            instructions,
+            mint.seed(),
+            mint.last_id(),
+            1,
+        );
+        assert!(tx.verify_plan());
+        bank.process_transaction(&tx).unwrap();
+        assert_eq!(bank.get_balance(&mint.pubkey()), Some(6));
+        assert_eq!(bank.get_balance(&carol), Some(3));
+        assert_eq!(bank.get_balance(&collector), Some(1));
+        assert_eq!(bank.get_balance(&bob), None);
+        assert_eq!(bank.get_signature_status(&sig), SignatureStatus::Confirmed);
+        assert_eq!(bank.get_signature_status(&tx.sig), SignatureStatus::Confirmed);
+        bank.verify_supply().unwrap();
+    }
+
+    #[test]
+    fn test_batch_rolls_back() {
+        let mint = Mint::new(10);
+        let bank = Bank::new(&mint);
+        let officer = KeyPair::new().pubkey();
+        let bob = KeyPair::new().pubkey();
+        let carol = KeyPair::new().pubkey();
+        let signers = vec![mint.pubkey(), officer];
+        let multisig =
+            Transaction::new_multisig(
+                &mint.keypair(),
+                bob,
+                2,
+                signers,
+                2,
+                mint.seed(),
+                mint.last_id(),
+            );
+        bank.process_transaction(&multisig).unwrap();
+        let approval = Transaction::new_signature(
+            &mint.keypair(),
+            multisig.sig,
+            mint.seed(),
+            mint.last_id(),
+        );
+        bank.process_transaction(&approval).unwrap();
+        let pending = bank.get_pending_contracts(&bob);
+        let state_hash = bank.update_state_hash();
+
+        // The second approval fails, so carol isn't paid either.
+        let payment = Transaction::new(&mint.keypair(), carol, 5, mint.seed(), mint.last_id());
+        let instructions = vec![
+            payment.instruction,
+            Instruction::ApplySignature(multisig.sig),
+        ];
+        let tx = Transaction::new_batch(
+            &mint.keypair(),
+            instructions,
+            mint.seed(),
+            mint.last_id(),
+            0,
+        );
+        let err = BankError::DuplicateApproval(mint.pubkey());
+        assert_eq!(bank.process_transaction(&tx), Err(err.clone()));
+        assert_eq!(bank.get_signature_status(&tx.sig), SignatureStatus::Failed(err));
+        assert_eq!(bank.get_balance(&mint.pubkey()), Some(8));
+        assert_eq!(bank.get_balance(&carol), None);
+        assert_eq!(bank.get_pending_contracts(&bob), pending);
+        assert_eq!(bank.transaction_count(), 2);
+        assert_eq!(bank.update_state_hash(), state_hash);
+        bank.verify_supply().unwrap();
+
+        // Batches that could only be applied in part are rejected up front.
+        let timestamp = Instruction::ApplyTimestamp(Utc::now());
+        let tx = Transaction::new_batch(
+            &mint.keypair(),
+            vec![timestamp],
+            mint.seed(),
+            mint.last_id(),
+            0,
+        );
+        assert_eq!(bank.process_transaction(&tx), Err(BankError::InvalidBatch));
+        assert_eq!(bank.get_balance(&mint.pubkey()), Some(8));
+    }
+
+    #[test]
+    fn test_batch_overflow_rolls_back() {
+        let mint = Mint::new(10);
+        let bank = Bank::new(&mint);
+        let collector = KeyPair::new().pubkey();
+        bank.set_fee_collector(collector);
+        let bob = KeyPair::new().pubkey();
+        let carol = KeyPair::new().pubkey();
+        let sig = bank.transfer_on_date(3, &mint.keypair(), bob, Utc::now(), mint.last_id())
+            .unwrap();
+        let pending = bank.get_pending_contracts(&bob);
+
+        // Break the supply invariant so that paying the fee overflows.
+        let payment = Payment {
+            tokens: i64::max_value(),
+            to: collector,
+        };
+        bank.apply_payment(&payment).unwrap();
+        let state_hash = bank.update_state_hash();
+
+        let payment = Transaction::new_taxed(
+            &mint.keypair(),
+            carol,
+            4,
+            1,
+            mint.seed(),
+            mint.last_id(),
+        );
+        let instructions = vec![payment.instruction, Instruction::ApplySignature(sig)];
+        let tx = Transaction::new_batch(
+            &mint.keypair(),
+            instructions,
+            mint.seed(),
+            mint.last_id(),
+            1,
+        );
+        let err = BankError::TokenOverflow(collector);
+        assert_eq!(bank.process_transaction(&tx), Err(err));
+        assert_eq!(bank.get_balance(&mint.pubkey()), Some(7));
+        assert_eq!(bank.get_balance(&carol), None);
+        assert_eq!(bank.get_balance(&collector), Some(i64::max_value()));
+        assert_eq!(bank.get_pending_contracts(&bob), pending);
+        assert_eq!(bank.get_signature_status(&sig), SignatureStatus::PendingWitness);
+        assert_eq!(bank.update_state_hash(), state_hash);
+        assert_eq!(bank.compute_state_hash(), state_hash);
+    }
+
+    #[test]
+    fn test_batch_without_contract_pays_no_fee() {
+        let mint = Mint::new(10);
+        let bank = Bank::new(&mint);
+        let collector = KeyPair::new().pubkey();
+        bank.set_fee_collector(collector);
+        let bob = KeyPair::new().pubkey();
+        let sig = bank.transfer_on_date(3, &mint.keypair(), bob, Utc::now(), mint.last_id())
+            .unwrap();
+
+        // Nothing in the batch debits the sender, so there's nothing to pay
+        // the fee with.
+        let tx = Transaction::new_batch(
+            &mint.keypair(),
+            vec![Instruction::ApplySignature(sig)],
+            mint.seed(),
+            mint.last_id(),
+            1,
+        );
+        assert!(!tx.verify_plan());
+        assert_eq!(bank.process_transaction(&tx), Err(BankError::SupplyViolation));
+        assert_eq!(bank.get_balance(&collector), None);
+        assert_eq!(bank.get_balance(&mint.pubkey()), Some(7));
+        bank.verify_supply().unwrap();
+    }
+
+    #[test]
+    fn test_joint_transfer() {
+        let mint = Mint::new(10);
+        let bank = Bank::new(&mint);
+        let alice = KeyPair::new();
+        let bob = KeyPair::new();
+        let carol = KeyPair::new().pubkey();
+        bank.transfer(3, &mint.keypair(), alice.pubkey(), mint.last_id())
+            .unwrap();
+
+        let tx = Transaction::new_joint(
+            &mint.keypair(),
+            &[(&alice, 2)],
+            carol,
+            6,
+            mint.seed(),
+            mint.last_id(),
+        );
+        bank.process_transaction(&tx).unwrap();
+        assert_eq!(bank.get_balance(&mint.pubkey()), Some(3));
+        assert_eq!(bank.get_balance(&alice.pubkey()), Some(1));
+        assert_eq!(bank.get_balance(&carol), Some(6));
+
+        // If any co-signer can't pay, nobody pays.
+        let tx = Transaction::new_joint(
+            &mint.keypair(),
+            &[(&alice, 2)],
+            carol,
+            3,
+            mint.seed(),
+            mint.last_id(),
+        );
+        let err = BankError::InsufficientFunds(alice.pubkey());
+        assert_eq!(bank.process_transaction(&tx), Err(err.clone()));
+        assert_eq!(bank.get_signature_status(&tx.sig), SignatureStatus::Failed(err));
+        assert_eq!(bank.get_balance(&mint.pubkey()), Some(3));
+        assert_eq!(bank.get_balance(&alice.pubkey()), Some(1));
+
+        let tx = Transaction::new_joint(
+            &mint.keypair(),
+            &[(&bob, 1)],
+            carol,
+            3,
+            mint.seed(),
+            mint.last_id(),
+        );
+        assert_eq!(
+            bank.process_transaction(&tx),
+            Err(BankError::AccountNotFound(bob.pubkey()))
+        );
+        assert_eq!(bank.get_balance(&mint.pubkey()), Some(3));
+        bank.verify_supply().unwrap();
+    }
+
+    #[test]
+    fn test_fee_payer() {
+        let mint = Mint::new(10);
+        let bank = Bank::new(&mint);
+        let collector = KeyPair::new().pubkey();
+        bank.set_fee_collector(collector);
+        let alice = KeyPair::new();
+        let bob = KeyPair::new().pubkey();
+        bank.transfer(3, &mint.keypair(), alice.pubkey(), mint.last_id())
+            .unwrap();
+
+        let tx =
+            Transaction::new_with_fee_payer(
+                &alice,
+                &mint.keypair(),
+                bob,
+                3,
+                2,
+                mint.seed(),
+                mint.last_id(),
+            );
+        let entry = next_entry(&mint.last_id(), 1, vec![tx]);
+        bank.process_entries(vec![entry.clone()]).unwrap();
+        assert_eq!(bank.get_balance(&alice.pubkey()), Some(0));
+        assert_eq!(bank.get_balance(&mint.pubkey()), Some(5));
+        assert_eq!(bank.get_balance(&bob), Some(3));
+        assert_eq!(bank.get_balance(&collector), Some(2));
+        assert_eq!(bank.get_entry_fees(&entry.id), Some(2));
+    }
+
+    #[test]
+    fn test_group_by_debit_account() {
+        let mint = Mint::new(10);
+        let alice = KeyPair::new();
+        let bob = KeyPair::new();
+        let carol = KeyPair::new();
+        let to = KeyPair::new().pubkey();
+        let last_id = mint.last_id();
+        let txs = vec![
+            Transaction::new(&alice, to, 1, mint.seed(), last_id),
+            Transaction::new(&bob, to, 1, mint.seed(), last_id),
+            Transaction::new(&carol, to, 1, mint.seed(), last_id),
+            Transaction::new_joint(&carol, &[(&alice, 1)], to, 2, mint.seed(), last_id),
+            Transaction::new(&bob, to, 1, mint.seed(), last_id),
+        ];
+        assert_eq!(
+            Bank::group_by_debit_account(&txs),
+            vec![vec![0, 2, 3], vec![1, 4]]
+        );
+    }
+
+    #[test]
+    fn test_cancel_transfer() {
+        let mint = Mint::new(1);
+        let bank = Bank::new(&mint);
+        let pubkey = KeyPair::new().pubkey();
+        let dt = Utc::now();
+        let sig = bank.transfer_on_date(1, &mint.keypair(), pubkey, dt, mint.last_id())
+            .unwrap();
+
+        // Assert the debit counts as a transaction.
+        assert_eq!(bank.transaction_count(), 1);
+
+        // Mint's balance will be zero because all funds are locked up.
+        assert_eq!(bank.get_balance(&mint.pubkey()), Some(0));
+
+        // pubkey's balance will be None because the funds have not been
+        // sent.
+        assert_eq!(bank.get_balance(&pubkey), None);
+
+        // Now, cancel the trancaction. Mint gets her funds back, pubkey never sees them.
+        bank.apply_signature(mint.pubkey(), sig).unwrap();
+        assert_eq!(bank.get_balance(&mint.pubkey()), Some(1));
+        assert_eq!(bank.get_balance(&pubkey), None);
+
+        // Assert cancel doesn't cause count to go backward.
+        assert_eq!(bank.transaction_count(), 1);
+
+        bank.apply_signature(mint.pubkey(), sig).unwrap(); // <-- Attack! Attempt to cancel completed transaction.
         assert_ne!(bank.get_balance(&mint.pubkey()), Some(2));
     }
 
@@ -556,19 +2679,52 @@ mod tests {
     }
 
     #[test]
-    fn test_forget_signature() {
+    fn test_fail_signature() {
         let mint = Mint::new(1);
         let bank = Bank::new(&mint);
         let sig = Signature::default();
         bank.reserve_signature_with_last_id(&sig, &mint.last_id())
             .unwrap();
-        bank.forget_signature_with_last_id(&sig, &mint.last_id());
+        bank.fail_signature_with_last_id(&sig, &mint.last_id(), &BankError::NegativeTokens);
         assert!(
             bank.reserve_signature_with_last_id(&sig, &mint.last_id())
                 .is_ok()
         );
     }
 
+    #[test]
+    fn test_signature_status() {
+        let mint = Mint::new(2);
+        let bank = Bank::new(&mint);
+        let pubkey = KeyPair::new().pubkey();
+        let dt = Utc::now();
+
+        let tx = Transaction::new(&mint.keypair(), pubkey, 3, mint.seed(), mint.last_id());
+        assert_eq!(bank.get_signature_status(&tx.sig), SignatureStatus::NotFound);
+        assert!(bank.process_transaction(&tx).is_err());
+        assert_eq!(
+            bank.get_signature_status(&tx.sig),
+            SignatureStatus::Failed(BankError::InsufficientFunds(mint.pubkey()))
+        );
+
+        let sig = bank.transfer(1, &mint.keypair(), pubkey, mint.last_id())
+            .unwrap();
+        assert_eq!(bank.get_signature_status(&sig), SignatureStatus::Confirmed);
+
+        // A duplicate doesn't change the status of the original.
+        assert!(bank.transfer(1, &mint.keypair(), pubkey, mint.last_id()).is_err());
+        assert_eq!(bank.get_signature_status(&sig), SignatureStatus::Confirmed);
+
+        let sig = bank.transfer_on_date(1, &mint.keypair(), pubkey, dt, mint.last_id())
+            .unwrap();
+        assert_eq!(
+            bank.get_signature_status(&sig),
+            SignatureStatus::PendingWitness
+        );
+        bank.apply_timestamp(mint.pubkey(), dt).unwrap();
+        assert_eq!(bank.get_signature_status(&sig), SignatureStatus::Confirmed);
+    }
+
     #[test]
     fn test_max_entry_ids() {
         let mint = Mint::new(1);
@@ -590,8 +2746,14 @@ mod tests {
         let mint = Mint::new(2);
         let bank = Bank::new(&mint);
         let keypair = KeyPair::new();
-        let tx0 = Transaction::new(&mint.keypair(), keypair.pubkey(), 2, mint.last_id());
-        let tx1 = Transaction::new(&keypair, mint.pubkey(), 1, mint.last_id());
+        let tx0 = Transaction::new(
+            &mint.keypair(),
+            keypair.pubkey(),
+            2,
+            mint.seed(),
+            mint.last_id(),
+        );
+        let tx1 = Transaction::new(&keypair, mint.pubkey(), 1, mint.seed(), mint.last_id());
         let txs = vec![tx0, tx1];
         let results = bank.process_transactions(txs);
         assert!(results[1].is_err());
@@ -599,6 +2761,366 @@ mod tests {
         // Assert bad transactions aren't counted.
         assert_eq!(bank.transaction_count(), 1);
     }
+
+    #[test]
+    fn test_process_transactions_is_deterministic() {
+        let mint = Mint::new(5);
+        let alice = KeyPair::new();
+        let bob = KeyPair::new();
+        let dt = Utc::now();
+        let txs = vec![
+            Transaction::new(&mint.keypair(), alice.pubkey(), 3, mint.seed(), mint.last_id()),
+            Transaction::new(&mint.keypair(), bob.pubkey(), 3, mint.seed(), mint.last_id()),
+            Transaction::new(&alice, bob.pubkey(), 1, mint.seed(), mint.last_id()),
+            Transaction::new_on_date(
+                &mint.keypair(),
+                bob.pubkey(),
+                dt,
+                2,
+                mint.seed(),
+                mint.last_id(),
+            ),
+            Transaction::new_timestamp(&mint.keypair(), dt, mint.seed(), mint.last_id()),
+            Transaction::new(&mint.keypair(), alice.pubkey(), 1, mint.seed(), mint.last_id()),
+        ];
+
+        let expected_bank = Bank::new(&mint);
+        let expected_results = expected_bank.process_transactions(txs.clone());
+        assert!(expected_results[0].is_ok());
+        assert_eq!(
+            expected_results[1],
+            Err(BankError::InsufficientFunds(mint.pubkey()))
+        );
+        assert_eq!(
+            expected_results[2],
+            Err(BankError::AccountNotFound(alice.pubkey()))
+        );
+        assert_eq!(
+            expected_results[5],
+            Err(BankError::InsufficientFunds(mint.pubkey()))
+        );
+        assert_eq!(expected_bank.get_balance(&bob.pubkey()), Some(2));
+
+        for _ in 0..100 {
+            let bank = Bank::new(&mint);
+            assert_eq!(bank.process_transactions(txs.clone()), expected_results);
+            assert_eq!(bank.snapshot(), expected_bank.snapshot());
+        }
+    }
+
+    #[test]
+    fn test_state_hash() {
+        let mint = Mint::new(10);
+        let keypair = KeyPair::new();
+        let dt = Utc::now();
+        let tx0 = Transaction::new(
+            &mint.keypair(),
+            keypair.pubkey(),
+            4,
+            mint.seed(),
+            mint.last_id(),
+        );
+        let tx1 = Transaction::new_on_date(
+            &keypair,
+            mint.pubkey(),
+            dt,
+            2,
+            mint.seed(),
+            mint.last_id(),
+        );
+        let tx2 = Transaction::new_signature(&keypair, tx1.sig, mint.seed(), mint.last_id());
+        let entries = next_entries(&mint.last_id(), 0, vec![vec![tx0], vec![tx1], vec![tx2]]);
+
+        let bank = Bank::new(&mint);
+        assert_eq!(bank.state_hash().0, bank.compute_state_hash());
+        let mut hashes = vec![bank.state_hash().0];
+        for entry in &entries {
+            bank.process_entries(vec![entry.clone()]).unwrap();
+            let (state_hash, count) = bank.state_hash();
+            assert_eq!(state_hash, bank.compute_state_hash());
+            assert_eq!(count, bank.transaction_count() as u64);
+            hashes.push(state_hash);
+        }
+
+        // Cancelling the postdated transfer refunds the sender and empties
+        // 'pending', which restores the state after the first entry.
+        assert_ne!(hashes[1], hashes[0]);
+        assert_ne!(hashes[2], hashes[1]);
+        assert_eq!(hashes[3], hashes[1]);
+        assert_eq!(bank.get_balance(&keypair.pubkey()), Some(4));
+
+        let restored = Bank::from_snapshot(&bank.snapshot()).unwrap();
+        assert_eq!(restored.state_hash(), bank.state_hash());
+    }
+
+    #[test]
+    fn test_state_hash_nonces() {
+        let mint = Mint::new(10);
+        let pubkey = KeyPair::new().pubkey();
+        let advance = Transaction::new_advance_nonce(&mint.keypair(), mint.seed(), mint.last_id());
+        let nonce = hash(&advance.sig);
+        let durable = Transaction::new(&mint.keypair(), pubkey, 0, mint.seed(), nonce);
+        let entries = next_entries(&mint.last_id(), 0, vec![vec![advance], vec![durable]]);
+
+        // Advancing a nonce changes the state, even though no tokens move.
+        let bank = Bank::new(&mint);
+        let mut hashes = vec![bank.state_hash().0];
+        for entry in &entries {
+            bank.process_entries(vec![entry.clone()]).unwrap();
+            let (state_hash, _) = bank.state_hash();
+            assert_eq!(state_hash, bank.compute_state_hash());
+            assert!(!hashes.contains(&state_hash));
+            hashes.push(state_hash);
+        }
+
+        let restored = Bank::from_snapshot(&bank.snapshot()).unwrap();
+        assert_eq!(restored.state_hash(), bank.state_hash());
+    }
+
+    #[test]
+    fn test_collect_fees() {
+        let mint = Mint::new(10);
+        let bank = Bank::new(&mint);
+        let collector = KeyPair::new().pubkey();
+        bank.set_fee_collector(collector);
+        let pubkey = KeyPair::new().pubkey();
+        let tx0 = Transaction::new_taxed(
+            &mint.keypair(),
+            pubkey,
+            4,
+            1,
+            mint.seed(),
+            mint.last_id(),
+        );
+        let tx1 = Transaction::new_taxed(
+            &mint.keypair(),
+            pubkey,
+            3,
+            2,
+            mint.seed(),
+            mint.last_id(),
+        );
+        let entries = next_entries(&mint.last_id(), 0, vec![vec![tx0, tx1]]);
+        bank.process_entries(entries.clone()).unwrap();
+
+        assert_eq!(bank.get_balance(&mint.pubkey()), Some(3));
+        assert_eq!(bank.get_balance(&pubkey), Some(4));
+        assert_eq!(bank.get_balance(&collector), Some(3));
+        assert_eq!(bank.get_entry_fees(&entries[0].id), Some(3));
+        assert_eq!(bank.get_entry_fees(&mint.last_id()), None);
+
+        let restored = Bank::from_snapshot(&bank.snapshot()).unwrap();
+        assert_eq!(restored.fee_collector(), collector);
+        assert_eq!(restored.get_entry_fees(&entries[0].id), Some(3));
+    }
+
+    #[test]
+    fn test_entry_fees_overflow() {
+        let mint = Mint::new(10);
+        let bank = Bank::new(&mint);
+        let pubkey = KeyPair::new().pubkey();
+        let max = i64::max_value();
+        let keypair = mint.keypair();
+        let tx = Transaction::new_taxed(&keypair, pubkey, max, max, mint.seed(), mint.last_id());
+        let entries = next_entries(&mint.last_id(), 0, vec![vec![tx.clone(), tx]]);
+        assert_eq!(
+            bank.process_entries(entries),
+            Err(BankError::SupplyViolation)
+        );
+        assert_eq!(bank.get_balance(&mint.pubkey()), Some(10));
+        assert_eq!(bank.last_id(), mint.last_id());
+    }
+
+    #[test]
+    fn test_genesis_fee_collector() {
+        let mut mint = Mint::new(10);
+        assert_eq!(Bank::new(&mint).fee_collector(), mint.pubkey());
+        let collector = KeyPair::new().pubkey();
+        mint.fee_collector = Some(collector);
+        let bank = Bank::new(&mint);
+        assert_eq!(bank.fee_collector(), collector);
+
+        // Only the mint chooses, and only in the genesis entry.
+        let keypair = KeyPair::new();
+        let mut txs = mint.create_transactions();
+        let seed = mint.seed();
+        txs.push(Transaction::new_fee_collector(&keypair, keypair.pubkey(), seed, seed));
+        let bank = Bank::new_from_genesis(&txs).unwrap();
+        assert_eq!(bank.fee_collector(), collector);
+
+        let last_id = mint.last_id();
+        bank.register_entry_id(&last_id);
+        let tx = Transaction::new_fee_collector(&mint.keypair(), mint.pubkey(), seed, last_id);
+        assert_eq!(bank.process_transaction(&tx), Err(BankError::GenesisOnly));
+        assert_eq!(bank.fee_collector(), collector);
+    }
+
+    #[test]
+    fn test_unbalanced_plan() {
+        let mint = Mint::new(10);
+        let bank = Bank::new(&mint);
+        let pubkey = KeyPair::new().pubkey();
+        let budget = Budget::new_payment(5, pubkey);
+        let tx = Transaction::new_budget(&mint.keypair(), budget, 1, mint.seed(), mint.last_id());
+        assert_eq!(
+            bank.process_transaction(&tx),
+            Err(BankError::SupplyViolation)
+        );
+        assert_eq!(bank.get_balance(&mint.pubkey()), Some(10));
+        assert_eq!(bank.get_balance(&pubkey), None);
+        bank.verify_supply().unwrap();
+    }
+
+    #[test]
+    fn test_token_overflow() {
+        let mint = Mint::new(i64::max_value());
+        let bank = Bank::new(&mint);
+        let payment = Payment {
+            tokens: 1,
+            to: mint.pubkey(),
+        };
+        assert_eq!(
+            bank.apply_payment(&payment),
+            Err(BankError::TokenOverflow(mint.pubkey()))
+        );
+        assert_eq!(bank.get_balance(&mint.pubkey()), Some(i64::max_value()));
+
+        // Moving the whole supply around never overflows.
+        let pubkey = KeyPair::new().pubkey();
+        bank.transfer(i64::max_value(), &mint.keypair(), pubkey, mint.last_id())
+            .unwrap();
+        assert_eq!(bank.get_balance(&pubkey), Some(i64::max_value()));
+        bank.verify_supply().unwrap();
+
+        // Creating a token overflows the total supply.
+        let payment = Payment {
+            tokens: 1,
+            to: mint.pubkey(),
+        };
+        bank.apply_payment(&payment).unwrap();
+        assert!(bank.total_supply().is_err());
+        assert!(bank.verify_supply().is_err());
+    }
+
+    #[test]
+    fn test_supply_invariant() {
+        let mint = Mint::new(100);
+        let bank = Bank::new(&mint);
+        bank.set_fee_collector(KeyPair::new().pubkey());
+        let alice = KeyPair::new();
+        let bob = KeyPair::new().pubkey();
+        let dt = Utc.ymd(2018, 7, 1).and_hms(0, 0, 0);
+        let last_id = mint.last_id();
+
+        let pay_alice = Transaction::new(&mint.keypair(), alice.pubkey(), 30, mint.seed(), last_id);
+        let batch0 = vec![
+            pay_alice.clone(),
+            Transaction::new_taxed(&mint.keypair(), bob, 10, 2, mint.seed(), last_id),
+            Transaction::new_on_date(&mint.keypair(), bob, dt, 20, mint.seed(), last_id),
+            Transaction::new_split(
+                &mint.keypair(),
+                vec![Payment { tokens: 5, to: bob }, Payment { tokens: 5, to: bob }],
+                mint.seed(),
+                last_id,
+            ),
+            // Fails: the mint has only 30 tokens left by now.
+            Transaction::new(&mint.keypair(), bob, 31, mint.seed(), last_id),
+            // Fails: a duplicate.
+            pay_alice,
+        ];
+        let results = bank.process_transactions(batch0);
+        let failures: Vec<_> = results.iter().map(|result| result.is_err()).collect();
+        assert_eq!(failures, vec![false, false, false, false, true, true]);
+        bank.verify_supply().unwrap();
+
+        let batch1 = vec![
+            Transaction::new(&alice, bob, 10, mint.seed(), last_id),
+            Transaction::new_timestamp(&mint.keypair(), dt, mint.seed(), last_id),
+            // Fails: alice only has 20 tokens left.
+            Transaction::new(&alice, bob, 21, mint.seed(), last_id),
+        ];
+        let results = bank.process_transactions(batch1);
+        assert!(results[2].is_err());
+        bank.verify_supply().unwrap();
+
+        assert_eq!(bank.get_balance(&bob), Some(48));
+        assert_eq!(bank.total_supply(), Ok(100));
+        Bank::from_snapshot(&bank.snapshot()).unwrap()
+            .verify_supply()
+            .unwrap();
+    }
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        let mint = Mint::new(10);
+        let bank = Bank::new(&mint);
+        let pubkey = KeyPair::new().pubkey();
+        let dt = Utc::now();
+        bank.transfer(2, &mint.keypair(), pubkey, mint.last_id())
+            .unwrap();
+        let sig = bank.transfer_on_date(3, &mint.keypair(), pubkey, dt, mint.last_id())
+            .unwrap();
+
+        let snapshot = bank.snapshot();
+        assert_eq!(snapshot.last_id(), Some(mint.last_id()));
+        let snapshot: Snapshot = deserialize(&serialize(&snapshot).unwrap()).unwrap();
+        let restored = Bank::from_snapshot(&snapshot).unwrap();
+        assert_eq!(restored.snapshot(), bank.snapshot());
+        assert_eq!(restored.last_id(), mint.last_id());
+        assert_eq!(restored.genesis(), mint.seed());
+        assert_eq!(restored.get_balance(&mint.pubkey()), Some(5));
+        assert_eq!(restored.transaction_count(), 2);
+
+        // Signatures recorded before the snapshot are still rejected.
+        assert_eq!(
+            restored.reserve_signature_with_last_id(&sig, &mint.last_id()),
+            Err(BankError::DuplicateSiganture(sig))
+        );
+
+        // Pending plans survive the snapshot.
+        restored.apply_signature(mint.pubkey(), sig).unwrap();
+        assert_eq!(restored.get_balance(&mint.pubkey()), Some(8));
+        assert_eq!(restored.get_balance(&pubkey), Some(2));
+
+        // Balances that don't add up to the supply at genesis are caught.
+        let mut snapshot = snapshot;
+        snapshot.balances[0].1 += 1;
+        assert_eq!(
+            Bank::from_snapshot(&snapshot).unwrap().verify_supply(),
+            Err(BankError::SupplyViolation)
+        );
+
+        // So is a snapshot without entry IDs to resume from.
+        snapshot.last_ids.clear();
+        assert_eq!(snapshot.last_id(), None);
+        assert!(Bank::from_snapshot(&snapshot).is_none());
+    }
+
+    #[test]
+    fn test_snapshot_then_replay_tail() {
+        let mint = Mint::new(10);
+        let keypair = KeyPair::new();
+        let tx0 = Transaction::new(
+            &mint.keypair(),
+            keypair.pubkey(),
+            4,
+            mint.seed(),
+            mint.last_id(),
+        );
+        let tx1 = Transaction::new(&keypair, mint.pubkey(), 1, mint.seed(), mint.last_id());
+        let entries = next_entries(&mint.last_id(), 0, vec![vec![tx0], vec![tx1]]);
+
+        let bank = Bank::new(&mint);
+        bank.process_entries(entries.clone()).unwrap();
+
+        let head = Bank::new(&mint);
+        head.process_entries(entries[..1].to_vec()).unwrap();
+        let tail = Bank::from_snapshot(&head.snapshot()).unwrap();
+        tail.process_entries(entries[1..].to_vec()).unwrap();
+
+        assert_eq!(tail.snapshot(), bank.snapshot());
+        assert_eq!(tail.get_balance(&keypair.pubkey()), Some(3));
+    }
 }
 
 #[cfg(all(feature = "unstable", test))]
@@ -620,7 +3142,13 @@ mod bench {
             .map(|i| {
                 // Seed the 'from' account.
                 let rando0 = KeyPair::new();
-                let tx = Transaction::new(&mint.keypair(), rando0.pubkey(), 1_000, mint.last_id());
+                let tx = Transaction::new(
+                    &mint.keypair(),
+                    rando0.pubkey(),
+                    1_000,
+                    mint.seed(),
+                    mint.last_id(),
+                );
                 bank.process_transaction(&tx).unwrap();
 
                 // Seed the 'to' account and a cell for its signature.
@@ -628,11 +3156,11 @@ mod bench {
                 bank.register_entry_id(&last_id);
 
                 let rando1 = KeyPair::new();
-                let tx = Transaction::new(&rando0, rando1.pubkey(), 1, last_id);
+                let tx = Transaction::new(&rando0, rando1.pubkey(), 1, mint.seed(), last_id);
                 bank.process_transaction(&tx).unwrap();
 
                 // Finally, return a transaction that's unique
-                Transaction::new(&rando0, rando1.pubkey(), 1, last_id)
+                Transaction::new(&rando0, rando1.pubkey(), 1, mint.seed(), last_id)
             })
             .collect();
         bencher.iter(|| {
@@ -1,7 +1,6 @@
 //! The `banking_stage` processes Transaction messages.
 
 use bank::Bank;
-use bincode::deserialize;
 use packet;
 use packet::SharedPackets;
 use rayon::prelude::*;
@@ -16,6 +15,7 @@ use std::time::Duration;
 use std::time::Instant;
 use timing;
 use transaction::Transaction;
+use wire;
 
 pub struct BankingStage {
     pub thread_hdl: JoinHandle<()>,
@@ -52,15 +52,23 @@ impl BankingStage {
         }
     }
 
+    /// Decode the transactions in the packets, followed by those reassembled
+    /// from fragments, in the same order as their verification results.
     fn deserialize_transactions(p: &packet::Packets) -> Vec<Option<(Transaction, SocketAddr)>> {
-        p.packets
+        let mut transactions: Vec<_> = p.packets
             .par_iter()
             .map(|x| {
-                deserialize(&x.data[0..x.meta.size])
-                    .map(|req| (req, x.meta.addr()))
+                wire::decode(&x.data[0..x.meta.size])
+                    .map(|tx| (tx, x.meta.addr()))
                     .ok()
             })
-            .collect()
+            .collect();
+        let reassembled: Vec<_> = p.reassembled
+            .par_iter()
+            .map(|(data, addr)| wire::decode(data).map(|tx| (tx, *addr)).ok())
+            .collect();
+        transactions.extend(reassembled);
+        transactions
     }
 
     fn process_packets(
@@ -99,6 +107,9 @@ impl BankingStage {
 
             debug!("process_transactions");
             let results = bank.process_transactions(transactions);
+            // Each batch becomes one Entry, so this is the state a validator will
+            // reach once it replays that Entry.
+            bank.update_state_hash();
             let transactions = results.into_iter().filter_map(|x| x.ok()).collect();
             signal_sender.send(Signal::Transactions(transactions))?;
             debug!("done process_transactions");
@@ -269,7 +280,9 @@ mod bench {
         let pubkey = KeyPair::new().pubkey();
 
         let transactions: Vec<_> = (0..tx)
-            .map(|i| Transaction::new(&mint.keypair(), pubkey, i as i64, mint.last_id()))
+            .map(|i| {
+                Transaction::new(&mint.keypair(), pubkey, i as i64, mint.seed(), mint.last_id())
+            })
             .collect();
 
         let (verified_sender, verified_receiver) = channel();
@@ -0,0 +1,274 @@
+//! The `base58` module writes keys, signatures and hashes the way people can
+//! read and copy them, in the Bitcoin base58 alphabet, which leaves out the
+//! easily confused characters `0`, `O`, `I` and `l`.
+
+use std::error;
+use std::fmt;
+
+const ALPHABET: &[u8; 58] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Base58Error {
+    InvalidCharacter(char),
+    /// The text decodes to this many bytes instead of the expected number.
+    InvalidLength(usize),
+}
+
+impl fmt::Display for Base58Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Base58Error::InvalidCharacter(c) => write!(f, "invalid base58 character `{}`", c),
+            Base58Error::InvalidLength(len) => write!(f, "base58 text decodes to {} bytes", len),
+        }
+    }
+}
+
+impl error::Error for Base58Error {
+    fn description(&self) -> &str {
+        "invalid base58"
+    }
+}
+
+/// Encode bytes as base58. Each leading zero byte is written as a `1`.
+pub fn encode(bytes: &[u8]) -> String {
+    let zeros = bytes.iter().take_while(|&&b| b == 0).count();
+    // Little-endian base58 digits of the big-endian number in `bytes`.
+    let mut digits: Vec<u8> = Vec::with_capacity(bytes.len() * 138 / 100 + 1);
+    for &byte in &bytes[zeros..] {
+        let mut carry = byte as u32;
+        for digit in digits.iter_mut() {
+            carry += (*digit as u32) << 8;
+            *digit = (carry % 58) as u8;
+            carry /= 58;
+        }
+        while carry > 0 {
+            digits.push((carry % 58) as u8);
+            carry /= 58;
+        }
+    }
+    let ones = (0..zeros).map(|_| '1');
+    let rest = digits.iter().rev().map(|&d| ALPHABET[d as usize] as char);
+    ones.chain(rest).collect()
+}
+
+/// Decode base58 text, the inverse of `encode`.
+pub fn decode(text: &str) -> Result<Vec<u8>, Base58Error> {
+    let ones = text.chars().take_while(|&c| c == '1').count();
+    // Little-endian bytes of the number in `text`.
+    let mut bytes: Vec<u8> = Vec::with_capacity(text.len() * 733 / 1000 + 1);
+    for c in text[ones..].chars() {
+        let mut carry = match ALPHABET.iter().position(|&a| a as char == c) {
+            Some(digit) => digit as u32,
+            None => return Err(Base58Error::InvalidCharacter(c)),
+        };
+        for byte in bytes.iter_mut() {
+            carry += (*byte as u32) * 58;
+            *byte = carry as u8;
+            carry >>= 8;
+        }
+        while carry > 0 {
+            bytes.push(carry as u8);
+            carry >>= 8;
+        }
+    }
+    bytes.extend((0..ones).map(|_| 0));
+    bytes.reverse();
+    Ok(bytes)
+}
+
+/// Define a fixed-size byte array type that is written in base58 by
+/// `Display`, `Debug`, `FromStr` and human-readable serializers such as JSON.
+/// Binary serializers such as bincode see the underlying `GenericArray`, and
+/// JSON written as an array of numbers is still accepted.
+macro_rules! base58_bytes {
+    ($(#[$attr:meta])* $name:ident, $size:ty) => {
+        $(#[$attr])*
+        #[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        pub struct $name(::generic_array::GenericArray<u8, $size>);
+
+        impl $name {
+            /// Copy the bytes from a slice, which must be the right size.
+            pub fn clone_from_slice(bytes: &[u8]) -> Self {
+                $name(::generic_array::GenericArray::clone_from_slice(bytes))
+            }
+        }
+
+        impl From<::generic_array::GenericArray<u8, $size>> for $name {
+            fn from(bytes: ::generic_array::GenericArray<u8, $size>) -> Self {
+                $name(bytes)
+            }
+        }
+
+        impl ::std::ops::Deref for $name {
+            type Target = [u8];
+            fn deref(&self) -> &[u8] {
+                &self.0
+            }
+        }
+
+        impl ::std::ops::DerefMut for $name {
+            fn deref_mut(&mut self) -> &mut [u8] {
+                &mut self.0
+            }
+        }
+
+        impl AsRef<[u8]> for $name {
+            fn as_ref(&self) -> &[u8] {
+                &self.0
+            }
+        }
+
+        impl ::std::fmt::Display for $name {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+                write!(f, "{}", ::base58::encode(&self.0))
+            }
+        }
+
+        impl ::std::fmt::Debug for $name {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+                write!(f, "{}", ::base58::encode(&self.0))
+            }
+        }
+
+        impl ::std::str::FromStr for $name {
+            type Err = ::base58::Base58Error;
+            fn from_str(text: &str) -> Result<Self, Self::Err> {
+                let bytes = ::base58::decode(text)?;
+                if bytes.len() != ::std::mem::size_of::<Self>() {
+                    return Err(::base58::Base58Error::InvalidLength(bytes.len()));
+                }
+                Ok($name::clone_from_slice(&bytes))
+            }
+        }
+
+        impl ::serde::Serialize for $name {
+            fn serialize<S: ::serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                if serializer.is_human_readable() {
+                    serializer.serialize_str(&self.to_string())
+                } else {
+                    ::serde::Serialize::serialize(&self.0, serializer)
+                }
+            }
+        }
+
+        impl<'de> ::serde::Deserialize<'de> for $name {
+            fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+            where
+                D: ::serde::Deserializer<'de>,
+            {
+                use serde::de::{Error, SeqAccess, Visitor};
+                use std::fmt;
+
+                struct BytesVisitor;
+                impl<'de> Visitor<'de> for BytesVisitor {
+                    type Value = $name;
+
+                    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                        write!(f, "a base58 string or an array of bytes")
+                    }
+
+                    fn visit_str<E: Error>(self, text: &str) -> Result<$name, E> {
+                        text.parse().map_err(E::custom)
+                    }
+
+                    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<$name, A::Error> {
+                        let mut bytes = vec![];
+                        while let Some(byte) = seq.next_element()? {
+                            bytes.push(byte);
+                        }
+                        if bytes.len() != ::std::mem::size_of::<$name>() {
+                            return Err(A::Error::invalid_length(bytes.len(), &self));
+                        }
+                        Ok($name::clone_from_slice(&bytes))
+                    }
+                }
+
+                if deserializer.is_human_readable() {
+                    deserializer.deserialize_any(BytesVisitor)
+                } else {
+                    ::serde::Deserialize::deserialize(deserializer).map($name)
+                }
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_decode() {
+        let cases: &[(&[u8], &str)] = &[
+            (b"", ""),
+            (&[0], "1"),
+            (&[0, 0, 1], "112"),
+            (&[57], "z"),
+            (&[58], "21"),
+            (b"hello world", "StV1DL6CwTryKyV"),
+        ];
+        for &(bytes, text) in cases {
+            assert_eq!(encode(bytes), text);
+            assert_eq!(decode(text), Ok(bytes.to_vec()));
+        }
+        let bytes: Vec<u8> = (0..64).map(|i| (i * 37) as u8).collect();
+        assert_eq!(decode(&encode(&bytes)), Ok(bytes));
+        assert_eq!(decode("1O1"), Err(Base58Error::InvalidCharacter('O')));
+    }
+
+    #[test]
+    fn test_parse() {
+        use signature::{KeyPair, KeyPairUtil, PublicKey};
+
+        let pubkey = KeyPair::new().pubkey();
+        assert_eq!(pubkey.to_string().parse(), Ok(pubkey));
+        assert_eq!(format!("{:?}", pubkey), pubkey.to_string());
+        assert_eq!(
+            "z".parse::<PublicKey>(),
+            Err(Base58Error::InvalidLength(1))
+        );
+    }
+
+    #[test]
+    fn test_serde() {
+        use bincode::{deserialize, serialize};
+        use generic_array::typenum::U32;
+        use generic_array::GenericArray;
+        use hash::{hash, Hash};
+        use serde_json;
+
+        let id = hash(b"hello");
+        let json = serde_json::to_string(&id).unwrap();
+        assert_eq!(json, format!("\"{}\"", id));
+        assert_eq!(serde_json::from_str::<Hash>(&json).unwrap(), id);
+
+        // JSON written before keys and hashes were base58 is still accepted.
+        let array = serde_json::to_string(&id.to_vec()).unwrap();
+        assert_eq!(serde_json::from_str::<Hash>(&array).unwrap(), id);
+        assert!(serde_json::from_str::<Hash>("[1, 2, 3]").is_err());
+        assert!(serde_json::from_str::<Hash>("\"0OIl\"").is_err());
+
+        // Binary encodings are unchanged.
+        let bytes = serialize(&id).unwrap();
+        let array: GenericArray<u8, U32> = GenericArray::clone_from_slice(&id);
+        assert_eq!(bytes, serialize(&array).unwrap());
+        assert_eq!(deserialize::<Hash>(&bytes).unwrap(), id);
+    }
+
+    #[test]
+    fn test_binary_size() {
+        use bincode::{serialize, serialized_size};
+        use hash::hash;
+        use signature::{KeyPair, KeyPairUtil, Signature};
+
+        // The ledger, snapshots and the wire format rely on bincode writing
+        // the raw bytes, not base58 strings.
+        let keypair = KeyPair::new();
+        let pubkey = keypair.pubkey();
+        assert_eq!(serialize(&pubkey).unwrap().len(), 32);
+        assert_eq!(serialized_size(&pubkey).unwrap(), 32);
+        let sig = Signature::clone_from_slice(keypair.sign(b"hello").as_ref());
+        assert_eq!(serialize(&sig).unwrap().len(), 64);
+        assert_eq!(serialize(&hash(b"hello")).unwrap().len(), 32);
+    }
+}
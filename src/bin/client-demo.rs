@@ -13,7 +13,7 @@ use rayon::prelude::*;
 use solana::crdt::{Crdt, ReplicatedData};
 use solana::data_replicator::DataReplicator;
 use solana::mint::MintDemo;
-use solana::signature::{GenKeys, KeyPair, KeyPairUtil};
+use solana::signature::{KeyPair, KeyPairUtil};
 use solana::streamer::default_window;
 use solana::thin_client::ThinClient;
 use solana::transaction::Transaction;
@@ -132,22 +132,24 @@ fn main() {
     });
     let mut client = mk_client(&client_addr, &leader);
 
+    println!("Get genesis...");
+    let genesis = client.get_genesis();
+    println!("Got genesis {:?}", genesis);
+
     println!("Get last ID...");
     let last_id = client.get_last_id();
     println!("Got last ID {:?}", last_id);
 
-    let rnd = GenKeys::new(demo.mint.keypair().public_key_bytes());
-
     println!("Creating keypairs...");
     let txs = demo.num_accounts / 2;
-    let keypairs = rnd.gen_n_keypairs(demo.num_accounts);
+    let keypairs = demo.keypairs();
     let keypair_pairs: Vec<_> = keypairs.chunks(2).collect();
 
     println!("Signing transactions...");
     let now = Instant::now();
     let transactions: Vec<_> = keypair_pairs
         .into_par_iter()
-        .map(|chunk| Transaction::new(&chunk[0], chunk[1].pubkey(), 1, last_id))
+        .map(|chunk| Transaction::new(&chunk[0], chunk[1].pubkey(), 1, genesis, last_id))
         .collect();
     let duration = now.elapsed();
     let ns = duration.as_secs() * 1_000_000_000 + u64::from(duration.subsec_nanos());
@@ -0,0 +1,155 @@
+//! A command-line executable for creating contracts from their text description.
+
+extern crate getopts;
+extern crate isatty;
+extern crate serde_json;
+extern crate solana;
+
+use getopts::Options;
+use isatty::stdin_isatty;
+use solana::budget_syntax;
+use solana::crdt::ReplicatedData;
+use solana::keystore;
+use solana::mint::Mint;
+use solana::signature::KeyPair;
+use solana::thin_client::ThinClient;
+use solana::transaction::Transaction;
+use std::env;
+use std::fs::File;
+use std::io::{stdin, Read};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::process::exit;
+use std::time::Duration;
+
+fn print_usage(program: &str, opts: Options) {
+    let mut brief = format!(
+        "Usage: cat <mint.json> | {} [options] <contract>\n\n",
+        program
+    );
+    brief += "  Creates a contract from its text description, for example:\n";
+    brief += "    race(after(2018-07-01T00:00Z) pay 10 to <key>, signed(<key>) pay 10 to <key>)\n";
+    brief += "  signs it with the mint's key and sends it to the leader.\n";
+    brief += "  Takes json formatted mint file to stdin, unless -k names a keypair file\n";
+    brief += "  to sign with instead.";
+
+    print!("{}", opts.usage(&brief));
+}
+
+fn main() {
+    let mut opts = Options::new();
+    opts.optopt("l", "", "leader", "leader.json");
+    opts.optopt("c", "", "client port", "port");
+    opts.optopt("t", "", "number of tokens to lock in the contract", "tokens");
+    opts.optopt("k", "", "keypair file to sign with instead of the mint", "key.json");
+    opts.optflag("p", "print", "print the signed transaction instead of sending it");
+    opts.optflag("h", "help", "print help");
+    let args: Vec<String> = env::args().collect();
+    let matches = match opts.parse(&args[1..]) {
+        Ok(m) => m,
+        Err(e) => {
+            eprintln!("{}", e);
+            exit(1);
+        }
+    };
+
+    if matches.opt_present("h") || matches.free.len() != 1 {
+        let program = args[0].clone();
+        print_usage(&program, opts);
+        return;
+    }
+
+    let budget = budget_syntax::parse(&matches.free[0]).unwrap_or_else(|e| {
+        eprintln!("failed to parse contract: {}", e);
+        exit(1);
+    });
+    let tokens: i64 = match matches.opt_str("t") {
+        Some(t) => t.parse().expect("integer"),
+        None => {
+            eprintln!("missing number of tokens, expected -t");
+            exit(1);
+        }
+    };
+
+    let mut addr: SocketAddr = "0.0.0.0:8100".parse().unwrap();
+    if matches.opt_present("c") {
+        let port = matches.opt_str("c").unwrap().parse().unwrap();
+        addr.set_port(port);
+    }
+    let leader = if matches.opt_present("l") {
+        read_leader(matches.opt_str("l").unwrap())
+    } else {
+        let server_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)), 8000);
+        ReplicatedData::new_leader(&server_addr)
+    };
+
+    let keypair = match matches.opt_str("k") {
+        Some(path) => keystore::read_keypair(&path).unwrap_or_else(|e| {
+            eprintln!("failed to read keypair {}: {}", path, e);
+            exit(1);
+        }),
+        None => read_mint_keypair(),
+    };
+
+    let mut client = mk_client(addr, &leader);
+    let genesis = client.get_genesis();
+    let last_id = client.get_last_id();
+    let tx = Transaction::new_budget(&keypair, budget, tokens, genesis, last_id);
+    if !tx.verify_plan() {
+        eprintln!("contract doesn't spend exactly {} tokens", tokens);
+        exit(1);
+    }
+
+    if matches.opt_present("p") {
+        let serialized = serde_json::to_string(&tx).unwrap_or_else(|e| {
+            eprintln!("failed to serialize: {}", e);
+            exit(1);
+        });
+        println!("{}", serialized);
+        return;
+    }
+
+    client.transfer_signed(tx.clone()).unwrap();
+    match client.confirm_transaction(&tx.sig, Duration::new(10, 0)) {
+        Ok(status) => println!("{:?}", status),
+        Err(e) => {
+            eprintln!("{}", e);
+            exit(1);
+        }
+    }
+}
+
+fn read_mint_keypair() -> KeyPair {
+    if stdin_isatty() {
+        eprintln!("nothing found on stdin, expected a json file");
+        exit(1);
+    }
+
+    let mut buffer = String::new();
+    let num_bytes = stdin().read_to_string(&mut buffer).unwrap();
+    if num_bytes == 0 {
+        eprintln!("empty file on stdin, expected a json file");
+        exit(1);
+    }
+
+    let mut mint: Mint = serde_json::from_str(&buffer).unwrap_or_else(|e| {
+        eprintln!("failed to parse json: {}", e);
+        exit(1);
+    });
+    mint.unlock().unwrap_or_else(|e| {
+        eprintln!("failed to unlock the mint's keystore: {}", e);
+        exit(1);
+    });
+    mint.keypair()
+}
+
+fn mk_client(addr: SocketAddr, r: &ReplicatedData) -> ThinClient {
+    ThinClient::bind(addr, r.requests_addr, r.transactions_addr).unwrap_or_else(|e| {
+        eprintln!("failed to bind client sockets to {}: {}", addr, e);
+        exit(1);
+    })
+}
+
+fn read_leader(path: String) -> ReplicatedData {
+    let file = File::open(path).expect("file");
+    serde_json::from_reader(file).expect("parse")
+}
@@ -4,6 +4,7 @@ extern crate solana;
 
 use getopts::Options;
 use solana::crdt::{get_ip_addr, parse_port_or_addr, ReplicatedData};
+use solana::keystore;
 use std::env;
 use std::io;
 use std::net::SocketAddr;
@@ -12,6 +13,7 @@ use std::process::exit;
 fn print_usage(program: &str, opts: Options) {
     let mut brief = format!("Usage: {} [options]\n\n", program);
     brief += "  Create a solana fullnode config file\n";
+    brief += "  The node's identity is a new key unless -k names a keystore.\n";
 
     print!("{}", opts.usage(&brief));
 }
@@ -20,6 +22,7 @@ fn main() {
     let mut opts = Options::new();
     opts.optopt("b", "", "bind", "bind to port or address");
     opts.optflag("d", "dyn", "detect network address dynamically");
+    opts.optopt("k", "", "keystore holding the node's identity", "key.json");
     opts.optflag("h", "help", "print help");
     let args: Vec<String> = env::args().collect();
     let matches = match opts.parse(&args[1..]) {
@@ -46,7 +49,17 @@ fn main() {
 
     // we need all the receiving sockets to be bound within the expected
     // port range that we open on aws
-    let repl_data = ReplicatedData::new_leader(&bind_addr);
+    let repl_data = match matches.opt_str("k") {
+        Some(path) => {
+            // Only the public key is needed, which is kept in the clear.
+            let keystore = keystore::read_keystore(&path).unwrap_or_else(|e| {
+                eprintln!("failed to read keystore {}: {}", path, e);
+                exit(1);
+            });
+            ReplicatedData::new_leader_with_pubkey(keystore.pubkey, &bind_addr)
+        }
+        None => ReplicatedData::new_leader(&bind_addr),
+    };
     let stdout = io::stdout();
     serde_json::to_writer(stdout, &repl_data).expect("serialize");
 }
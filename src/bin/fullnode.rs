@@ -1,3 +1,4 @@
+extern crate bincode;
 extern crate env_logger;
 extern crate getopts;
 extern crate isatty;
@@ -6,17 +7,16 @@ extern crate solana;
 #[macro_use]
 extern crate log;
 
+use bincode::{deserialize_from, serialize_into};
 use getopts::Options;
 use isatty::stdin_isatty;
-use solana::bank::Bank;
+use solana::bank::{Bank, Snapshot};
 use solana::crdt::ReplicatedData;
 use solana::entry::Entry;
-use solana::payment_plan::PaymentPlan;
 use solana::server::Server;
-use solana::transaction::Instruction;
 use std::env;
 use std::fs::File;
-use std::io::{stdin, Read};
+use std::io::{stdin, BufReader, BufWriter, Read};
 use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
 use std::process::exit;
 use std::sync::atomic::AtomicBool;
@@ -27,7 +27,8 @@ fn print_usage(program: &str, opts: Options) {
     let mut brief = format!("Usage: cat <transaction.log> | {} [options]\n\n", program);
     brief += "  Run a Solana node to handle transactions and\n";
     brief += "  write a new transaction log to stdout.\n";
-    brief += "  Takes existing transaction log from stdin.";
+    brief += "  Takes existing transaction log from stdin.\n";
+    brief += "  With a snapshot, only the entries after the snapshot are replayed.";
 
     print!("{}", opts.usage(&brief));
 }
@@ -36,6 +37,18 @@ fn main() {
     env_logger::init().unwrap();
     let mut opts = Options::new();
     opts.optopt("l", "", "load", "load my identity to path.json");
+    opts.optopt(
+        "s",
+        "",
+        "snapshot",
+        "start from the bank snapshot at path and replay only the ledger tail",
+    );
+    opts.optopt(
+        "o",
+        "",
+        "output snapshot",
+        "write a bank snapshot to path after processing the ledger",
+    );
     opts.optflag("h", "help", "print help");
     opts.optopt(
         "v",
@@ -78,33 +91,56 @@ fn main() {
 
     eprintln!("done parsing...");
 
-    // The first item in the ledger is required to be an entry with zero num_hashes,
-    // which implies its id can be used as the ledger's seed.
-    let entry0 = entries.next().unwrap();
-
-    // The second item in the ledger is a special transaction where the to and from
-    // fields are the same. That entry should be treated as a deposit, not a
-    // transfer to oneself.
-    let entry1: Entry = entries.next().unwrap();
-    let tx = &entry1.transactions[0];
-    let deposit = if let Instruction::NewContract(contract) = &tx.instruction {
-        contract.plan.final_payment()
-    } else {
-        None
-    };
+    let bank = if matches.opt_present("s") {
+        let path = matches.opt_str("s").unwrap();
+        eprintln!("loading snapshot {}...", path);
+        let file = File::open(&path).unwrap_or_else(|e| {
+            eprintln!("failed to open snapshot {}: {}", path, e);
+            exit(1);
+        });
+        let snapshot: Snapshot = deserialize_from(BufReader::new(file)).unwrap_or_else(|e| {
+            eprintln!("failed to parse snapshot: {}", e);
+            exit(1);
+        });
 
-    eprintln!("creating bank...");
+        // Skip every entry up to and including the one the snapshot was taken at.
+        let last_id = snapshot.last_id().unwrap_or_else(|| {
+            eprintln!("snapshot {} holds no entry IDs", path);
+            exit(1);
+        });
+        if !entries.by_ref().any(|entry: Entry| entry.id == last_id) {
+            eprintln!("snapshot entry {:?} not found in the ledger", last_id);
+            exit(1);
+        }
 
-    let bank = Bank::new_from_deposit(&deposit.unwrap());
-    bank.register_entry_id(&entry0.id);
-    bank.register_entry_id(&entry1.id);
+        eprintln!("creating bank from snapshot...");
+        let bank = Bank::from_snapshot(&snapshot).unwrap_or_else(|| {
+            eprintln!("snapshot {} holds no entry IDs", path);
+            exit(1);
+        });
+        if let Err(e) = bank.verify_supply() {
+            eprintln!("snapshot doesn't hold the supply created at genesis: {:?}", e);
+            exit(1);
+        }
+        bank
+    } else {
+        // The first item in the ledger is required to be an entry with zero num_hashes,
+        // which implies its id can be used as the ledger's seed.
+        let entry0: Entry = entries.next().unwrap();
 
-    eprintln!("processing entries...");
-    bank.process_entries(entries).expect("process_entries");
+        // The second item in the ledger starts with a special transaction where the to
+        // and from fields are the same. That entry should be treated as a deposit, not a
+        // transfer to oneself. It may be followed by the mint's choice of time sources.
+        let entry1: Entry = entries.next().unwrap();
 
-    eprintln!("creating networking stack...");
+        eprintln!("creating bank...");
+
+        let bank = Bank::new_from_genesis(&entry1.transactions).unwrap();
+        bank.register_entry(&entry0);
+        bank.register_entry(&entry1);
+        bank
+    };
 
-    let exit = Arc::new(AtomicBool::new(false));
     let bind_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)), 8000);
     let mut repl_data = ReplicatedData::new_leader(&bind_addr);
     if matches.opt_present("l") {
@@ -117,11 +153,32 @@ fn main() {
             }
         }
     }
-    let threads = if matches.opt_present("v") {
-        eprintln!("starting validator... {}", repl_data.requests_addr);
-        let path = matches.opt_str("v").unwrap();
+    let leader: Option<ReplicatedData> = matches.opt_str("v").map(|path| {
         let file = File::open(path).expect("file");
-        let leader = serde_json::from_reader(file).expect("parse");
+        serde_json::from_reader(file).expect("parse")
+    });
+
+    eprintln!("processing entries...");
+    bank.process_entries(entries).expect("process_entries");
+
+    if matches.opt_present("o") {
+        let path = matches.opt_str("o").unwrap();
+        eprintln!("writing snapshot {}...", path);
+        let file = File::create(&path).unwrap_or_else(|e| {
+            eprintln!("failed to create snapshot {}: {}", path, e);
+            exit(1);
+        });
+        serialize_into(&mut BufWriter::new(file), &bank.snapshot()).unwrap_or_else(|e| {
+            eprintln!("failed to write snapshot {}: {}", path, e);
+            exit(1);
+        });
+    }
+
+    eprintln!("creating networking stack...");
+
+    let exit = Arc::new(AtomicBool::new(false));
+    let threads = if let Some(leader) = leader {
+        eprintln!("starting validator... {}", repl_data.requests_addr);
         let s = Server::new_validator(
             bank,
             repl_data.clone(),
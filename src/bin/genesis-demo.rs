@@ -8,7 +8,7 @@ use rayon::prelude::*;
 use solana::bank::MAX_ENTRY_IDS;
 use solana::entry::{next_entry, Entry};
 use solana::mint::MintDemo;
-use solana::signature::{GenKeys, KeyPairUtil};
+use solana::signature::KeyPairUtil;
 use solana::transaction::Transaction;
 use std::io::{stdin, Read};
 use std::process::exit;
@@ -32,13 +32,13 @@ fn main() {
         exit(1);
     });
 
-    let rnd = GenKeys::new(demo.mint.keypair().public_key_bytes());
     let num_accounts = demo.num_accounts;
     let tokens_per_user = 1_000;
 
-    let keypairs = rnd.gen_n_keypairs(num_accounts);
+    let keypairs = demo.keypairs();
 
     let mint_keypair = demo.mint.keypair();
+    let genesis = demo.mint.seed();
     let last_id = demo.mint.last_id();
 
     for entry in demo.mint.create_entries() {
@@ -67,7 +67,7 @@ fn main() {
         .enumerate()
         .map(|(i, rando)| {
             let last_id = last_ids[i % MAX_ENTRY_IDS];
-            Transaction::new(&mint_keypair, rando.pubkey(), tokens_per_user, last_id)
+            Transaction::new(&mint_keypair, rando.pubkey(), tokens_per_user, genesis, last_id)
         })
         .collect();
 
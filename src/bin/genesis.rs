@@ -22,10 +22,14 @@ fn main() {
         exit(1);
     }
 
-    let mint: Mint = serde_json::from_str(&buffer).unwrap_or_else(|e| {
+    let mut mint: Mint = serde_json::from_str(&buffer).unwrap_or_else(|e| {
         eprintln!("failed to parse json: {}", e);
         exit(1);
     });
+    mint.unlock().unwrap_or_else(|e| {
+        eprintln!("failed to unlock the mint's keystore: {}", e);
+        exit(1);
+    });
     for x in mint.create_entries() {
         let serialized = serde_json::to_string(&x).unwrap_or_else(|e| {
             eprintln!("failed to serialize: {}", e);
@@ -0,0 +1,163 @@
+//! A command-line executable for managing keypair files, which are keystores
+//! encrypted with a passphrase.
+
+extern crate getopts;
+extern crate ring;
+extern crate serde_json;
+extern crate solana;
+
+use getopts::Options;
+use ring::rand::SystemRandom;
+use solana::derivation::{self, ExtendedKey};
+use solana::keystore::{self, Keystore};
+use solana::signature::KeyPair;
+use std::env;
+use std::process::exit;
+
+fn print_usage(program: &str, opts: Options) {
+    let mut brief = format!("Usage: {} new [options]\n", program);
+    brief += &format!("       {} import [options]\n", program);
+    brief += &format!("       {} derive [options] <path>\n", program);
+    brief += &format!("       {} export <key.json>\n", program);
+    brief += &format!("       {} pubkey <key.json>\n\n", program);
+    brief += "  new:    Generates a keypair and writes it to a new keystore.\n";
+    brief += "  import: Writes the keypair in the -i file, which is a keystore, a mint file\n";
+    brief += "          or a json array of PKCS#8 bytes, to a new keystore.\n";
+    brief += "  derive: Writes the keypair at a SLIP-0010 path such as m/44'/0', derived\n";
+    brief += "          from the private key in the -i file, to a new keystore.\n";
+    brief += "  export: Prints the keystore's keypair as a json array of PKCS#8 bytes.\n";
+    brief += "  pubkey: Prints the keystore's public key in base58. No passphrase is needed.\n";
+    brief += &format!(
+        "  The passphrase is read from the terminal, or from ${} if it's set.",
+        keystore::PASSPHRASE_VAR
+    );
+
+    print!("{}", opts.usage(&brief));
+}
+
+fn main() {
+    let mut opts = Options::new();
+    opts.optopt("o", "", "keystore file to create", "key.json");
+    opts.optopt("i", "", "keypair file to import or derive from", "mint.json");
+    opts.optflag("h", "help", "print help");
+    let args: Vec<String> = env::args().collect();
+    let matches = match opts.parse(&args[1..]) {
+        Ok(m) => m,
+        Err(e) => {
+            eprintln!("{}", e);
+            exit(1);
+        }
+    };
+
+    let program = args[0].clone();
+    if matches.opt_present("h") || matches.free.is_empty() {
+        print_usage(&program, opts);
+        return;
+    }
+
+    match matches.free[0].as_str() {
+        "new" => {
+            let pkcs8 = KeyPair::generate_pkcs8(&SystemRandom::new())
+                .expect("generate_pkcs8")
+                .to_vec();
+            write(&required(&matches, "o"), &pkcs8);
+        }
+        "import" => {
+            let pkcs8 = read_pkcs8(&required(&matches, "i"));
+            write(&required(&matches, "o"), &pkcs8);
+        }
+        "derive" => {
+            if matches.free.len() != 2 {
+                eprintln!("expected a single derivation path");
+                exit(1);
+            }
+            let path = derivation::parse_path(&matches.free[1]).unwrap_or_else(|e| {
+                eprintln!("invalid derivation path `{}`: {:?}", matches.free[1], e);
+                exit(1);
+            });
+            let input = required(&matches, "i");
+            let pkcs8 = read_pkcs8(&input);
+            let seed = derivation::pkcs8_seed(&pkcs8).unwrap_or_else(|| {
+                eprintln!("no ED25519 private key in {}", input);
+                exit(1);
+            });
+            let key = ExtendedKey::new_master(seed).derive(&path);
+            write(&required(&matches, "o"), &key.pkcs8());
+        }
+        "export" => {
+            let path = keystore_path(&matches);
+            let keystore = read(&path);
+            let passphrase = read_passphrase(&format!("Passphrase for {}: ", path));
+            let pkcs8 = keystore.decrypt(&passphrase).unwrap_or_else(|e| {
+                eprintln!("failed to unlock keystore {}: {:?}", path, e);
+                exit(1);
+            });
+            println!("{}", serde_json::to_string(&pkcs8).expect("serialize"));
+        }
+        "pubkey" => {
+            let keystore = read(&keystore_path(&matches));
+            println!("{}", keystore.pubkey);
+        }
+        command => {
+            eprintln!("unknown command `{}`", command);
+            print_usage(&program, opts);
+            exit(1);
+        }
+    }
+}
+
+fn required(matches: &getopts::Matches, name: &str) -> String {
+    matches.opt_str(name).unwrap_or_else(|| {
+        eprintln!("missing option -{}", name);
+        exit(1);
+    })
+}
+
+fn keystore_path(matches: &getopts::Matches) -> String {
+    if matches.free.len() != 2 {
+        eprintln!("expected a single keystore file");
+        exit(1);
+    }
+    matches.free[1].clone()
+}
+
+fn read_passphrase(prompt: &str) -> String {
+    keystore::read_passphrase(prompt).unwrap_or_else(|e| {
+        eprintln!("failed to read passphrase: {}", e);
+        exit(1);
+    })
+}
+
+fn read(path: &str) -> Keystore {
+    keystore::read_keystore(path).unwrap_or_else(|e| {
+        eprintln!("failed to read keystore {}: {}", path, e);
+        exit(1);
+    })
+}
+
+/// Seal the keypair with a new passphrase, typed twice unless it comes from
+/// the environment, and write it to a file that doesn't exist yet.
+fn write(path: &str, pkcs8: &[u8]) {
+    let passphrase = read_passphrase("New passphrase: ");
+    if env::var(keystore::PASSPHRASE_VAR).is_err()
+        && read_passphrase("Repeat passphrase: ") != passphrase
+    {
+        eprintln!("passphrases don't match");
+        exit(1);
+    }
+    let keystore = Keystore::new(pkcs8, &passphrase).unwrap_or_else(|e| {
+        eprintln!("failed to create keystore: {:?}", e);
+        exit(1);
+    });
+    keystore::write_keystore(path, &keystore).unwrap_or_else(|e| {
+        eprintln!("failed to write {}: {}", path, e);
+        exit(1);
+    });
+}
+
+fn read_pkcs8(path: &str) -> Vec<u8> {
+    keystore::read_pkcs8(path).unwrap_or_else(|e| {
+        eprintln!("failed to read keypair {}: {}", path, e);
+        exit(1);
+    })
+}
@@ -1,13 +1,46 @@
+extern crate getopts;
 extern crate isatty;
 extern crate serde_json;
 extern crate solana;
 
+use getopts::Options;
 use isatty::stdin_isatty;
+use solana::keystore;
 use solana::mint::Mint;
+use std::env;
 use std::io;
 use std::process::exit;
 
+fn print_usage(program: &str, opts: Options) {
+    let mut brief = format!("Usage: echo <tokens> | {} [options]\n\n", program);
+    brief += "  Create a mint file holding the given number of tokens. The mint's key\n";
+    brief += "  is new unless -k names a keystore, in which case the mint file holds\n";
+    brief += "  the keystore's path instead of the key, and the keystore is unlocked\n";
+    brief += "  when the genesis ledger is signed. Fees are collected by the mint\n";
+    brief += "  unless -c names another account.";
+
+    print!("{}", opts.usage(&brief));
+}
+
 fn main() {
+    let mut opts = Options::new();
+    opts.optopt("k", "", "keystore holding the mint's key", "key.json");
+    opts.optopt("c", "", "account that collects transaction fees", "pubkey");
+    opts.optflag("h", "help", "print help");
+    let args: Vec<String> = env::args().collect();
+    let matches = match opts.parse(&args[1..]) {
+        Ok(m) => m,
+        Err(e) => {
+            eprintln!("{}", e);
+            exit(1);
+        }
+    };
+    if matches.opt_present("h") {
+        let program = args[0].clone();
+        print_usage(&program, opts);
+        return;
+    }
+
     let mut input_text = String::new();
     if stdin_isatty() {
         eprintln!("nothing found on stdin, expected a token number");
@@ -20,7 +53,23 @@ fn main() {
         eprintln!("{}", e);
         exit(1);
     });
-    let mint = Mint::new(tokens);
+    let fee_collector = matches.opt_str("c").map(|text| {
+        text.parse().unwrap_or_else(|e| {
+            eprintln!("invalid fee collector {}: {}", text, e);
+            exit(1);
+        })
+    });
+    let mut mint = match matches.opt_str("k") {
+        Some(path) => {
+            let keystore = keystore::read_keystore(&path).unwrap_or_else(|e| {
+                eprintln!("failed to read keystore {}: {}", path, e);
+                exit(1);
+            });
+            Mint::new_with_keystore(tokens, &path, &keystore)
+        }
+        None => Mint::new(tokens),
+    };
+    mint.fee_collector = fee_collector;
     let serialized = serde_json::to_string(&mint).unwrap_or_else(|e| {
         eprintln!("failed to serialize: {}", e);
         exit(1);
@@ -0,0 +1,238 @@
+//! A command-line executable for signing transactions on a machine that isn't
+//! connected to the network, and for submitting them from one that is.
+
+extern crate chrono;
+extern crate getopts;
+extern crate serde_json;
+extern crate solana;
+
+use chrono::prelude::*;
+use getopts::Options;
+use solana::bank::SignatureStatus;
+use solana::base58::Base58Error;
+use solana::crdt::ReplicatedData;
+use solana::keystore;
+use solana::signature::{KeyPair, PublicKey, Signature};
+use solana::thin_client::ThinClient;
+use solana::transaction::Transaction;
+use std::env;
+use std::fs::File;
+use std::io::{stdout, Read, Write};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::process::exit;
+use std::str::FromStr;
+use std::time::Duration;
+
+fn print_usage(program: &str, opts: Options) {
+    let mut brief = format!("Usage: {} sign [options]\n", program);
+    brief += &format!("       {} submit [options] <tx.json>...\n", program);
+    brief += &format!("       {} query [options]\n\n", program);
+    brief += "  sign:   Creates a transaction and signs it with the key in the keypair file,\n";
+    brief += "          which is a keystore, a mint file or a json array of PKCS#8 bytes.\n";
+    brief += "          Needs -k, -g, -i, and one of --to with --tokens, --timestamp,\n";
+    brief += "          --witness or --advance-nonce. The network isn't used.\n";
+    brief += "  submit: Sends signed transactions to the leader and reports their status.\n";
+    brief += "  query:  Prints the leader's genesis and last ID, and the nonce of --key.\n";
+    brief += "  Keys, hashes and signatures are written in base58. The last ID passed to -i\n";
+    brief += "  may be the sender's nonce, so that the transaction doesn't expire.";
+
+    print!("{}", opts.usage(&brief));
+}
+
+fn main() {
+    let mut opts = Options::new();
+    opts.optopt("k", "", "keypair file of the sender", "keypair.json");
+    opts.optopt("g", "", "genesis of the ledger", "hash");
+    opts.optopt("i", "", "a recent last ID, or the sender's nonce", "hash");
+    opts.optopt("o", "", "write the signed transaction to a file", "tx.json");
+    opts.optopt("", "to", "pay tokens to this key", "key");
+    opts.optopt("", "tokens", "number of tokens to pay", "tokens");
+    opts.optopt("", "on", "postdate the payment, cancelable until then", "datetime");
+    opts.optopt("", "timestamp", "witness that the time is now", "datetime");
+    opts.optopt("", "witness", "witness the contract with this signature", "signature");
+    opts.optflag("", "advance-nonce", "create or advance the sender's nonce");
+    opts.optopt("", "key", "key whose nonce to query", "key");
+    opts.optopt("l", "", "leader", "leader.json");
+    opts.optopt("c", "", "client port", "port");
+    opts.optflag("h", "help", "print help");
+    let args: Vec<String> = env::args().collect();
+    let matches = match opts.parse(&args[1..]) {
+        Ok(m) => m,
+        Err(e) => {
+            eprintln!("{}", e);
+            exit(1);
+        }
+    };
+
+    let program = args[0].clone();
+    if matches.opt_present("h") || matches.free.is_empty() {
+        print_usage(&program, opts);
+        return;
+    }
+
+    match matches.free[0].as_str() {
+        "sign" => sign(&matches),
+        "submit" => submit(&matches),
+        "query" => query(&matches),
+        command => {
+            eprintln!("unknown command `{}`", command);
+            print_usage(&program, opts);
+            exit(1);
+        }
+    }
+}
+
+fn sign(matches: &getopts::Matches) {
+    let keypair = read_keypair(&required(matches, "k"));
+    let genesis = parse(&required(matches, "g"), "hash");
+    let last_id = parse(&required(matches, "i"), "hash");
+
+    let tx = if let Some(to) = matches.opt_str("to") {
+        let to: PublicKey = parse(&to, "key");
+        let tokens: i64 = required(matches, "tokens").parse().unwrap_or_else(|e| {
+            eprintln!("invalid number of tokens: {}", e);
+            exit(1);
+        });
+        match matches.opt_str("on") {
+            Some(dt) => {
+                let dt = parse_datetime(&dt);
+                Transaction::new_on_date(&keypair, to, dt, tokens, genesis, last_id)
+            }
+            None => Transaction::new(&keypair, to, tokens, genesis, last_id),
+        }
+    } else if let Some(dt) = matches.opt_str("timestamp") {
+        Transaction::new_timestamp(&keypair, parse_datetime(&dt), genesis, last_id)
+    } else if let Some(sig) = matches.opt_str("witness") {
+        let sig: Signature = parse(&sig, "signature");
+        Transaction::new_signature(&keypair, sig, genesis, last_id)
+    } else if matches.opt_present("advance-nonce") {
+        Transaction::new_advance_nonce(&keypair, genesis, last_id)
+    } else {
+        eprintln!("missing transaction, expected --to, --timestamp, --witness or --advance-nonce");
+        exit(1);
+    };
+
+    let serialized = serde_json::to_string(&tx).unwrap_or_else(|e| {
+        eprintln!("failed to serialize: {}", e);
+        exit(1);
+    });
+    match matches.opt_str("o") {
+        Some(path) => {
+            let mut file = File::create(&path).unwrap_or_else(|e| {
+                eprintln!("failed to create {}: {}", path, e);
+                exit(1);
+            });
+            writeln!(file, "{}", serialized).unwrap();
+        }
+        None => writeln!(stdout(), "{}", serialized).unwrap(),
+    }
+    eprintln!("signature {}", tx.sig);
+}
+
+fn submit(matches: &getopts::Matches) {
+    if matches.free.len() < 2 {
+        eprintln!("missing transaction files");
+        exit(1);
+    }
+    let mut client = mk_client(matches);
+    let mut failed = false;
+    for path in &matches.free[1..] {
+        let mut buffer = String::new();
+        File::open(path)
+            .and_then(|mut file| file.read_to_string(&mut buffer))
+            .unwrap_or_else(|e| {
+                eprintln!("failed to read {}: {}", path, e);
+                exit(1);
+            });
+        let tx: Transaction = serde_json::from_str(&buffer).unwrap_or_else(|e| {
+            eprintln!("failed to parse {}: {}", path, e);
+            exit(1);
+        });
+        if let Err(e) = client.transfer_signed(tx.clone()) {
+            eprintln!("{}: {}", path, e);
+            failed = true;
+            continue;
+        }
+        match client.confirm_transaction(&tx.sig, Duration::new(10, 0)) {
+            Ok(status) => {
+                if let SignatureStatus::Failed(_) = status {
+                    failed = true;
+                }
+                println!("{}: {} {:?}", path, tx.sig, status);
+            }
+            Err(e) => {
+                println!("{}: {} {}", path, tx.sig, e);
+                failed = true;
+            }
+        }
+    }
+    if failed {
+        exit(1);
+    }
+}
+
+fn query(matches: &getopts::Matches) {
+    let mut client = mk_client(matches);
+    println!("genesis {}", client.get_genesis());
+    println!("last_id {}", client.get_last_id());
+    if let Some(key) = matches.opt_str("key") {
+        let key: PublicKey = parse(&key, "key");
+        match client.get_nonce(&key) {
+            Ok(nonce) => println!("nonce {}", nonce),
+            Err(_) => println!("nonce none"),
+        }
+    }
+}
+
+fn required(matches: &getopts::Matches, name: &str) -> String {
+    matches.opt_str(name).unwrap_or_else(|| {
+        eprintln!("missing option -{}", name);
+        exit(1);
+    })
+}
+
+fn read_keypair(path: &str) -> KeyPair {
+    keystore::read_keypair(path).unwrap_or_else(|e| {
+        eprintln!("failed to read keypair {}: {}", path, e);
+        exit(1);
+    })
+}
+
+fn parse<T: FromStr<Err = Base58Error>>(text: &str, what: &str) -> T {
+    text.trim().parse().unwrap_or_else(|e| {
+        eprintln!("invalid {} `{}`: {}", what, text, e);
+        exit(1);
+    })
+}
+
+fn parse_datetime(text: &str) -> DateTime<Utc> {
+    DateTime::parse_from_rfc3339(text)
+        .map(|dt| dt.with_timezone(&Utc))
+        .unwrap_or_else(|e| {
+            eprintln!("invalid date `{}`, expected RFC 3339: {}", text, e);
+            exit(1);
+        })
+}
+
+fn mk_client(matches: &getopts::Matches) -> ThinClient {
+    let mut addr: SocketAddr = "0.0.0.0:8100".parse().unwrap();
+    if let Some(port) = matches.opt_str("c") {
+        addr.set_port(port.parse().expect("port"));
+    }
+    let leader = match matches.opt_str("l") {
+        Some(path) => read_leader(path),
+        None => {
+            let server_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)), 8000);
+            ReplicatedData::new_leader(&server_addr)
+        }
+    };
+    ThinClient::bind(addr, leader.requests_addr, leader.transactions_addr).unwrap_or_else(|e| {
+        eprintln!("failed to bind client sockets to {}: {}", addr, e);
+        exit(1);
+    })
+}
+
+fn read_leader(path: String) -> ReplicatedData {
+    let file = File::open(path).expect("file");
+    serde_json::from_reader(file).expect("parse")
+}
@@ -4,6 +4,7 @@
 //! `Payment`, the payment is executed.
 
 use chrono::prelude::*;
+use hash::{hash, Hash};
 use payment_plan::{Payment, PaymentPlan, Witness};
 use signature::PublicKey;
 use std::mem;
@@ -12,6 +13,16 @@ use std::mem;
 pub enum Condition {
     Timestamp(DateTime<Utc>),
     Signature(PublicKey),
+    Hash(Hash),
+    /// Satisfied once the ledger has at least this many ticks.
+    TickHeight(u64),
+    /// Satisfied once `m` of the `signers` have approved. Approvals are
+    /// recorded as signature witnesses arrive.
+    MultiSignature {
+        m: u64,
+        signers: Vec<PublicKey>,
+        approvals: Vec<PublicKey>,
+    },
 }
 
 impl Condition {
@@ -20,9 +31,79 @@ impl Condition {
         match (self, witness) {
             (Condition::Signature(pubkey), Witness::Signature(from)) => pubkey == from,
             (Condition::Timestamp(dt), Witness::Timestamp(last_time)) => dt <= last_time,
+            (Condition::Hash(h), Witness::Preimage(preimage)) => *h == hash(preimage),
+            (Condition::TickHeight(n), Witness::TickHeight(height)) => n <= height,
+            (Condition::MultiSignature { m, approvals, .. }, Witness::Signature(_)) => {
+                approvals.len() as u64 >= *m
+            }
             _ => false,
         }
     }
+
+    /// Record the approval of a signer, if the given Witness is one.
+    pub fn approve(&mut self, witness: &Witness) {
+        if !self.would_approve(witness) {
+            return;
+        }
+        if let (Condition::MultiSignature { approvals, .. }, Witness::Signature(from)) =
+            (self, witness)
+        {
+            approvals.push(*from);
+        }
+    }
+
+    /// Return true if the given Witness is an approval this Condition would record.
+    pub fn would_approve(&self, witness: &Witness) -> bool {
+        match (self, witness) {
+            (
+                Condition::MultiSignature {
+                    signers, approvals, ..
+                },
+                Witness::Signature(from),
+            ) => signers.contains(from) && !approvals.contains(from),
+            _ => false,
+        }
+    }
+
+    /// Return true if the given Witness is an approval this Condition has
+    /// already recorded.
+    pub fn is_approved_by(&self, witness: &Witness) -> bool {
+        match (self, witness) {
+            (Condition::MultiSignature { approvals, .. }, Witness::Signature(from)) => {
+                approvals.contains(from)
+            }
+            _ => false,
+        }
+    }
+
+    /// Return true if `pubkey` can help satisfy this Condition.
+    pub fn involves(&self, pubkey: &PublicKey) -> bool {
+        match self {
+            Condition::Signature(from) => from == pubkey,
+            Condition::MultiSignature { signers, .. } => signers.contains(pubkey),
+            Condition::Timestamp(_) | Condition::Hash(_) | Condition::TickHeight(_) => false,
+        }
+    }
+
+    /// Return true if the Condition can be satisfied. A new multisignature
+    /// condition must have no approvals, distinct signers, and a threshold
+    /// between 1 and the number of signers.
+    pub fn verify(&self) -> bool {
+        match self {
+            Condition::MultiSignature {
+                m,
+                signers,
+                approvals,
+            } => {
+                let distinct = signers
+                    .iter()
+                    .enumerate()
+                    .all(|(i, signer)| !signers[..i].contains(signer));
+                approvals.is_empty() && distinct && *m > 0 && *m <= signers.len() as u64
+            }
+            _ => true,
+        }
+    }
 }
 
 #[repr(C)]
@@ -31,6 +112,19 @@ pub enum Budget {
     Pay(Payment),
     After(Condition, Payment),
     Race((Condition, Payment), (Condition, Payment)),
+    Split(Vec<Payment>),
+
+    /// Once the Condition is satisfied, continue with the inner Budget.
+    When(Condition, Box<Budget>),
+
+    /// Once both Conditions are satisfied, in either order, continue with the
+    /// inner Budget.
+    And(Condition, Condition, Box<Budget>),
+
+    /// Reduce every arm, and collapse to the first one that can make its
+    /// payments. Arms are checked in order, so when one witness completes
+    /// several arms, the first of them wins.
+    Or(Vec<Budget>),
 }
 
 impl Budget {
@@ -39,11 +133,53 @@ impl Budget {
         Budget::Pay(Payment { tokens, to })
     }
 
+    /// Create a hash time-locked budget that pays `tokens` to `to` once someone
+    /// reveals the preimage of `hash`, or refunds `from` after the given DateTime.
+    pub fn new_hash_time_locked_payment(
+        hash: Hash,
+        dt: DateTime<Utc>,
+        from: PublicKey,
+        tokens: i64,
+        to: PublicKey,
+    ) -> Self {
+        Budget::Race(
+            (Condition::Hash(hash), Payment { tokens, to }),
+            (Condition::Timestamp(dt), Payment { tokens, to: from }),
+        )
+    }
+
+    /// Create a budget that pays `tokens` to `to` once `m` of the `signers`
+    /// have signed.
+    pub fn new_multisig_payment(
+        m: u64,
+        signers: Vec<PublicKey>,
+        tokens: i64,
+        to: PublicKey,
+    ) -> Self {
+        let cond = Condition::MultiSignature {
+            m,
+            signers,
+            approvals: vec![],
+        };
+        Budget::After(cond, Payment { tokens, to })
+    }
+
+    /// Create a budget that pays every Payment in `payments` at once.
+    pub fn new_split_payment(payments: Vec<Payment>) -> Self {
+        Budget::Split(payments)
+    }
+
     /// Create a budget that pays `tokens` to `to` after being witnessed by `from`.
     pub fn new_authorized_payment(from: PublicKey, tokens: i64, to: PublicKey) -> Self {
         Budget::After(Condition::Signature(from), Payment { tokens, to })
     }
 
+    /// Create a budget that pays `tokens` to `to` once the ledger reaches the
+    /// given tick height.
+    pub fn new_tick_height_payment(height: u64, tokens: i64, to: PublicKey) -> Self {
+        Budget::After(Condition::TickHeight(height), Payment { tokens, to })
+    }
+
     /// Create a budget that pays `tokens` to `to` after the given DateTime.
     pub fn new_future_payment(dt: DateTime<Utc>, tokens: i64, to: PublicKey) -> Self {
         Budget::After(Condition::Timestamp(dt), Payment { tokens, to })
@@ -62,6 +198,17 @@ impl Budget {
             (Condition::Signature(from), Payment { tokens, to: from }),
         )
     }
+
+    /// Return the Conditions the budget is currently waiting on.
+    fn pending_conditions(&self) -> Vec<&Condition> {
+        match self {
+            Budget::Pay(_) | Budget::Split(_) => vec![],
+            Budget::After(cond, _) | Budget::When(cond, _) => vec![cond],
+            Budget::Race(a, b) => vec![&a.0, &b.0],
+            Budget::And(cond0, cond1, _) => vec![cond0, cond1],
+            Budget::Or(arms) => arms.iter().flat_map(|arm| arm.pending_conditions()).collect(),
+        }
+    }
 }
 
 impl PaymentPlan for Budget {
@@ -73,26 +220,157 @@ impl PaymentPlan for Budget {
         }
     }
 
+    /// Return every Payment if the budget requires no additional Witnesses.
+    fn final_payments(&self) -> Option<Vec<Payment>> {
+        match self {
+            Budget::Pay(payment) => Some(vec![payment.clone()]),
+            Budget::Split(payments) => Some(payments.clone()),
+            Budget::Or(arms) => arms.iter().filter_map(|arm| arm.final_payments()).next(),
+            _ => None,
+        }
+    }
+
     /// Return true if the budget spends exactly `spendable_tokens`.
     fn verify(&self, spendable_tokens: i64) -> bool {
+        // A plan can never pay out a negative amount, since that would credit
+        // the sender at the expense of the payee.
+        if spendable_tokens < 0 {
+            return false;
+        }
         match self {
-            Budget::Pay(payment) | Budget::After(_, payment) => payment.tokens == spendable_tokens,
-            Budget::Race(a, b) => a.1.tokens == spendable_tokens && b.1.tokens == spendable_tokens,
+            Budget::Pay(payment) => payment.tokens == spendable_tokens,
+            Budget::After(cond, payment) => cond.verify() && payment.tokens == spendable_tokens,
+            Budget::Race(a, b) => {
+                a.0.verify() && b.0.verify() && a.1.tokens == spendable_tokens
+                    && b.1.tokens == spendable_tokens
+            }
+            Budget::Split(payments) => {
+                // Each payment must be non-negative, so that one recipient can't
+                // be funded at the expense of another.
+                let mut total = 0i64;
+                for payment in payments {
+                    if payment.tokens < 0 {
+                        return false;
+                    }
+                    match total.checked_add(payment.tokens) {
+                        Some(sum) => total = sum,
+                        None => return false,
+                    }
+                }
+                !payments.is_empty() && total == spendable_tokens
+            }
+            Budget::When(cond, budget) => cond.verify() && budget.verify(spendable_tokens),
+            Budget::And(cond0, cond1, budget) => {
+                cond0.verify() && cond1.verify() && budget.verify(spendable_tokens)
+            }
+            Budget::Or(arms) => {
+                !arms.is_empty() && arms.iter().all(|arm| arm.verify(spendable_tokens))
+            }
+        }
+    }
+
+    /// Return true if the budget has already recorded the given Witness as an
+    /// approval, and none of the conditions it's waiting on would record it.
+    fn is_duplicate_witness(&self, witness: &Witness) -> bool {
+        let conds = self.pending_conditions();
+        conds.iter().any(|cond| cond.is_approved_by(witness))
+            && !conds.iter().any(|cond| cond.would_approve(witness))
+    }
+
+    /// Return the earliest date among the Timestamp conditions of the budget,
+    /// including those of the budgets nested in When, And and Or. A nested
+    /// condition can't be reached before the ones around it, so this is never
+    /// later than the first time a timestamp could reduce the budget.
+    fn next_deadline(&self) -> Option<DateTime<Utc>> {
+        let own = self.pending_conditions()
+            .into_iter()
+            .filter_map(|cond| match cond {
+                Condition::Timestamp(dt) => Some(*dt),
+                _ => None,
+            })
+            .min();
+        let nested = match self {
+            Budget::When(_, budget) | Budget::And(_, _, budget) => budget.next_deadline(),
+            Budget::Or(arms) => arms.iter().filter_map(|arm| arm.next_deadline()).min(),
+            _ => None,
+        };
+        own.into_iter().chain(nested).min()
+    }
+
+    /// Return true if `pubkey` is a payee of any branch of the budget, or
+    /// can witness one of its conditions.
+    fn involves(&self, pubkey: &PublicKey) -> bool {
+        match self {
+            Budget::Pay(payment) => payment.to == *pubkey,
+            Budget::After(cond, payment) => cond.involves(pubkey) || payment.to == *pubkey,
+            Budget::Race(a, b) => {
+                a.0.involves(pubkey) || a.1.to == *pubkey || b.0.involves(pubkey)
+                    || b.1.to == *pubkey
+            }
+            Budget::Split(payments) => payments.iter().any(|payment| payment.to == *pubkey),
+            Budget::When(cond, budget) => cond.involves(pubkey) || budget.involves(pubkey),
+            Budget::And(cond0, cond1, budget) => {
+                cond0.involves(pubkey) || cond1.involves(pubkey) || budget.involves(pubkey)
+            }
+            Budget::Or(arms) => arms.iter().any(|arm| arm.involves(pubkey)),
         }
     }
 
     /// Apply a witness to the budget to see if the budget can be reduced.
-    /// If so, modify the budget in-place.
+    /// If so, modify the budget in-place. Each witness reduces a budget by
+    /// at most one step.
     fn apply_witness(&mut self, witness: &Witness) {
-        let new_payment = match self {
-            Budget::After(cond, payment) if cond.is_satisfied(witness) => Some(payment),
-            Budget::Race((cond, payment), _) if cond.is_satisfied(witness) => Some(payment),
-            Budget::Race(_, (cond, payment)) if cond.is_satisfied(witness) => Some(payment),
-            _ => None,
-        }.cloned();
+        let new_budget = match self {
+            Budget::Pay(_) | Budget::Split(_) => None,
+            Budget::After(cond, payment) => {
+                cond.approve(witness);
+                if cond.is_satisfied(witness) {
+                    Some(Budget::Pay(payment.clone()))
+                } else {
+                    None
+                }
+            }
+            Budget::Race((cond0, payment0), (cond1, payment1)) => {
+                cond0.approve(witness);
+                cond1.approve(witness);
+                if cond0.is_satisfied(witness) {
+                    Some(Budget::Pay(payment0.clone()))
+                } else if cond1.is_satisfied(witness) {
+                    Some(Budget::Pay(payment1.clone()))
+                } else {
+                    None
+                }
+            }
+            Budget::When(cond, budget) => {
+                cond.approve(witness);
+                if cond.is_satisfied(witness) {
+                    Some((**budget).clone())
+                } else {
+                    None
+                }
+            }
+            Budget::And(cond0, cond1, budget) => {
+                cond0.approve(witness);
+                cond1.approve(witness);
+                match (cond0.is_satisfied(witness), cond1.is_satisfied(witness)) {
+                    (true, true) => Some((**budget).clone()),
+                    (true, false) => Some(Budget::When(cond1.clone(), budget.clone())),
+                    (false, true) => Some(Budget::When(cond0.clone(), budget.clone())),
+                    (false, false) => None,
+                }
+            }
+            Budget::Or(arms) => {
+                for arm in arms.iter_mut() {
+                    arm.apply_witness(witness);
+                }
+                arms.iter()
+                    .find(|arm| arm.final_payments().is_some())
+                    .cloned()
+            }
+        };
 
-        if let Some(payment) = new_payment {
-            mem::replace(self, Budget::Pay(payment));
+        if let Some(budget) = new_budget {
+            mem::replace(self, budget);
         }
     }
 }
@@ -116,6 +394,25 @@ mod tests {
         assert!(!Condition::Timestamp(dt2).is_satisfied(&Witness::Timestamp(dt1)));
     }
 
+    #[test]
+    fn test_hash_satisfied() {
+        let preimage = b"secret".to_vec();
+        let cond = Condition::Hash(hash(&preimage));
+        assert!(cond.is_satisfied(&Witness::Preimage(preimage)));
+        assert!(!cond.is_satisfied(&Witness::Preimage(b"guess".to_vec())));
+        assert!(!cond.is_satisfied(&Witness::Signature(PublicKey::default())));
+    }
+
+    #[test]
+    fn test_tick_height_satisfied() {
+        let cond = Condition::TickHeight(10);
+        assert!(cond.is_satisfied(&Witness::TickHeight(10)));
+        assert!(cond.is_satisfied(&Witness::TickHeight(11)));
+        assert!(!cond.is_satisfied(&Witness::TickHeight(9)));
+        let dt = Utc.ymd(2014, 11, 14).and_hms(8, 9, 10);
+        assert!(!cond.is_satisfied(&Witness::Timestamp(dt)));
+    }
+
     #[test]
     fn test_verify() {
         let dt = Utc.ymd(2014, 11, 14).and_hms(8, 9, 10);
@@ -125,6 +422,226 @@ mod tests {
         assert!(Budget::new_authorized_payment(from, 42, to).verify(42));
         assert!(Budget::new_future_payment(dt, 42, to).verify(42));
         assert!(Budget::new_cancelable_future_payment(dt, from, 42, to).verify(42));
+        assert!(Budget::new_hash_time_locked_payment(Hash::default(), dt, from, 42, to).verify(42));
+        assert!(!Budget::new_payment(-42, to).verify(-42));
+    }
+
+    #[test]
+    fn test_next_deadline() {
+        let dt0 = Utc.ymd(2014, 11, 14).and_hms(8, 9, 10);
+        let dt1 = Utc.ymd(2014, 11, 15).and_hms(8, 9, 10);
+        let from = PublicKey::default();
+        let to = PublicKey::clone_from_slice(&[1; 32]);
+        assert_eq!(Budget::new_payment(42, to).next_deadline(), None);
+        assert_eq!(Budget::new_authorized_payment(from, 42, to).next_deadline(), None);
+        assert_eq!(
+            Budget::new_cancelable_future_payment(dt0, from, 42, to).next_deadline(),
+            Some(dt0)
+        );
+        let budget = Budget::Or(vec![
+            Budget::new_future_payment(dt1, 42, to),
+            Budget::new_future_payment(dt0, 42, from),
+        ]);
+        assert_eq!(budget.next_deadline(), Some(dt0));
+
+        // Deadlines nested under other conditions count too.
+        let signed = Condition::Signature(from);
+        let inner = Box::new(Budget::new_future_payment(dt1, 42, to));
+        let budget = Budget::When(signed.clone(), inner.clone());
+        assert_eq!(budget.next_deadline(), Some(dt1));
+        let budget = Budget::And(signed.clone(), Condition::Timestamp(dt0), inner.clone());
+        assert_eq!(budget.next_deadline(), Some(dt0));
+        let budget = Budget::Or(vec![
+            Budget::new_authorized_payment(from, 42, to),
+            Budget::When(signed, inner),
+        ]);
+        assert_eq!(budget.next_deadline(), Some(dt1));
+    }
+
+    #[test]
+    fn test_involves() {
+        let dt = Utc.ymd(2014, 11, 14).and_hms(8, 9, 10);
+        let from = PublicKey::default();
+        let to = PublicKey::clone_from_slice(&[1; 32]);
+        let other = PublicKey::clone_from_slice(&[2; 32]);
+        let budget = Budget::new_cancelable_future_payment(dt, from, 42, to);
+        assert!(budget.involves(&from));
+        assert!(budget.involves(&to));
+        assert!(!budget.involves(&other));
+
+        let budget = Budget::new_multisig_payment(1, vec![other], 42, to);
+        assert!(budget.involves(&other));
+        assert!(!budget.involves(&from));
+    }
+
+    #[test]
+    fn test_verify_split_payment() {
+        let to = PublicKey::default();
+        let payment = |tokens| Payment { tokens, to };
+        assert!(Budget::new_split_payment(vec![payment(40), payment(2)]).verify(42));
+        assert!(!Budget::new_split_payment(vec![payment(40), payment(1)]).verify(42));
+        assert!(!Budget::new_split_payment(vec![payment(43), payment(-1)]).verify(42));
+        assert!(!Budget::new_split_payment(vec![payment(i64::max_value()), payment(1)]).verify(0));
+        assert!(!Budget::new_split_payment(vec![]).verify(0));
+    }
+
+    #[test]
+    fn test_split_payment() {
+        let to = PublicKey::default();
+        let payments = vec![Payment { tokens: 1, to }, Payment { tokens: 2, to }];
+        let budget = Budget::new_split_payment(payments.clone());
+        assert_eq!(budget.final_payment(), None);
+        assert_eq!(budget.final_payments(), Some(payments));
+    }
+
+    #[test]
+    fn test_verify_multisig_payment() {
+        let a = PublicKey::clone_from_slice(&[1; 32]);
+        let b = PublicKey::clone_from_slice(&[2; 32]);
+        let to = PublicKey::default();
+        assert!(Budget::new_multisig_payment(2, vec![a, b], 42, to).verify(42));
+        assert!(!Budget::new_multisig_payment(3, vec![a, b], 42, to).verify(42));
+        assert!(!Budget::new_multisig_payment(0, vec![a, b], 42, to).verify(42));
+        assert!(!Budget::new_multisig_payment(2, vec![a, a], 42, to).verify(42));
+
+        let cond = Condition::MultiSignature {
+            m: 1,
+            signers: vec![a, b],
+            approvals: vec![a],
+        };
+        assert!(!Budget::After(cond, Payment { tokens: 42, to }).verify(42));
+    }
+
+    #[test]
+    fn test_multisig_payment() {
+        let a = PublicKey::clone_from_slice(&[1; 32]);
+        let b = PublicKey::clone_from_slice(&[2; 32]);
+        let c = PublicKey::clone_from_slice(&[3; 32]);
+        let outsider = PublicKey::clone_from_slice(&[4; 32]);
+        let to = PublicKey::default();
+
+        let mut budget = Budget::new_multisig_payment(2, vec![a, b, c], 42, to);
+        budget.apply_witness(&Witness::Signature(outsider));
+        budget.apply_witness(&Witness::Signature(b));
+        assert_eq!(budget.final_payment(), None);
+        assert!(budget.is_duplicate_witness(&Witness::Signature(b)));
+        assert!(!budget.is_duplicate_witness(&Witness::Signature(c)));
+
+        budget.apply_witness(&Witness::Signature(c));
+        assert_eq!(budget, Budget::new_payment(42, to));
+    }
+
+    #[test]
+    fn test_verify_compound() {
+        let dt = Utc.ymd(2014, 11, 14).and_hms(8, 9, 10);
+        let from = PublicKey::default();
+        let to = PublicKey::default();
+        let pay = Box::new(Budget::new_payment(42, to));
+        let after = Condition::Timestamp(dt);
+        let signed = Condition::Signature(from);
+
+        assert!(Budget::When(after.clone(), pay.clone()).verify(42));
+        assert!(!Budget::When(after.clone(), pay.clone()).verify(41));
+        assert!(Budget::And(after.clone(), signed.clone(), pay.clone()).verify(42));
+        let three_way = Budget::Or(vec![
+            Budget::After(after.clone(), Payment { tokens: 42, to }),
+            Budget::After(signed.clone(), Payment { tokens: 42, to: from }),
+            Budget::When(signed.clone(), pay.clone()),
+        ]);
+        assert!(three_way.verify(42));
+
+        // Every arm must spend the same tokens.
+        let unbalanced = Budget::Or(vec![
+            Budget::After(after.clone(), Payment { tokens: 42, to }),
+            Budget::After(signed.clone(), Payment { tokens: 41, to }),
+        ]);
+        assert!(!unbalanced.verify(42));
+        assert!(!Budget::Or(vec![]).verify(42));
+
+        // Nested conditions are verified too.
+        let bad_multisig = Budget::new_multisig_payment(3, vec![from], 42, to);
+        assert!(!Budget::When(after, Box::new(bad_multisig)).verify(42));
+    }
+
+    #[test]
+    fn test_and_reduces_in_either_order() {
+        let dt = Utc.ymd(2014, 11, 14).and_hms(8, 9, 10);
+        let from = PublicKey::default();
+        let to = PublicKey::clone_from_slice(&[1; 32]);
+        let pay = Box::new(Budget::new_payment(42, to));
+        let budget = Budget::And(Condition::Timestamp(dt), Condition::Signature(from), pay);
+
+        let mut time_first = budget.clone();
+        time_first.apply_witness(&Witness::Timestamp(dt));
+        assert_eq!(time_first.final_payment(), None);
+        time_first.apply_witness(&Witness::Signature(from));
+        assert_eq!(time_first, Budget::new_payment(42, to));
+
+        let mut signature_first = budget.clone();
+        signature_first.apply_witness(&Witness::Signature(from));
+        assert_eq!(signature_first.final_payment(), None);
+        signature_first.apply_witness(&Witness::Timestamp(dt));
+        assert_eq!(signature_first, Budget::new_payment(42, to));
+    }
+
+    #[test]
+    fn test_when_reduces_one_step_per_witness() {
+        let dt0 = Utc.ymd(2014, 11, 14).and_hms(8, 9, 10);
+        let dt1 = Utc.ymd(2014, 11, 14).and_hms(10, 9, 8);
+        let to = PublicKey::default();
+        let inner = Budget::new_future_payment(dt1, 42, to);
+        let mut budget = Budget::When(Condition::Timestamp(dt0), Box::new(inner.clone()));
+
+        // A witness that satisfies both levels only peels off the outer one.
+        budget.apply_witness(&Witness::Timestamp(dt1));
+        assert_eq!(budget, inner);
+        budget.apply_witness(&Witness::Timestamp(dt1));
+        assert_eq!(budget, Budget::new_payment(42, to));
+    }
+
+    #[test]
+    fn test_one_witness_satisfies_several_branches() {
+        let dt = Utc.ymd(2014, 11, 14).and_hms(8, 9, 10);
+        let from = PublicKey::default();
+        let to = PublicKey::clone_from_slice(&[1; 32]);
+
+        // Both conditions of an And can be met at once.
+        let pay = Box::new(Budget::new_payment(42, to));
+        let mut budget = Budget::And(Condition::Timestamp(dt), Condition::Timestamp(dt), pay);
+        budget.apply_witness(&Witness::Timestamp(dt));
+        assert_eq!(budget, Budget::new_payment(42, to));
+
+        // When several arms of an Or complete, the first one wins.
+        let mut budget = Budget::Or(vec![
+            Budget::new_authorized_payment(from, 42, from),
+            Budget::new_future_payment(dt, 42, to),
+            Budget::new_future_payment(dt, 42, from),
+        ]);
+        budget.apply_witness(&Witness::Timestamp(dt));
+        assert_eq!(budget, Budget::new_payment(42, to));
+    }
+
+    #[test]
+    fn test_three_way_race() {
+        let dt = Utc.ymd(2014, 11, 14).and_hms(8, 9, 10);
+        let a = PublicKey::clone_from_slice(&[1; 32]);
+        let b = PublicKey::clone_from_slice(&[2; 32]);
+        let to = PublicKey::clone_from_slice(&[3; 32]);
+        let mut budget = Budget::Or(vec![
+            Budget::new_future_payment(dt, 42, to),
+            Budget::new_authorized_payment(a, 42, a),
+            Budget::And(
+                Condition::Signature(a),
+                Condition::Signature(b),
+                Box::new(Budget::new_payment(42, b)),
+            ),
+        ]);
+
+        // The signature of `a` completes the second arm before the third can.
+        budget.apply_witness(&Witness::Signature(b));
+        assert_eq!(budget.final_payment(), None);
+        budget.apply_witness(&Witness::Signature(a));
+        assert_eq!(budget, Budget::new_payment(42, a));
     }
 
     #[test]
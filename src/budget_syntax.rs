@@ -0,0 +1,585 @@
+//! The `budget_syntax` module defines a human-readable text format for
+//! `Budget` contracts, so that they don't have to be written by hand in Rust
+//! or as JSON. For example:
+//!
+//! ```text
+//! race(after(2018-07-01T00:00Z) pay 10 to <key>, signed(<key>) pay 10 to <key>)
+//! ```
+//!
+//! Keys and hashes are written in base58. Parsing the output of `Display`
+//! yields the same `Budget`, and printing a parsed `Budget` yields its
+//! canonical text.
+//!
+//! ```text
+//! budget    := payment
+//!            | condition payment
+//!            | "split" "(" [payment {"," payment}] ")"
+//!            | "race" "(" condition payment "," condition payment ")"
+//!            | "when" "(" condition "," budget ")"
+//!            | "and" "(" condition "," condition "," budget ")"
+//!            | "or" "(" [budget {"," budget}] ")"
+//! payment   := "pay" tokens "to" key
+//! condition := "after" "(" datetime ")"
+//!            | "signed" "(" key ")"
+//!            | "hash" "(" hash ")"
+//!            | "height" "(" ticks ")"
+//!            | "multisig" "(" m "," keys ["," keys] ")"
+//! keys      := "[" [key {"," key}] "]"
+//! ```
+
+use base58::Base58Error;
+use budget::{Budget, Condition};
+use chrono::prelude::*;
+use hash::Hash;
+use payment_plan::Payment;
+use signature::PublicKey;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ParseError {
+    /// Byte offset into the input where the error was found.
+    pub pos: usize,
+    pub msg: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at column {}", self.msg, self.pos + 1)
+    }
+}
+
+pub type Result<T> = ::std::result::Result<T, ParseError>;
+
+/// Parse a Budget from its text format.
+pub fn parse(text: &str) -> Result<Budget> {
+    let mut parser = Parser { text, pos: 0 };
+    let budget = parser.budget()?;
+    parser.skip_whitespace();
+    if parser.pos < text.len() {
+        return Err(parser.error("expected end of input"));
+    }
+    Ok(budget)
+}
+
+impl FromStr for Budget {
+    type Err = ParseError;
+
+    fn from_str(text: &str) -> Result<Self> {
+        parse(text)
+    }
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.text[self.pos..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest
+            .find(|c: char| !c.is_whitespace())
+            .unwrap_or_else(|| rest.len());
+    }
+
+    /// Describe what's at the current position, for error messages.
+    fn found(&self) -> String {
+        match self.rest().chars().next() {
+            Some(c) => format!("`{}`", c),
+            None => "end of input".to_string(),
+        }
+    }
+
+    fn error(&self, expected: &str) -> ParseError {
+        ParseError {
+            pos: self.pos,
+            msg: format!("{}, found {}", expected, self.found()),
+        }
+    }
+
+    /// Consume the longest prefix whose characters satisfy `pred`.
+    fn take_while<F: Fn(char) -> bool>(&mut self, pred: F) -> &'a str {
+        let rest = self.rest();
+        let len = rest.find(|c| !pred(c)).unwrap_or_else(|| rest.len());
+        self.pos += len;
+        &rest[..len]
+    }
+
+    fn expect(&mut self, token: &str) -> Result<()> {
+        self.skip_whitespace();
+        if self.rest().starts_with(token) {
+            self.pos += token.len();
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected `{}`", token)))
+        }
+    }
+
+    /// Consume `token` if it's next, and report whether it was.
+    fn accept(&mut self, token: &str) -> bool {
+        self.skip_whitespace();
+        if self.rest().starts_with(token) {
+            self.pos += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn keyword(&mut self, keyword: &str) -> Result<()> {
+        self.skip_whitespace();
+        let start = self.pos;
+        if self.take_while(|c| c.is_ascii_alphabetic()) == keyword {
+            Ok(())
+        } else {
+            self.pos = start;
+            Err(self.error(&format!("expected `{}`", keyword)))
+        }
+    }
+
+    /// Parse a comma-separated list of items, up to and including `close`.
+    fn list<T, F>(&mut self, close: &str, mut item: F) -> Result<Vec<T>>
+    where
+        F: FnMut(&mut Self) -> Result<T>,
+    {
+        let mut items = vec![];
+        if self.accept(close) {
+            return Ok(items);
+        }
+        loop {
+            items.push(item(self)?);
+            if self.accept(close) {
+                return Ok(items);
+            }
+            self.expect(",")
+                .map_err(|_| self.error(&format!("expected `,` or `{}`", close)))?;
+        }
+    }
+
+    fn budget(&mut self) -> Result<Budget> {
+        self.skip_whitespace();
+        let start = self.pos;
+        match self.take_while(|c| c.is_ascii_alphabetic()) {
+            "pay" => {
+                self.pos = start;
+                Ok(Budget::Pay(self.payment()?))
+            }
+            "after" | "signed" | "hash" | "height" | "multisig" => {
+                self.pos = start;
+                let cond = self.condition()?;
+                Ok(Budget::After(cond, self.payment()?))
+            }
+            "split" => {
+                self.expect("(")?;
+                Ok(Budget::Split(self.list(")", Self::payment)?))
+            }
+            "race" => {
+                self.expect("(")?;
+                let cond0 = self.condition()?;
+                let payment0 = self.payment()?;
+                self.expect(",")?;
+                let cond1 = self.condition()?;
+                let payment1 = self.payment()?;
+                self.expect(")")?;
+                Ok(Budget::Race((cond0, payment0), (cond1, payment1)))
+            }
+            "when" => {
+                self.expect("(")?;
+                let cond = self.condition()?;
+                self.expect(",")?;
+                let budget = self.budget()?;
+                self.expect(")")?;
+                Ok(Budget::When(cond, Box::new(budget)))
+            }
+            "and" => {
+                self.expect("(")?;
+                let cond0 = self.condition()?;
+                self.expect(",")?;
+                let cond1 = self.condition()?;
+                self.expect(",")?;
+                let budget = self.budget()?;
+                self.expect(")")?;
+                Ok(Budget::And(cond0, cond1, Box::new(budget)))
+            }
+            "or" => {
+                self.expect("(")?;
+                Ok(Budget::Or(self.list(")", Self::budget)?))
+            }
+            _ => {
+                self.pos = start;
+                Err(self.error("expected a budget"))
+            }
+        }
+    }
+
+    fn condition(&mut self) -> Result<Condition> {
+        self.skip_whitespace();
+        let start = self.pos;
+        let cond = match self.take_while(|c| c.is_ascii_alphabetic()) {
+            "after" => {
+                self.expect("(")?;
+                Condition::Timestamp(self.datetime()?)
+            }
+            "signed" => {
+                self.expect("(")?;
+                Condition::Signature(self.key()?)
+            }
+            "hash" => {
+                self.expect("(")?;
+                Condition::Hash(self.hash()?)
+            }
+            "height" => {
+                self.expect("(")?;
+                Condition::TickHeight(self.unsigned()?)
+            }
+            "multisig" => {
+                self.expect("(")?;
+                let m = self.unsigned()?;
+                self.expect(",")?;
+                self.expect("[")?;
+                let signers = self.list("]", Self::key)?;
+                let approvals = if self.accept(",") {
+                    self.expect("[")?;
+                    self.list("]", Self::key)?
+                } else {
+                    vec![]
+                };
+                Condition::MultiSignature {
+                    m,
+                    signers,
+                    approvals,
+                }
+            }
+            _ => {
+                self.pos = start;
+                return Err(self.error("expected a condition"));
+            }
+        };
+        self.expect(")")?;
+        Ok(cond)
+    }
+
+    fn payment(&mut self) -> Result<Payment> {
+        self.keyword("pay")?;
+        let tokens = self.number()?;
+        self.keyword("to")?;
+        let to = self.key()?;
+        Ok(Payment { tokens, to })
+    }
+
+    fn number(&mut self) -> Result<i64> {
+        self.skip_whitespace();
+        let start = self.pos;
+        if self.rest().starts_with('-') {
+            self.pos += 1;
+        }
+        let digits = self.take_while(|c| c.is_ascii_digit());
+        if digits.is_empty() {
+            return Err(self.error("expected a number"));
+        }
+        let text = &self.text[start..self.pos];
+        text.parse().map_err(|_| ParseError {
+            pos: start,
+            msg: format!("number `{}` is out of range", text),
+        })
+    }
+
+    fn unsigned(&mut self) -> Result<u64> {
+        self.skip_whitespace();
+        let start = self.pos;
+        let digits = self.take_while(|c| c.is_ascii_digit());
+        if digits.is_empty() {
+            return Err(self.error("expected a non-negative number"));
+        }
+        digits.parse().map_err(|_| ParseError {
+            pos: start,
+            msg: format!("number `{}` is out of range", digits),
+        })
+    }
+
+    /// Parse a key or hash written in base58.
+    fn base58<T: FromStr<Err = Base58Error>>(&mut self, what: &str) -> Result<T> {
+        self.skip_whitespace();
+        let start = self.pos;
+        let text = self.take_while(|c| c.is_ascii_alphanumeric());
+        text.parse().map_err(|_| {
+            self.pos = start;
+            self.error(&format!("expected a {} in base58", what))
+        })
+    }
+
+    fn key(&mut self) -> Result<PublicKey> {
+        self.base58("key")
+    }
+
+    fn hash(&mut self) -> Result<Hash> {
+        self.base58("hash")
+    }
+
+    fn datetime(&mut self) -> Result<DateTime<Utc>> {
+        self.skip_whitespace();
+        let start = self.pos;
+        let text = self.take_while(|c| c != ')' && !c.is_whitespace());
+        parse_datetime(text).ok_or_else(|| ParseError {
+            pos: start,
+            msg: format!("invalid date `{}`, expected e.g. `2018-07-01T00:00Z`", text),
+        })
+    }
+}
+
+const DATETIME_FORMATS: [&str; 3] = ["%Y-%m-%dT%H:%MZ", "%Y-%m-%dT%H:%M:%SZ", "%Y-%m-%dT%H:%M:%S%.fZ"];
+
+fn parse_datetime(text: &str) -> Option<DateTime<Utc>> {
+    if let Ok(dt) = DateTime::parse_from_rfc3339(text) {
+        return Some(dt.with_timezone(&Utc));
+    }
+    DATETIME_FORMATS
+        .iter()
+        .filter_map(|format| NaiveDateTime::parse_from_str(text, format).ok())
+        .map(|naive| Utc.from_utc_datetime(&naive))
+        .next()
+}
+
+/// Print a DateTime with as little precision as it needs.
+fn format_datetime(dt: &DateTime<Utc>) -> String {
+    let format = if dt.nanosecond() != 0 {
+        DATETIME_FORMATS[2]
+    } else if dt.second() != 0 {
+        DATETIME_FORMATS[1]
+    } else {
+        DATETIME_FORMATS[0]
+    };
+    dt.format(format).to_string()
+}
+
+fn fmt_keys(f: &mut fmt::Formatter, keys: &[PublicKey]) -> fmt::Result {
+    let keys: Vec<_> = keys.iter().map(|key| key.to_string()).collect();
+    write!(f, "[{}]", keys.join(", "))
+}
+
+impl fmt::Display for Payment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "pay {} to {}", self.tokens, self.to)
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Condition::Timestamp(dt) => write!(f, "after({})", format_datetime(dt)),
+            Condition::Signature(key) => write!(f, "signed({})", key),
+            Condition::Hash(hash) => write!(f, "hash({})", hash),
+            Condition::TickHeight(height) => write!(f, "height({})", height),
+            Condition::MultiSignature {
+                m,
+                signers,
+                approvals,
+            } => {
+                write!(f, "multisig({}, ", m)?;
+                fmt_keys(f, signers)?;
+                if !approvals.is_empty() {
+                    write!(f, ", ")?;
+                    fmt_keys(f, approvals)?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+fn fmt_list<T: fmt::Display>(f: &mut fmt::Formatter, items: &[T]) -> fmt::Result {
+    let items: Vec<_> = items.iter().map(|item| item.to_string()).collect();
+    write!(f, "({})", items.join(", "))
+}
+
+impl fmt::Display for Budget {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Budget::Pay(payment) => write!(f, "{}", payment),
+            Budget::After(cond, payment) => write!(f, "{} {}", cond, payment),
+            Budget::Split(payments) => {
+                write!(f, "split")?;
+                fmt_list(f, payments)
+            }
+            Budget::Race((cond0, payment0), (cond1, payment1)) => write!(
+                f,
+                "race({} {}, {} {})",
+                cond0, payment0, cond1, payment1
+            ),
+            Budget::When(cond, budget) => write!(f, "when({}, {})", cond, budget),
+            Budget::And(cond0, cond1, budget) => {
+                write!(f, "and({}, {}, {})", cond0, cond1, budget)
+            }
+            Budget::Or(budgets) => {
+                write!(f, "or")?;
+                fmt_list(f, budgets)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hash::hash;
+
+    fn key(n: u8) -> PublicKey {
+        PublicKey::clone_from_slice(&[n; 32])
+    }
+
+    fn assert_round_trip(budget: Budget) {
+        let text = budget.to_string();
+        assert_eq!(parse(&text), Ok(budget), "{}", text);
+        assert_eq!(parse(&text).unwrap().to_string(), text);
+    }
+
+    #[test]
+    fn test_parse_race() {
+        let a = key(1).to_string();
+        let b = key(2).to_string();
+        let text = format!(
+            "race(after(2018-07-01T00:00Z) pay 10 to {}, signed({}) pay 10 to {})",
+            a, b, b
+        );
+        let dt = Utc.ymd(2018, 7, 1).and_hms(0, 0, 0);
+        let budget = Budget::new_cancelable_future_payment(dt, key(2), 10, key(1));
+        assert_eq!(parse(&text), Ok(budget.clone()));
+        assert_eq!(budget.to_string(), text);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let dt = Utc.ymd(2018, 7, 1).and_hms(0, 0, 0);
+        let pay = Box::new(Budget::new_payment(42, key(3)));
+        assert_round_trip(Budget::new_payment(-1, key(1)));
+        assert_round_trip(Budget::new_authorized_payment(key(1), 42, key(2)));
+        assert_round_trip(Budget::new_future_payment(dt, 42, key(2)));
+        assert_round_trip(Budget::new_tick_height_payment(1_000, 42, key(2)));
+        assert_round_trip(Budget::After(
+            Condition::TickHeight(u64::max_value()),
+            Payment {
+                tokens: 42,
+                to: key(2),
+            },
+        ));
+        assert_round_trip(Budget::new_hash_time_locked_payment(
+            hash(b"secret"),
+            dt,
+            key(1),
+            42,
+            key(2),
+        ));
+        assert_round_trip(Budget::new_multisig_payment(
+            2,
+            vec![key(1), key(2), key(3)],
+            42,
+            key(4),
+        ));
+        assert_round_trip(Budget::After(
+            Condition::MultiSignature {
+                m: 2,
+                signers: vec![key(1), key(2)],
+                approvals: vec![key(2)],
+            },
+            Payment {
+                tokens: 42,
+                to: key(3),
+            },
+        ));
+        assert_round_trip(Budget::After(
+            Condition::MultiSignature {
+                m: u64::max_value(),
+                signers: vec![key(1)],
+                approvals: vec![],
+            },
+            Payment {
+                tokens: 42,
+                to: key(3),
+            },
+        ));
+        assert_round_trip(Budget::new_split_payment(vec![
+            Payment {
+                tokens: 1,
+                to: key(1),
+            },
+            Payment {
+                tokens: 2,
+                to: key(2),
+            },
+        ]));
+        assert_round_trip(Budget::When(Condition::Timestamp(dt), pay.clone()));
+        assert_round_trip(Budget::And(
+            Condition::Timestamp(dt),
+            Condition::Signature(key(1)),
+            pay.clone(),
+        ));
+        assert_round_trip(Budget::Or(vec![
+            Budget::When(Condition::Signature(key(1)), pay.clone()),
+            Budget::new_future_payment(dt, 42, key(2)),
+            Budget::Or(vec![]),
+        ]));
+    }
+
+    #[test]
+    fn test_round_trip_datetime_precision() {
+        let dt = Utc.ymd(2018, 7, 1).and_hms(12, 30, 15);
+        assert_round_trip(Budget::new_future_payment(dt, 1, key(1)));
+        let dt = Utc.ymd(2018, 7, 1).and_hms_nano(12, 30, 15, 1);
+        assert_round_trip(Budget::new_future_payment(dt, 1, key(1)));
+        let dt = Utc.ymd(2018, 7, 1).and_hms_milli(12, 30, 15, 250);
+        assert_round_trip(Budget::new_future_payment(dt, 1, key(1)));
+    }
+
+    #[test]
+    fn test_parse_whitespace() {
+        let text = format!("  when ( signed( {} ) ,pay 1 to {} )  ", key(1), key(2));
+        let budget = Budget::When(
+            Condition::Signature(key(1)),
+            Box::new(Budget::new_payment(1, key(2))),
+        );
+        assert_eq!(parse(&text), Ok(budget));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let a = key(1).to_string();
+        let err = parse("give 10 to me").unwrap_err();
+        assert_eq!(err.pos, 0);
+        assert_eq!(err.to_string(), "expected a budget, found `g` at column 1");
+
+        let err = parse(&format!("pay 10 from {}", a)).unwrap_err();
+        assert_eq!(err.pos, 7);
+        assert_eq!(err.msg, "expected `to`, found `f`");
+
+        let err = parse("pay 10 to 1234").unwrap_err();
+        assert_eq!(err.pos, 10);
+        assert_eq!(err.msg, "expected a key in base58, found `1`");
+
+        let err = parse(&format!("pay 10 to {}0", a)).unwrap_err();
+        assert_eq!(err.pos, 10);
+        let found = format!("found `{}`", &a[..1]);
+        assert_eq!(err.msg, format!("expected a key in base58, {}", found));
+
+        let err = parse(&format!("after(2018-13-01T00:00Z) pay 1 to {}", a)).unwrap_err();
+        assert_eq!(err.pos, 6);
+
+        let err = parse(&format!("or(pay 1 to {} pay 1 to {})", a, a)).unwrap_err();
+        assert_eq!(err.pos, 13 + a.len());
+        assert_eq!(err.msg, "expected `,` or `)`, found `p`");
+
+        let err = parse(&format!("pay 1 to {})", a)).unwrap_err();
+        assert_eq!(err.pos, 9 + a.len());
+        assert_eq!(err.msg, "expected end of input, found `)`");
+
+        let err = parse("pay 99999999999999999999 to").unwrap_err();
+        assert_eq!(err.pos, 4);
+
+        let err = parse("race(").unwrap_err();
+        assert_eq!(err.msg, "expected a condition, found end of input");
+    }
+}
@@ -119,12 +119,17 @@ impl ReplicatedData {
     }
 
     pub fn new_leader(bind_addr: &SocketAddr) -> Self {
+        Self::new_leader_with_pubkey(KeyPair::new().pubkey(), bind_addr)
+    }
+
+    /// Create a leader whose identity is an existing key, such as one from a
+    /// keystore, so that the fees it collects can be spent.
+    pub fn new_leader_with_pubkey(pubkey: PublicKey, bind_addr: &SocketAddr) -> Self {
         let transactions_addr = bind_addr.clone();
         let gossip_addr = Self::next_port(&bind_addr, 1);
         let replicate_addr = Self::next_port(&bind_addr, 2);
         let requests_addr = Self::next_port(&bind_addr, 3);
         let repair_addr = Self::next_port(&bind_addr, 4);
-        let pubkey = KeyPair::new().pubkey();
         ReplicatedData::new(
             pubkey,
             gossip_addr,
@@ -204,15 +209,47 @@ impl Crdt {
         self.insert(&me);
     }
 
+    /// Publish the bank's state hash, computed after `count` transactions.
+    pub fn set_last_verified(&mut self, hash: Hash, count: u64) {
+        let mut me = self.my_data().clone();
+        if me.last_verified_hash == hash && me.last_verified_count == count {
+            return;
+        }
+        me.last_verified_hash = hash;
+        me.last_verified_count = count;
+        me.version += 1;
+        self.insert(&me);
+    }
+
+    /// Log an error if `v` reports a different state hash than ours for the
+    /// same transaction count.
+    fn check_last_verified(&self, v: &ReplicatedData) {
+        let me = self.my_data();
+        if v.id != me.id && v.last_verified_count == me.last_verified_count
+            && v.last_verified_count != 0
+            && v.last_verified_hash != me.last_verified_hash
+        {
+            error!(
+                "state divergence at transaction count {}: me: {} {} v.id: {} {}",
+                v.last_verified_count,
+                self.me,
+                me.last_verified_hash,
+                v.id,
+                v.last_verified_hash
+            );
+        }
+    }
+
     pub fn insert(&mut self, v: &ReplicatedData) {
         // TODO check that last_verified types are always increasing
+        self.check_last_verified(v);
         if self.table.get(&v.id).is_none() || (v.version > self.table[&v.id].version) {
             //somehow we signed a message for our own identity with a higher version that
             // we have stored ourselves
             trace!(
-                "me: {:?} v.id: {:?} version: {}",
-                &self.me[..4],
-                &v.id[..4],
+                "me: {} v.id: {} version: {}",
+                self.me,
+                v.id,
                 v.version
             );
             self.update_index += 1;
@@ -220,9 +257,9 @@ impl Crdt {
             let _ = self.local.insert(v.id, self.update_index);
         } else {
             trace!(
-                "INSERT FAILED me: {:?} data: {:?} new.version: {} me.version: {}",
-                &self.me[..4],
-                &v.id[..4],
+                "INSERT FAILED me: {} data: {} new.version: {} me.version: {}",
+                self.me,
+                v.id,
                 v.version,
                 self.table[&v.id].version
             );
@@ -446,8 +483,8 @@ impl Crdt {
         let options: Vec<_> = self.table.values().filter(|v| v.id != self.me).collect();
         if options.len() < 1 {
             trace!(
-                "crdt too small for gossip {:?} {}",
-                &self.me[..4],
+                "crdt too small for gossip {} {}",
+                self.me,
                 self.table.len()
             );
             return Err(Error::CrdtTooSmall);
@@ -457,9 +494,9 @@ impl Crdt {
         let remote_update_index = *self.remote.get(&v.id).unwrap_or(&0);
         let req = Protocol::RequestUpdates(remote_update_index, self.table[&self.me].clone());
         trace!(
-            "created gossip request from {:?} to {:?} {}",
-            &self.me[..4],
-            &v.id[..4],
+            "created gossip request from {} to {} {}",
+            self.me,
+            v.id,
             v.gossip_addr
         );
         Ok((v.gossip_addr, req))
@@ -581,18 +618,18 @@ impl Crdt {
                 if len < 1 {
                     let me = obj.read().unwrap();
                     trace!(
-                        "no updates me {:?} ix {} since {}",
-                        &me.me[..4],
+                        "no updates me {} ix {} since {}",
+                        me.me,
                         me.update_index,
                         v
                     );
                     None
                 } else if let Ok(r) = to_blob(rsp, addr, &blob_recycler) {
                     trace!(
-                        "sending updates me {:?} len {} to {:?} {}",
-                        &obj.read().unwrap().me[..4],
+                        "sending updates me {} len {} to {} {}",
+                        obj.read().unwrap().me,
                         len,
-                        &reqdata.id[..4],
+                        reqdata.id,
                         addr,
                     );
                     Some(r)
@@ -602,7 +639,7 @@ impl Crdt {
                 }
             }
             Ok(Protocol::ReceiveUpdates(from, ups, data)) => {
-                trace!("ReceivedUpdates {:?} {} {}", &from[0..4], ups, data.len());
+                trace!("ReceivedUpdates {} {} {}", from, ups, data.len());
                 obj.write()
                     .expect("'obj' write lock in ReceiveUpdates")
                     .apply_updates(from, ups, &data);
@@ -739,6 +776,7 @@ impl TestNode {
 #[cfg(test)]
 mod tests {
     use crdt::{parse_port_or_addr, Crdt, ReplicatedData};
+    use hash::{self, Hash};
     use packet::BlobRecycler;
     use result::Error;
     use signature::{KeyPair, KeyPairUtil};
@@ -777,6 +815,24 @@ mod tests {
         crdt.insert(&d);
         assert_eq!(crdt.table[&d.id].version, 2);
     }
+    #[test]
+    fn set_last_verified_test() {
+        let d = ReplicatedData::new_leader(&"127.0.0.1:1234".parse().unwrap());
+        let mut crdt = Crdt::new(d.clone());
+        let hash = Hash::default();
+        crdt.set_last_verified(hash, 1);
+        assert_eq!(crdt.my_data().version, 1);
+        assert_eq!(crdt.my_data().last_verified_count, 1);
+
+        // Publishing the same state again doesn't create a new version.
+        crdt.set_last_verified(hash, 1);
+        assert_eq!(crdt.my_data().version, 1);
+
+        let hash = hash::hash(&[1]);
+        crdt.set_last_verified(hash, 2);
+        assert_eq!(crdt.my_data().version, 2);
+        assert_eq!(crdt.my_data().last_verified_hash, hash);
+    }
     fn sorted(ls: &Vec<ReplicatedData>) -> Vec<ReplicatedData> {
         let mut copy: Vec<_> = ls.iter().cloned().collect();
         copy.sort_by(|x, y| x.id.cmp(&y.id));
@@ -790,6 +846,11 @@ mod tests {
         assert_eq!(d1.requests_addr, "127.0.0.1:1237".parse().unwrap());
         assert_eq!(d1.transactions_addr, "127.0.0.1:1234".parse().unwrap());
         assert_eq!(d1.repair_addr, "127.0.0.1:1238".parse().unwrap());
+
+        let pubkey = KeyPair::new().pubkey();
+        let d2 = ReplicatedData::new_leader_with_pubkey(pubkey, &"127.0.0.1:1234".parse().unwrap());
+        assert_eq!(d2.id, pubkey);
+        assert_eq!(d2.repair_addr, d1.repair_addr);
     }
     #[test]
     fn update_test() {
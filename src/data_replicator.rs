@@ -23,8 +23,8 @@ impl DataReplicator {
         let blob_recycler = packet::BlobRecycler::default();
         let (request_sender, request_receiver) = channel();
         trace!(
-            "DataReplicator: id: {:?}, listening on: {:?}",
-            &crdt.read().unwrap().me[..4],
+            "DataReplicator: id: {}, listening on: {:?}",
+            crdt.read().unwrap().me,
             gossip_listen_socket.local_addr().unwrap()
         );
         let t_receiver = streamer::blob_receiver(
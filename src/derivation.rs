@@ -0,0 +1,276 @@
+//! The `derivation` module derives keypairs from a single master seed, so a
+//! wallet can back up one seed and recreate any of its accounts by index. It
+//! follows SLIP-0010 for ED25519, which only has hardened child keys, so the
+//! same seed and path give the same key in any implementation of it.
+
+use byteorder::{BigEndian, WriteBytesExt};
+use ring::{digest, hmac};
+use signature::{KeyPair, KeyPairUtil, PublicKey};
+use std::fmt;
+use untrusted::Input;
+
+/// Child indexes at or above this are hardened. ED25519 only supports
+/// hardened derivation, so `ExtendedKey::child` adds it to every index.
+pub const HARDENED: u32 = 1 << 31;
+
+const MASTER_KEY: &[u8] = b"ed25519 seed";
+
+/// The PKCS#8 v2 encoding of an ED25519 keypair is this prefix, the 32-byte
+/// seed, the infix, and the 32-byte public key.
+const PKCS8_PREFIX: [u8; 16] = [
+    0x30, 0x53, 0x02, 0x01, 0x01, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x04, 0x22, 0x04, 0x20,
+];
+const PKCS8_INFIX: [u8; 5] = [0xa1, 0x23, 0x03, 0x21, 0x00];
+
+/// A private key together with the chain code that derives its children.
+#[derive(Clone, PartialEq, Eq)]
+pub struct ExtendedKey {
+    key: [u8; 32],
+    chain_code: [u8; 32],
+}
+
+// Keep private keys out of logs.
+impl fmt::Debug for ExtendedKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ExtendedKey({})", self.pubkey())
+    }
+}
+
+impl ExtendedKey {
+    fn from_hmac(key: &[u8], data: &[u8]) -> Self {
+        let signature = hmac::sign(&hmac::SigningKey::new(&digest::SHA512, key), data);
+        let bytes = signature.as_ref();
+        let mut extended_key = ExtendedKey {
+            key: [0; 32],
+            chain_code: [0; 32],
+        };
+        extended_key.key.copy_from_slice(&bytes[..32]);
+        extended_key.chain_code.copy_from_slice(&bytes[32..]);
+        extended_key
+    }
+
+    /// Return the root key for a seed, which should be 16 to 64 random bytes.
+    pub fn new_master(seed: &[u8]) -> Self {
+        Self::from_hmac(MASTER_KEY, seed)
+    }
+
+    /// Return the hardened child key at `index`, which must be less than
+    /// `HARDENED`.
+    pub fn child(&self, index: u32) -> Self {
+        assert!(index < HARDENED, "child index {} is too large", index);
+        let mut data = Vec::with_capacity(1 + 32 + 4);
+        data.push(0);
+        data.extend_from_slice(&self.key);
+        data.write_u32::<BigEndian>(index | HARDENED)
+            .expect("write_u32 in derivation pub fn child");
+        Self::from_hmac(&self.chain_code, &data)
+    }
+
+    /// Return the key reached by taking each child index in turn.
+    pub fn derive(&self, path: &[u32]) -> Self {
+        path.iter().fold(self.clone(), |key, &index| key.child(index))
+    }
+
+    pub fn keypair(&self) -> KeyPair {
+        KeyPair::from_pkcs8(Input::from(&self.pkcs8()))
+            .expect("from_pkcs8 in derivation pub fn keypair")
+    }
+
+    pub fn pubkey(&self) -> PublicKey {
+        let keypair = KeyPair::from_seed_unchecked(Input::from(&self.key))
+            .expect("from_seed_unchecked in derivation pub fn pubkey");
+        keypair.pubkey()
+    }
+
+    /// Return the keypair as PKCS#8 bytes, the form kept by `Mint` and
+    /// keystores.
+    pub fn pkcs8(&self) -> Vec<u8> {
+        let mut pkcs8 = PKCS8_PREFIX.to_vec();
+        pkcs8.extend_from_slice(&self.key);
+        pkcs8.extend_from_slice(&PKCS8_INFIX);
+        pkcs8.extend_from_slice(&self.pubkey());
+        pkcs8
+    }
+}
+
+/// Return the 32-byte private seed in the PKCS#8 encoding of a keypair, or
+/// None if it isn't laid out like the ones `pkcs8` and ring write.
+pub fn pkcs8_seed(pkcs8: &[u8]) -> Option<&[u8]> {
+    let start = PKCS8_PREFIX.len();
+    if pkcs8.len() < start + 32 || pkcs8[..start] != PKCS8_PREFIX {
+        return None;
+    }
+    Some(&pkcs8[start..start + 32])
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum PathError {
+    /// The path doesn't start with `m`.
+    MissingRoot,
+    /// The component isn't a number below `HARDENED` marked hardened by a
+    /// trailing `'` or `H`.
+    InvalidIndex(String),
+}
+
+/// Parse a path such as `m/44'/0'/1'` into child indexes without the
+/// hardened bit.
+pub fn parse_path(text: &str) -> Result<Vec<u32>, PathError> {
+    let mut components = text.trim().split('/');
+    if components.next() != Some("m") {
+        return Err(PathError::MissingRoot);
+    }
+    components
+        .map(|component| {
+            let invalid = || PathError::InvalidIndex(component.to_string());
+            if !component.ends_with('\'') && !component.ends_with('H') {
+                return Err(invalid());
+            }
+            let index: u32 = component[..component.len() - 1]
+                .parse()
+                .map_err(|_| invalid())?;
+            if index >= HARDENED {
+                return Err(invalid());
+            }
+            Ok(index)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use signature::SignatureUtil;
+
+    fn from_hex(text: &str) -> Vec<u8> {
+        (0..text.len() / 2)
+            .map(|i| u8::from_str_radix(&text[2 * i..2 * i + 2], 16).unwrap())
+            .collect()
+    }
+
+    fn assert_vector(key: &ExtendedKey, chain_code: &str, private: &str, public: &str) {
+        assert_eq!(key.chain_code.to_vec(), from_hex(chain_code));
+        assert_eq!(key.key.to_vec(), from_hex(private));
+        // SLIP-0010 writes ED25519 public keys with a leading zero byte.
+        assert_eq!(key.pubkey().to_vec(), from_hex(&public[2..]));
+        assert_eq!(key.keypair().pubkey(), key.pubkey());
+    }
+
+    /// Test vector 1 for ed25519 from SLIP-0010.
+    #[test]
+    fn test_slip10_vector1() {
+        let master = ExtendedKey::new_master(&from_hex("000102030405060708090a0b0c0d0e0f"));
+        assert_vector(
+            &master,
+            "90046a93de5380a72b5e45010748567d5ea02bbf6522f979e05c0d8d8ca9fffb",
+            "2b4be7f19ee27bbf30c667b642d5f4aa69fd169872f8fc3059c08ebae2eb19e7",
+            "00a4b2856bfec510abab89753fac1ac0e1112364e7d250545963f135f2a33188ed",
+        );
+        assert_vector(
+            &master.derive(&[0]),
+            "8b59aa11380b624e81507a27fedda59fea6d0b779a778918a2fd3590e16e9c69",
+            "68e0fe46dfb67e368c75379acec591dad19df3cde26e63b93a8e704f1dade7a3",
+            "008c8a13df77a28f3445213a0f432fde644acaa215fc72dcdf300d5efaa85d350c",
+        );
+        assert_vector(
+            &master.derive(&[0, 1]),
+            "a320425f77d1b5c2505a6b1b27382b37368ee640e3557c315416801243552f14",
+            "b1d0bad404bf35da785a64ca1ac54b2617211d2777696fbffaf208f746ae84f2",
+            "001932a5270f335bed617d5b935c80aedb1a35bd9fc1e31acafd5372c30f5c1187",
+        );
+        assert_vector(
+            &master.derive(&[0, 1, 2]),
+            "2e69929e00b5ab250f49c3fb1c12f252de4fed2c1db88387094a0f8c4c9ccd6c",
+            "92a5b23c0b8a99e37d07df3fb9966917f5d06e02ddbd909c7e184371463e9fc9",
+            "00ae98736566d30ed0e9d2f4486a64bc95740d89c7db33f52121f8ea8f76ff0fc1",
+        );
+        assert_vector(
+            &master.derive(&[0, 1, 2, 2]),
+            "8f6d87f93d750e0efccda017d662a1b31a266e4a6f5993b15f5c1f07f74dd5cc",
+            "30d1dc7e5fc04c31219ab25a27ae00b50f6fd66622f6e9c913253d6511d1e662",
+            "008abae2d66361c879b900d204ad2cc4984fa2aa344dd7ddc46007329ac76c429c",
+        );
+        assert_vector(
+            &master.derive(&[0, 1, 2, 2, 1_000_000_000]),
+            "68789923a0cac2cd5a29172a475fe9e0fb14cd6adb5ad98a3fa70333e7afa230",
+            "8f94d394a8e8fd6b1bc2f3f49f5c47e385281d5c17e65324b0f62483e37e8793",
+            "003c24da049451555d51a7014a37337aa4e12d41e485abccfa46b47dfb2af54b7a",
+        );
+    }
+
+    /// Test vector 2 for ed25519 from SLIP-0010.
+    #[test]
+    fn test_slip10_vector2() {
+        let master = ExtendedKey::new_master(&from_hex(
+            "fffcf9f6f3f0edeae7e4e1dedbd8d5d2cfccc9c6c3c0bdbab7b4b1aeaba8a5a2\
+             9f9c999693908d8a8784817e7b7875726f6c696663605d5a5754514e4b484542",
+        ));
+        assert_vector(
+            &master,
+            "ef70a74db9c3a5af931b5fe73ed8e1a53464133654fd55e7a66f8570b8e33c3b",
+            "171cb88b1b3c1db25add599712e36245d75bc65a1a5c9e18d76f9f2b1eab4012",
+            "008fe9693f8fa62a4305a140b9764c5ee01e455963744fe18204b4fb948249308a",
+        );
+        assert_vector(
+            &master.derive(&parse_path("m/0'").unwrap()),
+            "0b78a3226f915c082bf118f83618a618ab6dec793752624cbeb622acb562862d",
+            "1559eb2bbec5790b0c65d8693e4d0875b1747f4970ae8b650486ed7470845635",
+            "0086fab68dcb57aa196c77c5f264f215a112c22a912c10d123b0d03c3c28ef1037",
+        );
+        assert_vector(
+            &master.derive(&parse_path("m/0'/2147483647'").unwrap()),
+            "138f0b2551bcafeca6ff2aa88ba8ed0ed8de070841f0c4ef0165df8181eaad7f",
+            "ea4f5bfe8694d8bb74b7b59404632fd5968b774ed545e810de9c32a4fb4192f4",
+            "005ba3b9ac6e90e83effcd25ac4e58a1365a9e35a3d3ae5eb07b9e4d90bcf7506d",
+        );
+        assert_vector(
+            &master.derive(&parse_path("m/0'/2147483647'/1'").unwrap()),
+            "73bd9fff1cfbde33a1b846c27085f711c0fe2d66fd32e139d3ebc28e5a4a6b90",
+            "3757c7577170179c7868353ada796c839135b3d30554bbb74a4b1e4a5a58505c",
+            "002e66aa57069c86cc18249aecf5cb5a9cebbfd6fadeab056254763874a9352b45",
+        );
+        assert_vector(
+            &master.derive(&parse_path("m/0'/2147483647'/1'/2147483646'").unwrap()),
+            "0902fe8a29f9140480a00ef244bd183e8a13288e4412d8389d140aac1794825a",
+            "5837736c89570de861ebc173b1086da4f505d4adb387c6a1b1342d5e4ac9ec72",
+            "00e33c0f7d81d843c572275f287498e8d408654fdf0d1e065b84e2e6f157aab09b",
+        );
+        assert_vector(
+            &master.derive(&parse_path("m/0'/2147483647'/1'/2147483646'/2'").unwrap()),
+            "5d70af781f3a37b829f0d060924d5e960bdc02e85423494afc0b1a41bbe196d4",
+            "551d333177df541ad876a60ea71f00447931c0a9da16f227c11ea080d7391b8d",
+            "0047150c75db263559a70d5778bf36abbab30fb061ad69f69ece61a72b0cfa4fc0",
+        );
+    }
+
+    #[test]
+    fn test_derived_keypair_signs() {
+        let key = ExtendedKey::new_master(&[7; 32]).derive(&[44, 0]);
+        let keypair = key.keypair();
+        let sig = ::signature::Signature::clone_from_slice(keypair.sign(b"hello").as_ref());
+        assert!(sig.verify(&key.pubkey(), b"hello"));
+        assert_ne!(key.pubkey(), ExtendedKey::new_master(&[7; 32]).derive(&[44, 1]).pubkey());
+    }
+
+    #[test]
+    fn test_pkcs8_seed() {
+        let key = ExtendedKey::new_master(&[7; 32]);
+        assert_eq!(pkcs8_seed(&key.pkcs8()), Some(&key.key[..]));
+        assert_eq!(pkcs8_seed(&key.pkcs8()[..40]), None);
+        assert_eq!(pkcs8_seed(&[0; 85]), None);
+    }
+
+    #[test]
+    fn test_parse_path() {
+        assert_eq!(parse_path("m"), Ok(vec![]));
+        assert_eq!(parse_path("m/44'/0H/7'"), Ok(vec![44, 0, 7]));
+        assert_eq!(parse_path("44'"), Err(PathError::MissingRoot));
+        assert_eq!(
+            parse_path("m/44"),
+            Err(PathError::InvalidIndex("44".to_string()))
+        );
+        assert_eq!(
+            parse_path("m/2147483648'"),
+            Err(PathError::InvalidIndex("2147483648'".to_string()))
+        );
+    }
+}
@@ -129,8 +129,8 @@ mod tests {
 
         // First, verify entries
         let keypair = KeyPair::new();
-        let tx0 = Transaction::new(&keypair, keypair.pubkey(), 0, zero);
-        let tx1 = Transaction::new(&keypair, keypair.pubkey(), 1, zero);
+        let tx0 = Transaction::new(&keypair, keypair.pubkey(), 0, zero, zero);
+        let tx1 = Transaction::new(&keypair, keypair.pubkey(), 1, zero, zero);
         let mut e0 = Entry::new(&zero, 0, vec![tx0.clone(), tx1.clone()]);
         assert!(e0.verify(&zero));
 
@@ -146,8 +146,8 @@ mod tests {
 
         // First, verify entries
         let keypair = KeyPair::new();
-        let tx0 = Transaction::new_timestamp(&keypair, Utc::now(), zero);
-        let tx1 = Transaction::new_signature(&keypair, Default::default(), zero);
+        let tx0 = Transaction::new_timestamp(&keypair, Utc::now(), zero, zero);
+        let tx1 = Transaction::new_signature(&keypair, Default::default(), zero, zero);
         let mut e0 = Entry::new(&zero, 0, vec![tx0.clone(), tx1.clone()]);
         assert!(e0.verify(&zero));
 
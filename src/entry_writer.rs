@@ -26,7 +26,7 @@ impl<'a> EntryWriter<'a> {
 
     fn write_entry<W: Write>(&self, writer: &Mutex<W>, entry: &Entry) {
         trace!("write_entry entry");
-        self.bank.register_entry_id(&entry.id);
+        self.bank.register_entry(entry);
         writeln!(
             writer.lock().expect("'writer' lock in fn fn write_entry"),
             "{}",
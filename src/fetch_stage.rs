@@ -1,16 +1,125 @@
 //! The `fetch_stage` batches input from a UDP socket and sends it to a channel.
+//! Transactions that arrived in fragments are reassembled on the way.
 
-use packet;
-use std::net::UdpSocket;
-use std::sync::atomic::AtomicBool;
-use std::sync::mpsc::channel;
+use packet::{self, Packets, SharedPackets};
+use result::Result;
+use std::collections::HashMap;
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
 use std::sync::Arc;
-use std::thread::JoinHandle;
+use std::thread::{Builder, JoinHandle};
+use std::time::{Duration, Instant};
 use streamer;
+use wire;
+
+/// The most transactions that may be partly received at once. Once there are
+/// this many, the oldest is dropped to make room for a new one.
+pub const MAX_PARTIAL_TRANSACTIONS: usize = 1024;
+
+/// How long the rest of a transaction's fragments are waited for.
+pub const FRAGMENT_TIMEOUT_MS: u64 = 1000;
+
+/// The fragments of a transaction received so far.
+struct Partial {
+    fragments: Vec<Option<Vec<u8>>>,
+    received: usize,
+    started: Instant,
+}
+
+/// Collects fragments until every one of a transaction has arrived. Each
+/// transaction is identified by the address that sent it and its ID, so one
+/// sender can't interfere with the transactions of another.
+#[derive(Default)]
+pub struct Reassembler {
+    partials: HashMap<(SocketAddr, u64), Partial>,
+}
+
+impl Reassembler {
+    /// Add a fragment, and return the transaction if it was the last one
+    /// missing. A fragment whose count disagrees with the fragments before it
+    /// starts the transaction over.
+    pub fn insert(&mut self, addr: SocketAddr, fragment: &wire::Fragment) -> Option<Vec<u8>> {
+        let key = (addr, fragment.id);
+        let restart = match self.partials.get(&key) {
+            Some(partial) => partial.fragments.len() != fragment.count,
+            None => {
+                self.make_room();
+                true
+            }
+        };
+        if restart {
+            let partial = Partial {
+                fragments: vec![None; fragment.count],
+                received: 0,
+                started: Instant::now(),
+            };
+            self.partials.insert(key, partial);
+        }
+
+        let done = {
+            let partial = self.partials.get_mut(&key).expect("partial in insert");
+            if partial.fragments[fragment.index].is_none() {
+                partial.received += 1;
+            }
+            partial.fragments[fragment.index] = Some(fragment.data.to_vec());
+            partial.received == partial.fragments.len()
+        };
+        if !done {
+            return None;
+        }
+        let partial = self.partials.remove(&key).expect("partial in insert");
+        let data = partial
+            .fragments
+            .into_iter()
+            .flat_map(|fragment| fragment.expect("fragment in insert"))
+            .collect();
+        Some(data)
+    }
+
+    /// Drop the oldest partial transaction if there's no room for another.
+    fn make_room(&mut self) {
+        if self.partials.len() < MAX_PARTIAL_TRANSACTIONS {
+            return;
+        }
+        let oldest = self.partials
+            .iter()
+            .min_by_key(|(_, partial)| partial.started)
+            .map(|(key, _)| *key);
+        if let Some(key) = oldest {
+            self.partials.remove(&key);
+        }
+    }
+
+    /// Drop the transactions whose fragments stopped arriving.
+    pub fn expire(&mut self) {
+        let timeout = Duration::from_millis(FRAGMENT_TIMEOUT_MS);
+        self.partials
+            .retain(|_, partial| partial.started.elapsed() < timeout);
+    }
+
+    /// Move the fragments in `packets` into the reassembler, and append the
+    /// transactions they complete to `packets.reassembled`. The fragments'
+    /// packets are emptied, so that later stages ignore them.
+    pub fn reassemble(&mut self, packets: &mut Packets) {
+        for packet in &mut packets.packets {
+            let addr = packet.meta.addr();
+            let data = match wire::decode_fragment(&packet.data[..packet.meta.size]) {
+                Ok(fragment) => self.insert(addr, &fragment),
+                Err(_) => continue,
+            };
+            packet.meta.size = 0;
+            if let Some(data) = data {
+                packets.reassembled.push((data, addr));
+            }
+        }
+        self.expire();
+    }
+}
 
 pub struct FetchStage {
     pub packet_receiver: streamer::PacketReceiver,
-    pub thread_hdl: JoinHandle<()>,
+    pub thread_hdls: Vec<JoinHandle<()>>,
 }
 
 impl FetchStage {
@@ -19,13 +128,133 @@ impl FetchStage {
         exit: Arc<AtomicBool>,
         packet_recycler: packet::PacketRecycler,
     ) -> Self {
+        let (fragment_sender, fragment_receiver) = channel();
+        let receiver_hdl =
+            streamer::receiver(socket, exit.clone(), packet_recycler.clone(), fragment_sender);
+
         let (packet_sender, packet_receiver) = channel();
-        let thread_hdl =
-            streamer::receiver(socket, exit.clone(), packet_recycler.clone(), packet_sender);
+        let reassembler_hdl = Builder::new()
+            .name("solana-reassembler".to_string())
+            .spawn(move || {
+                let mut reassembler = Reassembler::default();
+                loop {
+                    let e = Self::reassemble(&mut reassembler, &fragment_receiver, &packet_sender);
+                    if e.is_err() && exit.load(Ordering::Relaxed) {
+                        break;
+                    }
+                }
+            })
+            .unwrap();
 
         FetchStage {
             packet_receiver,
-            thread_hdl,
+            thread_hdls: vec![receiver_hdl, reassembler_hdl],
+        }
+    }
+
+    fn reassemble(
+        reassembler: &mut Reassembler,
+        receiver: &Receiver<SharedPackets>,
+        sender: &Sender<SharedPackets>,
+    ) -> Result<()> {
+        let msgs = receiver.recv_timeout(Duration::new(1, 0))?;
+        reassembler.reassemble(&mut msgs.write().expect("'msgs' write lock in reassemble"));
+        sender.send(msgs)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hash::Hash;
+    use packet::{Packet, PACKET_DATA_SIZE};
+    use signature::{KeyPair, KeyPairUtil, Signature};
+    use transaction::{Instruction, Transaction};
+
+    fn large_tx_data() -> Vec<u8> {
+        let keypair = KeyPair::new();
+        let witness = Instruction::ApplySignature(Signature::default());
+        let zero = Hash::default();
+        let tx = Transaction::new_batch(&keypair, vec![witness; 64], zero, zero, 0);
+        wire::encode(&tx)
+    }
+
+    fn to_packet(datagram: &[u8], addr: &SocketAddr) -> Packet {
+        let mut packet = Packet::default();
+        packet.data[..datagram.len()].copy_from_slice(datagram);
+        packet.meta.size = datagram.len();
+        packet.meta.set_addr(addr);
+        packet
+    }
+
+    #[test]
+    fn test_reassemble() {
+        let addr = "127.0.0.1:1234".parse().unwrap();
+        let data = large_tx_data();
+        assert!(data.len() > PACKET_DATA_SIZE);
+        let mut datagrams = wire::to_datagrams(&data).unwrap();
+        assert!(datagrams.len() > 2);
+
+        // Fragments may arrive out of order, alongside whole transactions.
+        datagrams.reverse();
+        let whole = vec![wire::VERSION; 8];
+        datagrams.insert(1, whole.clone());
+        let mut packets = Packets::default();
+        packets.packets = datagrams.iter().map(|d| to_packet(d, &addr)).collect();
+
+        let mut reassembler = Reassembler::default();
+        reassembler.reassemble(&mut packets);
+        assert_eq!(packets.reassembled, vec![(data, addr)]);
+        assert!(reassembler.partials.is_empty());
+        for (i, packet) in packets.packets.iter().enumerate() {
+            let size = if i == 1 { whole.len() } else { 0 };
+            assert_eq!(packet.meta.size, size);
+        }
+    }
+
+    #[test]
+    fn test_reassemble_by_sender() {
+        let alice = "127.0.0.1:1234".parse().unwrap();
+        let mallory = "127.0.0.1:5678".parse().unwrap();
+        let data = large_tx_data();
+        let datagrams = wire::to_datagrams(&data).unwrap();
+        let fragments: Vec<_> = datagrams
+            .iter()
+            .map(|d| wire::decode_fragment(d).unwrap())
+            .collect();
+
+        // Fragments with the same ID from another sender don't complete it.
+        let mut reassembler = Reassembler::default();
+        for fragment in &fragments[1..] {
+            assert_eq!(reassembler.insert(alice, fragment), None);
+        }
+        assert_eq!(reassembler.insert(mallory, &fragments[0]), None);
+
+        // Nor does resending a fragment that already arrived.
+        assert_eq!(reassembler.insert(alice, &fragments[1]), None);
+        assert_eq!(reassembler.insert(alice, &fragments[0]), Some(data));
+    }
+
+    #[test]
+    fn test_reassembler_limits() {
+        let data = large_tx_data();
+        let datagrams = wire::to_datagrams(&data).unwrap();
+        let fragment = wire::decode_fragment(&datagrams[0]).unwrap();
+
+        // Senders that never finish can't hold more than a fixed number of
+        // partial transactions.
+        let mut reassembler = Reassembler::default();
+        for port in 0..MAX_PARTIAL_TRANSACTIONS + 10 {
+            let addr = SocketAddr::new("127.0.0.1".parse().unwrap(), port as u16);
+            reassembler.insert(addr, &fragment);
+        }
+        assert_eq!(reassembler.partials.len(), MAX_PARTIAL_TRANSACTIONS);
+
+        for partial in reassembler.partials.values_mut() {
+            partial.started -= Duration::from_millis(FRAGMENT_TIMEOUT_MS);
         }
+        reassembler.expire();
+        assert!(reassembler.partials.is_empty());
     }
 }
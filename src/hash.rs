@@ -1,16 +1,19 @@
 //! The `hash` module provides functions for creating SHA-256 hashes.
 
 use generic_array::typenum::U32;
-use generic_array::GenericArray;
 use sha2::{Digest, Sha256};
 
-pub type Hash = GenericArray<u8, U32>;
+base58_bytes!(
+    /// A SHA-256 hash, written in base58.
+    Hash,
+    U32
+);
 
 /// Return a Sha256 hash for the given data.
 pub fn hash(val: &[u8]) -> Hash {
     let mut hasher = Sha256::default();
     hasher.input(val);
-    hasher.result()
+    Hash::from(hasher.result())
 }
 
 /// Return the hash of the given hash extended with the given value.
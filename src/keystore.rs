@@ -0,0 +1,273 @@
+//! The `keystore` module stores keypairs in files encrypted with a passphrase.
+//! A key is stretched from the passphrase with PBKDF2 and seals the keypair's
+//! PKCS#8 bytes with ChaCha20-Poly1305. The public key is kept in the clear,
+//! so it can be printed without the passphrase, and is authenticated as the
+//! sealed data's associated data, so it can't be swapped for another.
+
+use libc;
+use mint::Mint;
+use ring::rand::{SecureRandom, SystemRandom};
+use ring::{aead, digest, pbkdf2};
+use serde_json;
+use signature::{KeyPair, KeyPairUtil, PublicKey};
+use std::env;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::AsRawFd;
+use std::result;
+use untrusted::Input;
+
+/// The PBKDF2 iterations used for new keystores. Existing keystores record
+/// their own count, so it can be raised without breaking them.
+pub const ITERATIONS: u32 = 100_000;
+
+/// The most PBKDF2 iterations a keystore may ask for, so that a corrupt file
+/// can't make unlocking it take forever.
+pub const MAX_ITERATIONS: u32 = 10 * ITERATIONS;
+
+/// Scripts can pass the passphrase in this environment variable instead of
+/// typing it at the terminal.
+pub const PASSPHRASE_VAR: &str = "SOLANA_PASSPHRASE";
+
+const SALT_SIZE: usize = 16;
+const KEY_SIZE: usize = 32;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum KeystoreError {
+    /// The passphrase is wrong, or the keystore was tampered with.
+    WrongPassphrase,
+    /// The bytes aren't a PKCS#8 keypair for the keystore's public key.
+    InvalidKeyPair,
+    /// The iteration count is zero or above `MAX_ITERATIONS`.
+    InvalidIterations(u32),
+}
+
+pub type Result<T> = result::Result<T, KeystoreError>;
+
+/// A keypair sealed with a passphrase.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct Keystore {
+    pub pubkey: PublicKey,
+    pub iterations: u32,
+    salt: Vec<u8>,
+    nonce: Vec<u8>,
+    ciphertext: Vec<u8>,
+}
+
+fn stretch(passphrase: &str, salt: &[u8], iterations: u32) -> [u8; KEY_SIZE] {
+    let mut key = [0u8; KEY_SIZE];
+    pbkdf2::derive(
+        &digest::SHA256,
+        iterations,
+        salt,
+        passphrase.as_bytes(),
+        &mut key,
+    );
+    key
+}
+
+impl Keystore {
+    /// Seal the PKCS#8 bytes of a keypair with the passphrase.
+    pub fn new(pkcs8: &[u8], passphrase: &str) -> Result<Self> {
+        let keypair =
+            KeyPair::from_pkcs8(Input::from(pkcs8)).map_err(|_| KeystoreError::InvalidKeyPair)?;
+        let pubkey = keypair.pubkey();
+
+        let algorithm = &aead::CHACHA20_POLY1305;
+        let rnd = SystemRandom::new();
+        let mut salt = vec![0u8; SALT_SIZE];
+        let mut nonce = vec![0u8; algorithm.nonce_len()];
+        rnd.fill(&mut salt).expect("fill in keystore pub fn new");
+        rnd.fill(&mut nonce).expect("fill in keystore pub fn new");
+
+        let key = stretch(passphrase, &salt, ITERATIONS);
+        let sealing_key =
+            aead::SealingKey::new(algorithm, &key).expect("sealing key in keystore pub fn new");
+        let mut ciphertext = pkcs8.to_vec();
+        ciphertext.resize(pkcs8.len() + algorithm.tag_len(), 0);
+        let len = aead::seal_in_place(
+            &sealing_key,
+            &nonce,
+            &pubkey,
+            &mut ciphertext,
+            algorithm.tag_len(),
+        ).expect("seal_in_place in keystore pub fn new");
+        ciphertext.truncate(len);
+
+        Ok(Keystore {
+            pubkey,
+            iterations: ITERATIONS,
+            salt,
+            nonce,
+            ciphertext,
+        })
+    }
+
+    /// Return the PKCS#8 bytes of the sealed keypair.
+    pub fn decrypt(&self, passphrase: &str) -> Result<Vec<u8>> {
+        if self.iterations == 0 || self.iterations > MAX_ITERATIONS {
+            return Err(KeystoreError::InvalidIterations(self.iterations));
+        }
+        let key = stretch(passphrase, &self.salt, self.iterations);
+        let opening_key = aead::OpeningKey::new(&aead::CHACHA20_POLY1305, &key)
+            .expect("opening key in keystore pub fn decrypt");
+        let mut buffer = self.ciphertext.clone();
+        let pkcs8 = aead::open_in_place(&opening_key, &self.nonce, &self.pubkey, 0, &mut buffer)
+            .map_err(|_| KeystoreError::WrongPassphrase)?
+            .to_vec();
+        Ok(pkcs8)
+    }
+
+    /// Return the sealed keypair, checking that it belongs to `pubkey`.
+    pub fn keypair(&self, passphrase: &str) -> Result<KeyPair> {
+        let pkcs8 = self.decrypt(passphrase)?;
+        let keypair =
+            KeyPair::from_pkcs8(Input::from(&pkcs8)).map_err(|_| KeystoreError::InvalidKeyPair)?;
+        if keypair.pubkey() != self.pubkey {
+            return Err(KeystoreError::InvalidKeyPair);
+        }
+        Ok(keypair)
+    }
+}
+
+fn invalid_data<E: ::std::fmt::Debug>(e: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("{:?}", e))
+}
+
+pub fn read_keystore(path: &str) -> io::Result<Keystore> {
+    let file = File::open(path)?;
+    serde_json::from_reader(file).map_err(invalid_data)
+}
+
+/// Write the keystore to a new file that only its owner can read.
+pub fn write_keystore(path: &str, keystore: &Keystore) -> io::Result<()> {
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)?;
+    serde_json::to_writer(&mut file, keystore).map_err(invalid_data)?;
+    writeln!(file)
+}
+
+/// Read the PKCS#8 bytes of the keypair in `path`, which may be a keystore,
+/// a mint file or a json array of the bytes. Keystores, including the one a
+/// mint file names, are unlocked with a passphrase from `read_passphrase`.
+pub fn read_pkcs8(path: &str) -> io::Result<Vec<u8>> {
+    let mut buffer = String::new();
+    File::open(path)?.read_to_string(&mut buffer)?;
+    if let Ok(keystore) = serde_json::from_str::<Keystore>(&buffer) {
+        let passphrase = read_passphrase(&format!("Passphrase for {}: ", path))?;
+        return keystore.decrypt(&passphrase).map_err(invalid_data);
+    }
+    if let Ok(mut mint) = serde_json::from_str::<Mint>(&buffer) {
+        mint.unlock()?;
+        return Ok(mint.pkcs8);
+    }
+    serde_json::from_str(&buffer).map_err(invalid_data)
+}
+
+/// Read the keypair in `path`, in any of the formats `read_pkcs8` accepts.
+pub fn read_keypair(path: &str) -> io::Result<KeyPair> {
+    let pkcs8 = read_pkcs8(path)?;
+    KeyPair::from_pkcs8(Input::from(&pkcs8)).map_err(invalid_data)
+}
+
+/// Return the passphrase in `PASSPHRASE_VAR`, or else prompt for one on the
+/// terminal without echoing it.
+pub fn read_passphrase(prompt: &str) -> io::Result<String> {
+    if let Ok(passphrase) = env::var(PASSPHRASE_VAR) {
+        return Ok(passphrase);
+    }
+    let mut tty = OpenOptions::new().read(true).write(true).open("/dev/tty")?;
+    write!(tty, "{}", prompt)?;
+    tty.flush()?;
+
+    let fd = tty.as_raw_fd();
+    let mut termios: libc::termios = unsafe { ::std::mem::zeroed() };
+    if unsafe { libc::tcgetattr(fd, &mut termios) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let echo = termios.c_lflag;
+    termios.c_lflag &= !libc::ECHO;
+    unsafe { libc::tcsetattr(fd, libc::TCSANOW, &termios) };
+
+    let mut passphrase = String::new();
+    let result = BufReader::new(&tty).read_line(&mut passphrase);
+
+    termios.c_lflag = echo;
+    unsafe { libc::tcsetattr(fd, libc::TCSANOW, &termios) };
+    writeln!(tty)?;
+    result?;
+
+    while passphrase.ends_with('\n') || passphrase.ends_with('\r') {
+        passphrase.pop();
+    }
+    Ok(passphrase)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_pkcs8() -> Vec<u8> {
+        KeyPair::generate_pkcs8(&SystemRandom::new())
+            .unwrap()
+            .to_vec()
+    }
+
+    #[test]
+    fn test_keystore_roundtrip() {
+        let pkcs8 = new_pkcs8();
+        let keystore = Keystore::new(&pkcs8, "hunter2").unwrap();
+        assert_eq!(keystore.decrypt("hunter2"), Ok(pkcs8.clone()));
+
+        let keypair = keystore.keypair("hunter2").unwrap();
+        assert_eq!(keypair.pubkey(), keystore.pubkey);
+        assert!(!keystore.ciphertext.windows(pkcs8.len()).any(|w| w == &pkcs8[..]));
+
+        let json = serde_json::to_string(&keystore).unwrap();
+        let keystore2: Keystore = serde_json::from_str(&json).unwrap();
+        assert_eq!(keystore2, keystore);
+        assert_eq!(keystore2.decrypt("hunter2"), Ok(pkcs8));
+    }
+
+    #[test]
+    fn test_keystore_errors() {
+        assert_eq!(
+            Keystore::new(&[1, 2, 3], "hunter2"),
+            Err(KeystoreError::InvalidKeyPair)
+        );
+
+        let keystore = Keystore::new(&new_pkcs8(), "hunter2").unwrap();
+        assert_eq!(
+            keystore.decrypt("hunter3"),
+            Err(KeystoreError::WrongPassphrase)
+        );
+
+        let mut swapped = keystore.clone();
+        swapped.pubkey = KeyPair::new().pubkey(); // <-- attack!
+        assert_eq!(
+            swapped.decrypt("hunter2"),
+            Err(KeystoreError::WrongPassphrase)
+        );
+
+        let mut tampered = keystore.clone();
+        tampered.ciphertext[0] ^= 1;
+        assert_eq!(
+            tampered.decrypt("hunter2"),
+            Err(KeystoreError::WrongPassphrase)
+        );
+
+        // The iteration count isn't authenticated, so it's checked before use.
+        for &iterations in &[0, MAX_ITERATIONS + 1, u32::max_value()] {
+            let mut tampered = keystore.clone();
+            tampered.iterations = iterations;
+            assert_eq!(
+                tampered.decrypt("hunter2"),
+                Err(KeystoreError::InvalidIterations(iterations))
+            );
+        }
+    }
+}
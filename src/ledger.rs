@@ -1,16 +1,14 @@
 //! The `ledger` module provides functions for parallel verification of the
 //! Proof of History ledger.
 
-use bincode::{deserialize, serialize_into};
+use bincode::{deserialize, serialize_into, serialized_size};
 use entry::{next_entry, Entry};
 use hash::Hash;
 use packet;
-use packet::{SharedBlob, BLOB_DATA_SIZE, BLOB_SIZE};
+use packet::{SharedBlob, BLOB_HEADER_SIZE, BLOB_SIZE};
 use rayon::prelude::*;
-use std::cmp::min;
 use std::collections::VecDeque;
 use std::io::Cursor;
-use std::mem::size_of;
 use transaction::Transaction;
 
 pub trait Block {
@@ -27,42 +25,57 @@ impl Block for [Entry] {
     }
 
     fn to_blobs(&self, blob_recycler: &packet::BlobRecycler, q: &mut VecDeque<SharedBlob>) {
+        // Transactions hold their signatures, co-signers and plans on the
+        // heap, so only their serialized size bounds what a blob can carry.
+        let max_size = (BLOB_SIZE - BLOB_HEADER_SIZE) as u64;
+        let empty_size =
+            serialized_size(&Vec::<Entry>::new()).expect("serialized_size in to_blobs");
         let mut start = 0;
         let mut end = 0;
         while start < self.len() {
             let mut entries: Vec<Vec<Entry>> = Vec::new();
-            let mut total = 0;
+            let mut total = empty_size;
             for i in &self[start..] {
-                total += size_of::<Transaction>() * i.transactions.len();
-                total += size_of::<Entry>();
-                if total >= BLOB_DATA_SIZE {
+                total += serialized_size(i).expect("serialized_size in to_blobs");
+                if total > max_size {
                     break;
                 }
                 end += 1;
             }
             // See if we need to split the transactions
             if end <= start {
+                let entry = &self[end];
+                let header_size = empty_size + serialized_size(&Entry {
+                    num_hashes: entry.num_hashes,
+                    id: entry.id,
+                    transactions: vec![],
+                }).expect("serialized_size in to_blobs");
                 let mut transaction_start = 0;
-                let num_transactions_per_blob = BLOB_DATA_SIZE / size_of::<Transaction>();
-                let total_entry_chunks = (self[end].transactions.len() + num_transactions_per_blob
-                    - 1) / num_transactions_per_blob;
-                trace!(
-                    "splitting transactions end: {} total_chunks: {}",
-                    end,
-                    total_entry_chunks
-                );
-                for _ in 0..total_entry_chunks {
-                    let transaction_end = min(
-                        transaction_start + num_transactions_per_blob,
-                        self[end].transactions.len(),
+                while transaction_start < entry.transactions.len() {
+                    let mut size = header_size;
+                    let num_transactions = entry.transactions[transaction_start..]
+                        .iter()
+                        .take_while(|tx| {
+                            size += serialized_size(tx).expect("serialized_size in to_blobs");
+                            size <= max_size
+                        })
+                        .count();
+                    // Transactions are limited to wire::MAX_TRANSACTION_SIZE,
+                    // well within a blob.
+                    assert!(num_transactions > 0, "transaction larger than a blob");
+                    let transaction_end = transaction_start + num_transactions;
+                    trace!(
+                        "splitting transactions end: {} transactions: {}..{}",
+                        end,
+                        transaction_start,
+                        transaction_end
                     );
-                    let mut entry = Entry {
-                        num_hashes: self[end].num_hashes,
-                        id: self[end].id,
-                        transactions: self[end].transactions[transaction_start..transaction_end]
+                    entries.push(vec![Entry {
+                        num_hashes: entry.num_hashes,
+                        id: entry.id,
+                        transactions: entry.transactions[transaction_start..transaction_end]
                             .to_vec(),
-                    };
-                    entries.push(vec![entry]);
+                    }]);
                     transaction_start = transaction_end;
                 }
                 end += 1;
@@ -128,10 +141,14 @@ pub fn reconstruct_entries_from_blobs(blobs: &VecDeque<SharedBlob>) -> Vec<Entry
 #[cfg(test)]
 mod tests {
     use super::*;
+    use bincode::serialize;
     use hash::hash;
     use packet::BlobRecycler;
+    use payment_plan::Payment;
     use signature::{KeyPair, KeyPairUtil};
+    use std::mem::size_of;
     use transaction::Transaction;
+    use wire;
 
     #[test]
     fn test_verify_slice() {
@@ -152,7 +169,7 @@ mod tests {
         let zero = Hash::default();
         let one = hash(&zero);
         let keypair = KeyPair::new();
-        let tx0 = Transaction::new(&keypair, keypair.pubkey(), 1, one);
+        let tx0 = Transaction::new(&keypair, keypair.pubkey(), 1, zero, one);
         let transactions = vec![tx0.clone(); 10000];
         let e0 = Entry::new(&zero, 0, transactions);
 
@@ -164,12 +181,50 @@ mod tests {
         assert_eq!(reconstruct_entries_from_blobs(&blob_q), entries);
     }
 
+    #[test]
+    fn test_large_transactions_to_blobs() {
+        let zero = Hash::default();
+        let keypair = KeyPair::new();
+        let cosigner = KeyPair::new();
+        let payment = Payment {
+            tokens: 1,
+            to: keypair.pubkey(),
+        };
+        // About the largest transactions the network accepts.
+        let count = (wire::MAX_TRANSACTION_SIZE - 512) / serialize(&payment).unwrap().len();
+        let split = Transaction::new_split(&keypair, vec![payment; count], zero, zero);
+        let cosigners = [(&cosigner, 1)];
+        let to = keypair.pubkey();
+        let cosigned = Transaction::new_joint(&keypair, &cosigners, to, 2, zero, zero);
+        for tx in &[&split, &cosigned] {
+            assert!(serialize(tx).unwrap().len() > size_of::<Transaction>());
+        }
+        assert!(serialize(&split).unwrap().len() <= wire::MAX_TRANSACTION_SIZE);
+
+        let transactions = (0..100)
+            .map(|i| if i % 2 == 0 { split.clone() } else { cosigned.clone() })
+            .collect();
+        let e0 = Entry::new(&zero, 0, transactions);
+        let e1 = Entry::new(&e0.id, 0, vec![split.clone(); 3]);
+        let e2 = Entry::new(&e1.id, 0, vec![cosigned.clone(); 3]);
+        let entries = vec![e0, e1, e2];
+        let blob_recycler = BlobRecycler::default();
+        let mut blob_q = VecDeque::new();
+        entries.to_blobs(&blob_recycler, &mut blob_q);
+
+        assert!(blob_q.len() > 1);
+        for blob in &blob_q {
+            assert!(blob.read().unwrap().meta.size <= BLOB_SIZE);
+        }
+        assert_eq!(reconstruct_entries_from_blobs(&blob_q), entries);
+    }
+
     #[test]
     fn test_next_entries() {
         let mut id = Hash::default();
         let next_id = hash(&id);
         let keypair = KeyPair::new();
-        let tx0 = Transaction::new(&keypair, keypair.pubkey(), 1, next_id);
+        let tx0 = Transaction::new(&keypair, keypair.pubkey(), 1, id, next_id);
         let transactions = vec![tx0.clone(); 5];
         let transaction_batches = vec![transactions.clone(); 5];
         let entries0 = next_entries(&id, 0, transaction_batches);
@@ -1,15 +1,20 @@
 #![cfg_attr(feature = "unstable", feature(test))]
 pub mod bank;
 pub mod banking_stage;
+#[macro_use]
+pub mod base58;
 pub mod budget;
+pub mod budget_syntax;
 pub mod crdt;
 pub mod data_replicator;
+pub mod derivation;
 pub mod entry;
 pub mod entry_writer;
 #[cfg(feature = "erasure")]
 pub mod erasure;
 pub mod fetch_stage;
 pub mod hash;
+pub mod keystore;
 pub mod ledger;
 pub mod logger;
 pub mod mint;
@@ -33,6 +38,8 @@ pub mod timing;
 pub mod tpu;
 pub mod transaction;
 pub mod tvu;
+pub mod vesting;
+pub mod wire;
 pub mod write_stage;
 extern crate bincode;
 extern crate byteorder;
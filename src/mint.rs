@@ -1,17 +1,36 @@
 //! The `mint` module is a library for generating the chain's genesis block.
 
+use derivation::{self, ExtendedKey};
 use entry::Entry;
 use hash::{hash, Hash};
+use keystore::{self, Keystore};
+use rayon::prelude::*;
 use ring::rand::SystemRandom;
 use signature::{KeyPair, KeyPairUtil, PublicKey};
+use std::io;
 use transaction::Transaction;
 use untrusted::Input;
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Mint {
+    /// The PKCS#8 bytes of the mint's keypair. Mint files made from a
+    /// keystore leave them out, and name the keystore instead.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub pkcs8: Vec<u8>,
     pubkey: PublicKey,
     pub tokens: i64,
+    /// The keys trusted to report the time, set in the genesis entry. The
+    /// mint is the only time source if none are listed.
+    #[serde(default)]
+    pub time_sources: Vec<PublicKey>,
+    /// The account that collects transaction fees, set in the genesis entry.
+    /// The mint collects them if it's None.
+    #[serde(default)]
+    pub fee_collector: Option<PublicKey>,
+    /// The path of the keystore holding the mint's keypair, if it isn't in
+    /// `pkcs8`. It must be unlocked before the mint can sign anything.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keystore: Option<String>,
 }
 
 impl Mint {
@@ -20,18 +39,63 @@ impl Mint {
         let pkcs8 = KeyPair::generate_pkcs8(&rnd)
             .expect("generate_pkcs8 in mint pub fn new")
             .to_vec();
-        let keypair =
-            KeyPair::from_pkcs8(Input::from(&pkcs8)).expect("from_pkcs8 in mint pub fn new");
+        Self::new_with_pkcs8(tokens, pkcs8)
+    }
+
+    /// Create a mint for an existing keypair, such as one from a keystore.
+    pub fn new_with_pkcs8(tokens: i64, pkcs8: Vec<u8>) -> Self {
+        let keypair = KeyPair::from_pkcs8(Input::from(&pkcs8))
+            .expect("from_pkcs8 in mint pub fn new_with_pkcs8");
         let pubkey = keypair.pubkey();
         Mint {
             pkcs8,
             pubkey,
             tokens,
+            time_sources: vec![pubkey],
+            fee_collector: None,
+            keystore: None,
         }
     }
 
+    /// Create a mint for the keypair in a keystore, without unlocking it.
+    pub fn new_with_keystore(tokens: i64, path: &str, keystore: &Keystore) -> Self {
+        Mint {
+            pkcs8: vec![],
+            pubkey: keystore.pubkey,
+            tokens,
+            time_sources: vec![keystore.pubkey],
+            fee_collector: None,
+            keystore: Some(path.to_string()),
+        }
+    }
+
+    /// Read the keypair from the mint's keystore, if it names one, with a
+    /// passphrase from `keystore::read_passphrase`.
+    pub fn unlock(&mut self) -> io::Result<()> {
+        let path = match &self.keystore {
+            Some(path) if self.pkcs8.is_empty() => path.clone(),
+            _ => return Ok(()),
+        };
+        let keystore = keystore::read_keystore(&path)?;
+        let invalid = |e| io::Error::new(io::ErrorKind::InvalidData, format!("{:?}", e));
+        if keystore.pubkey != self.pubkey {
+            return Err(invalid(keystore::KeystoreError::InvalidKeyPair));
+        }
+        let passphrase = keystore::read_passphrase(&format!("Passphrase for {}: ", path))?;
+        self.pkcs8 = keystore.decrypt(&passphrase).map_err(invalid)?;
+        Ok(())
+    }
+
+    /// Return the PKCS#8 bytes of the mint's keypair. Panics if the mint's
+    /// keystore hasn't been unlocked, since everything derived from them,
+    /// the ledger's seed included, would be wrong.
+    fn unlocked_pkcs8(&self) -> &[u8] {
+        assert!(!self.pkcs8.is_empty(), "mint keystore is locked");
+        &self.pkcs8
+    }
+
     pub fn seed(&self) -> Hash {
-        hash(&self.pkcs8)
+        hash(self.unlocked_pkcs8())
     }
 
     pub fn last_id(&self) -> Hash {
@@ -39,7 +103,8 @@ impl Mint {
     }
 
     pub fn keypair(&self) -> KeyPair {
-        KeyPair::from_pkcs8(Input::from(&self.pkcs8)).expect("from_pkcs8 in mint pub fn keypair")
+        KeyPair::from_pkcs8(Input::from(self.unlocked_pkcs8()))
+            .expect("from_pkcs8 in mint pub fn keypair")
     }
 
     pub fn pubkey(&self) -> PublicKey {
@@ -48,8 +113,16 @@ impl Mint {
 
     pub fn create_transactions(&self) -> Vec<Transaction> {
         let keypair = self.keypair();
-        let tx = Transaction::new(&keypair, self.pubkey(), self.tokens, self.seed());
-        vec![tx]
+        let seed = self.seed();
+        let mut txs = vec![Transaction::new(&keypair, self.pubkey(), self.tokens, seed, seed)];
+        if !self.time_sources.is_empty() {
+            let sources = self.time_sources.clone();
+            txs.push(Transaction::new_time_sources(&keypair, sources, seed, seed));
+        }
+        if let Some(collector) = self.fee_collector {
+            txs.push(Transaction::new_fee_collector(&keypair, collector, seed, seed));
+        }
+        txs
     }
 
     pub fn create_entries(&self) -> Vec<Entry> {
@@ -65,6 +138,23 @@ pub struct MintDemo {
     pub num_accounts: i64,
 }
 
+impl MintDemo {
+    /// Return the keypairs of the demo accounts, derived at paths `m/0'` to
+    /// `m/<num_accounts - 1>'` from a master seed that is the mint's private
+    /// key. Every tool holding the mint file finds the same accounts, and
+    /// nobody without it can. The mint's `seed` can't be used, since it's
+    /// the genesis entry ID that every node sees.
+    pub fn keypairs(&self) -> Vec<KeyPair> {
+        let seed = derivation::pkcs8_seed(self.mint.unlocked_pkcs8())
+            .expect("pkcs8_seed in MintDemo pub fn keypairs");
+        let master = ExtendedKey::new_master(seed);
+        (0..self.num_accounts as u32)
+            .into_par_iter()
+            .map(|i| master.child(i).keypair())
+            .collect()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -74,14 +164,78 @@ mod tests {
 
     #[test]
     fn test_create_transactions() {
-        let mut transactions = Mint::new(100).create_transactions().into_iter();
+        let mint = Mint::new(100);
+        let mut transactions = mint.create_transactions().into_iter();
         let tx = transactions.next().unwrap();
         if let Instruction::NewContract(contract) = tx.instruction {
             if let Plan::Budget(Budget::Pay(payment)) = contract.plan {
                 assert_eq!(tx.from, payment.to);
             }
         }
+        let tx = transactions.next().unwrap();
+        assert_eq!(
+            tx.instruction,
+            Instruction::SetTimeSources(vec![mint.pubkey()])
+        );
         assert_eq!(transactions.next(), None);
+
+        let collector = KeyPair::new().pubkey();
+        let mint = Mint {
+            fee_collector: Some(collector),
+            ..mint
+        };
+        let tx = mint.create_transactions().pop().unwrap();
+        assert_eq!(tx.instruction, Instruction::SetFeeCollector(collector));
+    }
+
+    #[test]
+    fn test_new_with_pkcs8() {
+        let mint = Mint::new(100);
+        let mint2 = Mint::new_with_pkcs8(100, mint.pkcs8.clone());
+        assert_eq!(mint2.pubkey(), mint.pubkey());
+        assert_eq!(mint2.last_id(), mint.last_id());
+    }
+
+    #[test]
+    fn test_new_with_keystore() {
+        use serde_json;
+
+        let mint = Mint::new(100);
+        let keystore = Keystore::new(&mint.pkcs8, "hunter2").unwrap();
+        let locked = Mint::new_with_keystore(100, "key.json", &keystore);
+        assert_eq!(locked.pubkey(), mint.pubkey());
+
+        // The mint file holds the keystore's path, not the key.
+        let json = serde_json::to_string(&locked).unwrap();
+        assert!(!json.contains("pkcs8"));
+        let locked: Mint = serde_json::from_str(&json).unwrap();
+        assert_eq!(locked.keystore, Some("key.json".to_string()));
+        assert!(locked.pkcs8.is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "mint keystore is locked")]
+    fn test_locked_mint_has_no_entries() {
+        let mint = Mint::new(100);
+        let keystore = Keystore::new(&mint.pkcs8, "hunter2").unwrap();
+        Mint::new_with_keystore(100, "key.json", &keystore).create_entries();
+    }
+
+    #[test]
+    fn test_demo_keypairs() {
+        let demo = MintDemo {
+            mint: Mint::new(100),
+            num_accounts: 3,
+        };
+        let pubkeys: Vec<_> = demo.keypairs().iter().map(|k| k.pubkey()).collect();
+        let seed = derivation::pkcs8_seed(&demo.mint.pkcs8).unwrap();
+        let master = ExtendedKey::new_master(seed);
+        let expected: Vec<_> = (0..3).map(|i| master.child(i).pubkey()).collect();
+        assert_eq!(pubkeys, expected);
+
+        // The genesis entry ID is public, so it mustn't be what they're from.
+        let public = ExtendedKey::new_master(&demo.mint.seed());
+        assert_ne!(pubkeys[0], public.child(0).pubkey());
     }
 
     #[test]
@@ -19,7 +19,11 @@ pub type BlobRecycler = Recycler<Blob>;
 pub const NUM_PACKETS: usize = 1024 * 8;
 pub const BLOB_SIZE: usize = 64 * 1024;
 pub const BLOB_DATA_SIZE: usize = BLOB_SIZE - BLOB_ID_END;
-pub const PACKET_DATA_SIZE: usize = 256;
+/// The largest datagram a node reads. 1280 bytes is the minimum IPv6 MTU,
+/// less 40 for the IPv6 header and 8 for the UDP header, so a packet of
+/// this size crosses any network without being fragmented. Longer
+/// transactions are sent in several packets; see the `wire` module.
+pub const PACKET_DATA_SIZE: usize = 1280 - 40 - 8;
 pub const NUM_BLOBS: usize = (NUM_PACKETS * PACKET_DATA_SIZE) / BLOB_SIZE;
 
 #[derive(Clone, Default)]
@@ -97,6 +101,9 @@ impl Meta {
 #[derive(Debug)]
 pub struct Packets {
     pub packets: Vec<Packet>,
+    /// Transactions reassembled from the fragments in `packets`, which are
+    /// too large for a `Packet`, along with the address that sent them.
+    pub reassembled: Vec<(Vec<u8>, SocketAddr)>,
 }
 
 //auto derive doesn't support large arrays
@@ -104,6 +111,7 @@ impl Default for Packets {
     fn default() -> Packets {
         Packets {
             packets: vec![Packet::default(); NUM_PACKETS],
+            reassembled: vec![],
         }
     }
 }
@@ -170,6 +178,7 @@ impl<T: Default> Recycler<T> {
 impl Packets {
     fn run_read_from(&mut self, socket: &UdpSocket) -> Result<usize> {
         self.packets.resize(NUM_PACKETS, Packet::default());
+        self.reassembled.clear();
         let mut i = 0;
         //DOCUMENTED SIDE-EFFECT
         //Performance out of the IO without poll
@@ -221,6 +230,7 @@ pub fn to_packets<T: Serialize>(r: &PacketRecycler, xs: Vec<T>) -> Vec<SharedPac
     let mut out = vec![];
     for x in xs.chunks(NUM_PACKETS) {
         let p = r.allocate();
+        p.write().unwrap().reassembled.clear();
         p.write()
             .unwrap()
             .packets
@@ -10,6 +10,9 @@ use signature::PublicKey;
 pub enum Witness {
     Timestamp(DateTime<Utc>),
     Signature(PublicKey),
+    Preimage(Vec<u8>),
+    /// The number of ticks in the ledger, as counted by the bank.
+    TickHeight(u64),
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
@@ -19,13 +22,36 @@ pub struct Payment {
 }
 
 pub trait PaymentPlan {
-    /// Return Payment if the payment plan requires no additional Witnesses.
+    /// Return Payment if the payment plan requires no additional Witnesses
+    /// and pays a single recipient.
     fn final_payment(&self) -> Option<Payment>;
 
+    /// Return every Payment if the payment plan requires no additional Witnesses.
+    fn final_payments(&self) -> Option<Vec<Payment>> {
+        self.final_payment().map(|payment| vec![payment])
+    }
+
     /// Return true if the plan spends exactly `spendable_tokens`.
     fn verify(&self, spendable_tokens: i64) -> bool;
 
+    /// Return true if the plan has already recorded the given Witness as an
+    /// approval, and would ignore it.
+    fn is_duplicate_witness(&self, witness: &Witness) -> bool;
+
     /// Apply a witness to the payment plan to see if the plan can be reduced.
     /// If so, modify the plan in-place.
     fn apply_witness(&mut self, witness: &Witness);
+
+    /// Return the earliest time at which a Timestamp Witness could reduce
+    /// the plan, or None if no timestamp can.
+    fn next_deadline(&self) -> Option<DateTime<Utc>>;
+
+    /// Return true if `pubkey` may be paid by the plan or witness it.
+    fn involves(&self, pubkey: &PublicKey) -> bool;
+
+    /// Remove and return the Payments that are due while the rest of the plan
+    /// still waits on Witnesses. Plans that pay out all at once have none.
+    fn take_due_payments(&mut self) -> Vec<Payment> {
+        vec![]
+    }
 }
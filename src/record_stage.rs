@@ -178,8 +178,8 @@ mod tests {
         let record_stage = RecordStage::new(signal_receiver, &zero);
         let alice_keypair = KeyPair::new();
         let bob_pubkey = KeyPair::new().pubkey();
-        let tx0 = Transaction::new(&alice_keypair, bob_pubkey, 1, zero);
-        let tx1 = Transaction::new(&alice_keypair, bob_pubkey, 2, zero);
+        let tx0 = Transaction::new(&alice_keypair, bob_pubkey, 1, zero, zero);
+        let tx1 = Transaction::new(&alice_keypair, bob_pubkey, 2, zero, zero);
         tx_sender
             .send(Signal::Transactions(vec![tx0, tx1]))
             .unwrap();
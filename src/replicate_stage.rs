@@ -1,11 +1,12 @@
 //! The `replicate_stage` replicates transactions broadcast by the leader.
 
 use bank::Bank;
+use crdt::Crdt;
 use ledger;
 use packet;
 use result::Result;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 use std::thread::{Builder, JoinHandle};
 use std::time::Duration;
 use streamer;
@@ -18,6 +19,7 @@ impl ReplicateStage {
     /// Process verified blobs, already in order
     fn replicate_requests(
         bank: &Arc<Bank>,
+        crdt: &Arc<RwLock<Crdt>>,
         verified_receiver: &streamer::BlobReceiver,
         blob_recycler: &packet::BlobRecycler,
     ) -> Result<()> {
@@ -29,6 +31,10 @@ impl ReplicateStage {
             error!("process_entries {} {:?}", blobs.len(), res);
         }
         res?;
+        let (state_hash, count) = bank.state_hash();
+        crdt.write()
+            .expect("'crdt' write lock in fn replicate_requests")
+            .set_last_verified(state_hash, count);
         for blob in blobs {
             blob_recycler.recycle(blob);
         }
@@ -37,6 +43,7 @@ impl ReplicateStage {
 
     pub fn new(
         bank: Arc<Bank>,
+        crdt: Arc<RwLock<Crdt>>,
         exit: Arc<AtomicBool>,
         window_receiver: streamer::BlobReceiver,
         blob_recycler: packet::BlobRecycler,
@@ -44,7 +51,8 @@ impl ReplicateStage {
         let thread_hdl = Builder::new()
             .name("solana-replicate-stage".to_string())
             .spawn(move || loop {
-                let e = Self::replicate_requests(&bank, &window_receiver, &blob_recycler);
+                let e =
+                    Self::replicate_requests(&bank, &crdt, &window_receiver, &blob_recycler);
                 if e.is_err() && exit.load(Ordering::Relaxed) {
                     break;
                 }
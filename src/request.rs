@@ -1,14 +1,23 @@
 //! The `request` module defines the messages for the thin client.
 
+use bank::{PendingContract, SignatureStatus};
+use bincode::serialized_size;
 use hash::Hash;
-use signature::PublicKey;
+use packet::BLOB_DATA_SIZE;
+use signature::{PublicKey, Signature};
 
 #[cfg_attr(feature = "cargo-clippy", allow(large_enum_variant))]
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum Request {
     GetBalance { key: PublicKey },
     GetLastId,
+    GetGenesis,
     GetTransactionCount,
+    GetEntryFees { id: Hash },
+    GetSignatureStatus { sig: Signature },
+    GetPendingContracts { key: PublicKey },
+    GetTickHeight,
+    GetNonce { key: PublicKey },
 }
 
 impl Request {
@@ -22,5 +31,81 @@ impl Request {
 pub enum Response {
     Balance { key: PublicKey, val: Option<i64> },
     LastId { id: Hash },
+    Genesis { id: Hash },
     TransactionCount { transaction_count: u64 },
+    EntryFees { id: Hash, fees: Option<i64> },
+    SignatureStatus { sig: Signature, status: SignatureStatus },
+    PendingContracts {
+        key: PublicKey,
+        contracts: Vec<(Signature, PendingContract)>,
+    },
+    TickHeight { tick_height: u64 },
+    Nonce { key: PublicKey, nonce: Option<Hash> },
+}
+
+impl Response {
+    /// Return a `PendingContracts` response with as many of `contracts` as
+    /// fit in a blob, in order. Plans vary in size, so the response is
+    /// limited by its serialized size rather than a count of contracts.
+    pub fn pending_contracts(
+        key: PublicKey,
+        mut contracts: Vec<(Signature, PendingContract)>,
+    ) -> Self {
+        let empty = Response::PendingContracts {
+            key,
+            contracts: vec![],
+        };
+        let mut size = serialized_size(&empty).expect("serialized_size in pending_contracts");
+        let count = contracts
+            .iter()
+            .take_while(|contract| {
+                size += serialized_size(contract).expect("serialized_size in pending_contracts");
+                size <= BLOB_DATA_SIZE as u64
+            })
+            .count();
+        contracts.truncate(count);
+        Response::PendingContracts { key, contracts }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bincode::serialize;
+    use budget::Budget;
+    use packet::{to_blob, BlobRecycler};
+    use payment_plan::Payment;
+    use signature::{KeyPair, KeyPairUtil};
+    use transaction::Plan;
+    use wire;
+
+    #[test]
+    fn test_pending_contracts_fit_in_blob() {
+        let key = KeyPair::new().pubkey();
+        let payment = Payment { tokens: 1, to: key };
+        // About the largest plan a transaction can carry.
+        let count = wire::MAX_TRANSACTION_SIZE / serialize(&payment).unwrap().len();
+        let contract = PendingContract {
+            from: key,
+            tokens: count as i64,
+            expires: None,
+            plan: Plan::Budget(Budget::Split(vec![payment; count])),
+        };
+        let contracts = vec![(Signature::default(), contract); 128];
+
+        let response = Response::pending_contracts(key, contracts.clone());
+        let len = match &response {
+            Response::PendingContracts { contracts, .. } => contracts.len(),
+            _ => panic!("expected PendingContracts"),
+        };
+        assert!(0 < len && len < contracts.len());
+        assert!(serialize(&response).unwrap().len() <= BLOB_DATA_SIZE);
+        let addr = "0.0.0.0:0".parse().unwrap();
+        to_blob(response, addr, &BlobRecycler::default()).unwrap();
+
+        match Response::pending_contracts(key, contracts[..len].to_vec()) {
+            Response::PendingContracts { contracts, .. } => assert_eq!(contracts.len(), len),
+            _ => panic!("expected PendingContracts"),
+        }
+    }
 }
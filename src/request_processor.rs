@@ -34,12 +34,48 @@ impl RequestProcessor {
                 info!("Response::LastId {:?}", rsp);
                 Some(rsp)
             }
+            Request::GetGenesis => {
+                let id = self.bank.genesis();
+                let rsp = (Response::Genesis { id }, rsp_addr);
+                info!("Response::Genesis {:?}", rsp);
+                Some(rsp)
+            }
             Request::GetTransactionCount => {
                 let transaction_count = self.bank.transaction_count() as u64;
                 let rsp = (Response::TransactionCount { transaction_count }, rsp_addr);
                 info!("Response::TransactionCount {:?}", rsp);
                 Some(rsp)
             }
+            Request::GetEntryFees { id } => {
+                let fees = self.bank.get_entry_fees(&id);
+                let rsp = (Response::EntryFees { id, fees }, rsp_addr);
+                info!("Response::EntryFees {:?}", rsp);
+                Some(rsp)
+            }
+            Request::GetSignatureStatus { sig } => {
+                let status = self.bank.get_signature_status(&sig);
+                let rsp = (Response::SignatureStatus { sig, status }, rsp_addr);
+                info!("Response::SignatureStatus {:?}", rsp);
+                Some(rsp)
+            }
+            Request::GetPendingContracts { key } => {
+                let contracts = self.bank.get_pending_contracts(&key);
+                let rsp = (Response::pending_contracts(key, contracts), rsp_addr);
+                info!("Response::PendingContracts {:?}", rsp);
+                Some(rsp)
+            }
+            Request::GetTickHeight => {
+                let tick_height = self.bank.tick_height();
+                let rsp = (Response::TickHeight { tick_height }, rsp_addr);
+                info!("Response::TickHeight {:?}", rsp);
+                Some(rsp)
+            }
+            Request::GetNonce { key } => {
+                let nonce = self.bank.get_nonce(&key);
+                let rsp = (Response::Nonce { key, nonce }, rsp_addr);
+                info!("Response::Nonce {:?}", rsp);
+                Some(rsp)
+            }
         }
     }
 
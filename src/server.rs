@@ -37,9 +37,11 @@ impl Server {
         let rpu = Rpu::new(bank.clone(), requests_socket, respond_socket, exit.clone());
         thread_hdls.extend(rpu.thread_hdls);
 
+        let crdt = Arc::new(RwLock::new(Crdt::new(me)));
         let blob_recycler = packet::BlobRecycler::default();
         let tpu = Tpu::new(
             bank.clone(),
+            crdt.clone(),
             tick_duration,
             transactions_socket,
             blob_recycler.clone(),
@@ -48,7 +50,6 @@ impl Server {
         );
         thread_hdls.extend(tpu.thread_hdls);
 
-        let crdt = Arc::new(RwLock::new(Crdt::new(me)));
         let window = streamer::default_window();
         let gossip_send_socket = UdpSocket::bind("0.0.0.0:0").expect("bind 0");
         let data_replicator = DataReplicator::new(
@@ -1,7 +1,6 @@
 //! The `signature` module provides functionality for public, and private keys.
 
 use generic_array::typenum::{U32, U64};
-use generic_array::GenericArray;
 use rand::{ChaChaRng, Rng, SeedableRng};
 use rayon::prelude::*;
 use ring::error::Unspecified;
@@ -12,8 +11,18 @@ use std::cell::RefCell;
 use untrusted;
 
 pub type KeyPair = Ed25519KeyPair;
-pub type PublicKey = GenericArray<u8, U32>;
-pub type Signature = GenericArray<u8, U64>;
+
+base58_bytes!(
+    /// An ED25519 public key, written in base58.
+    PublicKey,
+    U32
+);
+
+base58_bytes!(
+    /// An ED25519 signature, written in base58.
+    Signature,
+    U64
+);
 
 pub trait KeyPairUtil {
     fn new() -> Self;
@@ -32,7 +41,7 @@ impl KeyPairUtil for Ed25519KeyPair {
 
     /// Return the public key for the given keypair
     fn pubkey(&self) -> PublicKey {
-        GenericArray::clone_from_slice(self.public_key_bytes())
+        PublicKey::clone_from_slice(self.public_key_bytes())
     }
 }
 
@@ -40,15 +49,17 @@ pub trait SignatureUtil {
     fn verify(&self, peer_public_key_bytes: &[u8], msg_bytes: &[u8]) -> bool;
 }
 
-impl SignatureUtil for GenericArray<u8, U64> {
+impl SignatureUtil for Signature {
     fn verify(&self, peer_public_key_bytes: &[u8], msg_bytes: &[u8]) -> bool {
         let peer_public_key = untrusted::Input::from(peer_public_key_bytes);
         let msg = untrusted::Input::from(msg_bytes);
-        let sig = untrusted::Input::from(self);
+        let sig = untrusted::Input::from(self.as_ref());
         signature::verify(&signature::ED25519, peer_public_key, msg, sig).is_ok()
     }
 }
 
+/// Generates keypairs quickly from a seeded RNG, for benchmarks. Keys that
+/// need to be recreated from a backed-up seed come from `derivation` instead.
 pub struct GenKeys {
     // This is necessary because the rng needs to mutate its state to remain
     // deterministic, and the fill trait requires an immuatble reference to self
@@ -1,6 +1,5 @@
-use packet::{Packet, SharedPackets};
-use std::mem::size_of;
-use transaction::{PUB_KEY_OFFSET, SIGNED_DATA_OFFSET, SIG_OFFSET};
+use packet::{Packet, Packets, SharedPackets};
+use wire;
 
 pub const TX_OFFSET: usize = 0;
 
@@ -28,27 +27,33 @@ extern "C" {
 
 #[cfg(not(feature = "cuda"))]
 fn verify_packet(packet: &Packet) -> u8 {
+    verify_data(&packet.data[TX_OFFSET..packet.meta.size])
+}
+
+/// Check the signature of the sender and of every co-signer, which all
+/// sign the same data. The size and header are checked first, so oversized
+/// or truncated packets cost no signature verification. Reassembled
+/// transactions are always checked here, since they don't fit the fixed
+/// packet layout that the GPU verifier reads.
+fn verify_data(data: &[u8]) -> u8 {
     use ring::signature;
-    use signature::{PublicKey, Signature};
     use untrusted;
 
-    let msg_start = TX_OFFSET + SIGNED_DATA_OFFSET;
-    let sig_start = TX_OFFSET + SIG_OFFSET;
-    let sig_end = sig_start + size_of::<Signature>();
-    let pub_key_start = TX_OFFSET + PUB_KEY_OFFSET;
-    let pub_key_end = pub_key_start + size_of::<PublicKey>();
-
-    if packet.meta.size <= msg_start {
-        return 0;
-    }
-
-    let msg_end = packet.meta.size;
-    signature::verify(
-        &signature::ED25519,
-        untrusted::Input::from(&packet.data[pub_key_start..pub_key_end]),
-        untrusted::Input::from(&packet.data[msg_start..msg_end]),
-        untrusted::Input::from(&packet.data[sig_start..sig_end]),
-    ).is_ok() as u8
+    let (num_signers, signed_data) = match wire::signed_data_range(data) {
+        Ok(range) => range,
+        Err(_) => return 0,
+    };
+    let msg = &data[signed_data];
+    (0..num_signers).all(|i| {
+        let pub_key_start = wire::key_offset(num_signers, i);
+        let sig_start = wire::sig_offset(i);
+        signature::verify(
+            &signature::ED25519,
+            untrusted::Input::from(&data[pub_key_start..pub_key_start + wire::KEY_SIZE]),
+            untrusted::Input::from(msg),
+            untrusted::Input::from(&data[sig_start..sig_start + wire::SIG_SIZE]),
+        ).is_ok()
+    }) as u8
 }
 
 fn batch_size(batches: &Vec<SharedPackets>) -> usize {
@@ -65,19 +70,28 @@ pub fn ed25519_verify(batches: &Vec<SharedPackets>) -> Vec<Vec<u8>> {
     batches
         .into_par_iter()
         .map(|p| {
-            p.read()
-                .expect("'p' read lock in ed25519_verify")
-                .packets
-                .par_iter()
-                .map(verify_packet)
-                .collect()
+            let p = p.read().expect("'p' read lock in ed25519_verify");
+            let mut v: Vec<u8> = p.packets.par_iter().map(verify_packet).collect();
+            v.extend(verify_reassembled(&p));
+            v
         })
         .collect()
 }
 
+/// Verify the transactions reassembled from fragments, whose results follow
+/// those of the packets.
+fn verify_reassembled(p: &Packets) -> Vec<u8> {
+    use rayon::prelude::*;
+    p.reassembled
+        .par_iter()
+        .map(|(data, _)| verify_data(data))
+        .collect()
+}
+
 #[cfg(feature = "cuda")]
 pub fn ed25519_verify(batches: &Vec<SharedPackets>) -> Vec<Vec<u8>> {
     use packet::PACKET_DATA_SIZE;
+    use std::mem::size_of;
 
     info!("CUDA ECDSA for {}", batch_size(batches));
     let mut out = Vec::new();
@@ -92,8 +106,17 @@ pub fn ed25519_verify(batches: &Vec<SharedPackets>) -> Vec<Vec<u8>> {
                 .expect("'packets' read lock in pub fn ed25519_verify"),
         );
     }
+    // The GPU only checks the sender's signature at fixed offsets, so
+    // co-signed packets and malformed headers are rejected.
+    let mut rejected = Vec::new();
+    let mut reassembled = Vec::new();
     let mut num = 0;
     for p in locks {
+        reassembled.push(verify_reassembled(&p));
+        rejected.extend(p.packets.iter().map(|packet| {
+            let data = &packet.data[TX_OFFSET..packet.meta.size];
+            wire::signed_data_range(data).map(|(num_signers, _)| num_signers) != Ok(1)
+        }));
         elems.push(Elems {
             elems: p.packets.as_ptr(),
             num: p.packets.len() as u32,
@@ -107,18 +130,18 @@ pub fn ed25519_verify(batches: &Vec<SharedPackets>) -> Vec<Vec<u8>> {
     trace!("Starting verify num packets: {}", num);
     trace!("elem len: {}", elems.len() as u32);
     trace!("packet sizeof: {}", size_of::<Packet>() as u32);
-    trace!("pub key: {}", (TX_OFFSET + PUB_KEY_OFFSET) as u32);
-    trace!("sig offset: {}", (TX_OFFSET + SIG_OFFSET) as u32);
-    trace!("sign data: {}", (TX_OFFSET + SIGNED_DATA_OFFSET) as u32);
+    trace!("pub key: {}", (TX_OFFSET + wire::PUB_KEY_OFFSET) as u32);
+    trace!("sig offset: {}", (TX_OFFSET + wire::SIG_OFFSET) as u32);
+    trace!("sign data: {}", (TX_OFFSET + wire::SIGNED_DATA_OFFSET) as u32);
     trace!("len offset: {}", PACKET_DATA_SIZE as u32);
     unsafe {
         let res = ed25519_verify_many(
             elems.as_ptr(),
             elems.len() as u32,
             size_of::<Packet>() as u32,
-            (TX_OFFSET + PUB_KEY_OFFSET) as u32,
-            (TX_OFFSET + SIG_OFFSET) as u32,
-            (TX_OFFSET + SIGNED_DATA_OFFSET) as u32,
+            (TX_OFFSET + wire::PUB_KEY_OFFSET) as u32,
+            (TX_OFFSET + wire::SIG_OFFSET) as u32,
+            (TX_OFFSET + wire::SIGNED_DATA_OFFSET) as u32,
             PACKET_DATA_SIZE as u32,
             out.as_mut_ptr(),
         );
@@ -130,36 +153,39 @@ pub fn ed25519_verify(batches: &Vec<SharedPackets>) -> Vec<Vec<u8>> {
     let mut num = 0;
     for vs in rvs.iter_mut() {
         for mut v in vs.iter_mut() {
-            *v = out[num];
+            *v = if rejected[num] { 0 } else { out[num] };
             if *v != 0 {
                 trace!("VERIFIED PACKET!!!!!");
             }
             num += 1;
         }
     }
+    for (vs, reassembled) in rvs.iter_mut().zip(reassembled) {
+        vs.extend(reassembled);
+    }
     rvs
 }
 
 #[cfg(test)]
 mod tests {
-    use bincode::serialize;
     use packet::{Packet, Packets, SharedPackets};
     use sigverify;
     use std::sync::RwLock;
     use transaction::Transaction;
     use transaction::{memfind, test_tx};
+    use wire;
 
     #[test]
     fn test_layout() {
         let tx = test_tx();
-        let tx_bytes = serialize(&tx).unwrap();
-        let packet = serialize(&tx).unwrap();
+        let tx_bytes = wire::encode(&tx);
+        let packet = wire::encode(&tx);
         assert_matches!(memfind(&packet, &tx_bytes), Some(sigverify::TX_OFFSET));
         assert_matches!(memfind(&packet, &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9]), None);
     }
 
     fn make_packet_from_transaction(tx: Transaction) -> Packet {
-        let tx_bytes = serialize(&tx).unwrap();
+        let tx_bytes = wire::encode(&tx);
         let mut packet = Packet::default();
         packet.meta.size = tx_bytes.len();
         packet.data[..packet.meta.size].copy_from_slice(&tx_bytes);
@@ -211,4 +237,58 @@ mod tests {
     fn test_verify_fail() {
         test_verify_n(5, true);
     }
+
+    #[test]
+    fn test_verify_reassembled() {
+        use hash::Hash;
+        use signature::{KeyPair, KeyPairUtil, Signature};
+        use transaction::Instruction;
+
+        let keypair = KeyPair::new();
+        let witness = Instruction::ApplySignature(Signature::default());
+        let zero = Hash::default();
+        let tx = Transaction::new_batch(&keypair, vec![witness; 64], zero, zero, 0);
+        let data = wire::encode(&tx);
+        let mut forged = data.clone();
+        let last = forged.len() - 1;
+        forged[last] ^= 1;
+
+        let addr = "127.0.0.1:1234".parse().unwrap();
+        let mut packets = Packets::default();
+        packets.packets = vec![make_packet_from_transaction(test_tx())];
+        packets.reassembled = vec![(data, addr), (forged, addr)];
+        let batches = vec![SharedPackets::new(RwLock::new(packets))];
+
+        // Their results follow those of the packets.
+        assert_eq!(sigverify::ed25519_verify(&batches), vec![vec![1, 1, 0]]);
+    }
+
+    #[cfg(not(feature = "cuda"))]
+    #[test]
+    fn test_verify_cosigned() {
+        use hash::Hash;
+        use signature::{KeyPair, KeyPairUtil};
+
+        let keypair0 = KeyPair::new();
+        let keypair1 = KeyPair::new();
+        let keypair2 = KeyPair::new();
+        let cosigners = [(&keypair1, 1), (&keypair2, 2)];
+        let to = keypair0.pubkey();
+        let zero = Hash::default();
+        let tx = Transaction::new_joint(&keypair0, &cosigners, to, 42, zero, zero);
+        let tx_bytes = wire::encode(&tx);
+        assert_eq!(sigverify::verify_data(&tx_bytes), 1);
+
+        let mut unsigned = tx.clone();
+        unsigned.cosigs.pop();
+        assert_eq!(sigverify::verify_data(&wire::encode(&unsigned)), 0);
+
+        let mut forged = tx.clone();
+        forged.cosigs.swap(0, 1); // <-- attack!
+        assert_eq!(sigverify::verify_data(&wire::encode(&forged)), 0);
+
+        let mut signers = tx_bytes.clone();
+        signers[wire::NUM_SIGNERS_OFFSET] = 2;
+        assert_eq!(sigverify::verify_data(&signers), 0);
+    }
 }
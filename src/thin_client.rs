@@ -3,14 +3,19 @@
 //! messages to the network directly. The binary encoding of its messages are
 //! unstable and may change in future releases.
 
+use bank::{PendingContract, SignatureStatus};
 use bincode::{deserialize, serialize};
 use hash::Hash;
+use packet::BLOB_SIZE;
 use request::{Request, Response};
 use signature::{KeyPair, PublicKey, Signature};
 use std::collections::HashMap;
 use std::io;
 use std::net::{SocketAddr, UdpSocket};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
 use transaction::Transaction;
+use wire;
 
 pub struct ThinClient {
     requests_addr: SocketAddr,
@@ -18,8 +23,14 @@ pub struct ThinClient {
     transactions_addr: SocketAddr,
     transactions_socket: UdpSocket,
     last_id: Option<Hash>,
+    genesis: Option<Hash>,
     transaction_count: u64,
+    tick_height: u64,
     balances: HashMap<PublicKey, Option<i64>>,
+    entry_fees: HashMap<Hash, Option<i64>>,
+    nonces: HashMap<PublicKey, Option<Hash>>,
+    signature_statuses: HashMap<Signature, SignatureStatus>,
+    pending_contracts: HashMap<PublicKey, Vec<(Signature, PendingContract)>>,
 }
 
 impl ThinClient {
@@ -38,14 +49,41 @@ impl ThinClient {
             transactions_addr,
             transactions_socket,
             last_id: None,
+            genesis: None,
             transaction_count: 0,
+            tick_height: 0,
             balances: HashMap::new(),
+            entry_fees: HashMap::new(),
+            nonces: HashMap::new(),
+            signature_statuses: HashMap::new(),
+            pending_contracts: HashMap::new(),
         };
         client
     }
 
+    /// Create a new ThinClient that sends transactions from `addr` and
+    /// requests from the port after it. Responses are awaited for at most
+    /// five seconds, so that a lost one can't block the caller forever.
+    pub fn bind(
+        mut addr: SocketAddr,
+        requests_addr: SocketAddr,
+        transactions_addr: SocketAddr,
+    ) -> io::Result<Self> {
+        let port = addr.port();
+        let transactions_socket = UdpSocket::bind(addr)?;
+        addr.set_port(port + 1);
+        let requests_socket = UdpSocket::bind(addr)?;
+        requests_socket.set_read_timeout(Some(Duration::new(5, 0)))?;
+        Ok(Self::new(
+            requests_addr,
+            requests_socket,
+            transactions_addr,
+            transactions_socket,
+        ))
+    }
+
     pub fn recv_response(&self) -> io::Result<Response> {
-        let mut buf = vec![0u8; 1024];
+        let mut buf = vec![0u8; BLOB_SIZE];
         trace!("start recv_from");
         self.requests_socket.recv_from(&mut buf)?;
         trace!("end recv_from");
@@ -63,19 +101,51 @@ impl ThinClient {
                 info!("Response last_id {:?}", id);
                 self.last_id = Some(id);
             }
+            Response::Genesis { id } => {
+                info!("Response genesis {:?}", id);
+                self.genesis = Some(id);
+            }
             Response::TransactionCount { transaction_count } => {
                 info!("Response transaction count {:?}", transaction_count);
                 self.transaction_count = transaction_count;
             }
+            Response::EntryFees { id, fees } => {
+                trace!("Response entry fees {:?} {:?}", id, fees);
+                self.entry_fees.insert(id, fees);
+            }
+            Response::SignatureStatus { sig, status } => {
+                trace!("Response signature status {:?} {:?}", sig, status);
+                self.signature_statuses.insert(sig, status);
+            }
+            Response::PendingContracts { key, contracts } => {
+                trace!("Response pending contracts {:?} {:?}", key, contracts);
+                self.pending_contracts.insert(key, contracts);
+            }
+            Response::TickHeight { tick_height } => {
+                trace!("Response tick height {:?}", tick_height);
+                self.tick_height = tick_height;
+            }
+            Response::Nonce { key, nonce } => {
+                trace!("Response nonce {:?} {:?}", key, nonce);
+                self.nonces.insert(key, nonce);
+            }
         }
     }
 
     /// Send a signed Transaction to the server for processing. This method
-    /// does not wait for a response.
+    /// does not wait for a response. Transactions that don't fit in a single
+    /// packet are sent in fragments, and those longer than
+    /// `wire::MAX_TRANSACTION_SIZE` are rejected without being sent.
     pub fn transfer_signed(&self, tx: Transaction) -> io::Result<usize> {
-        let data = serialize(&tx).expect("serialize Transaction in pub fn transfer_signed");
-        self.transactions_socket
-            .send_to(&data, &self.transactions_addr)
+        let datagrams = wire::to_datagrams(&wire::encode(&tx)).map_err(|_| {
+            io::Error::new(io::ErrorKind::InvalidInput, "transaction too large")
+        })?;
+        let mut sent = 0;
+        for datagram in datagrams {
+            sent += self.transactions_socket
+                .send_to(&datagram, &self.transactions_addr)?;
+        }
+        Ok(sent)
     }
 
     /// Creates, signs, and processes a Transaction. Useful for writing unit-tests.
@@ -84,9 +154,10 @@ impl ThinClient {
         n: i64,
         keypair: &KeyPair,
         to: PublicKey,
+        genesis: &Hash,
         last_id: &Hash,
     ) -> io::Result<Signature> {
-        let tx = Transaction::new(keypair, to, n, *last_id);
+        let tx = Transaction::new(keypair, to, n, *genesis, *last_id);
         let sig = tx.sig;
         self.transfer_signed(tx).map(|_| sig)
     }
@@ -135,6 +206,27 @@ impl ThinClient {
         self.transaction_count
     }
 
+    /// Request the number of ticks the server has registered, the height
+    /// that `Condition::TickHeight` is checked against.
+    pub fn get_tick_height(&mut self) -> io::Result<u64> {
+        trace!("get_tick_height");
+        let req = Request::GetTickHeight;
+        let data = serialize(&req).expect("serialize GetTickHeight in pub fn get_tick_height");
+        self.requests_socket
+            .send_to(&data, &self.requests_addr)
+            .expect("buffer error in pub fn get_tick_height");
+        let mut done = false;
+        while !done {
+            let resp = self.recv_response()?;
+            trace!("recv_response {:?}", resp);
+            if let Response::TickHeight { .. } = &resp {
+                done = true;
+            }
+            self.process_response(resp);
+        }
+        Ok(self.tick_height)
+    }
+
     /// Request the last Entry ID from the server. This method blocks
     /// until the server sends a response.
     pub fn get_last_id(&mut self) -> Hash {
@@ -155,6 +247,167 @@ impl ThinClient {
         self.last_id.expect("some last_id")
     }
 
+    /// Request the ID of the first entry of the server's ledger, which every
+    /// transaction must be signed for. The ID never changes, so it's only
+    /// requested once.
+    pub fn get_genesis(&mut self) -> Hash {
+        if let Some(genesis) = self.genesis {
+            return genesis;
+        }
+        info!("get_genesis");
+        let req = Request::GetGenesis;
+        let data = serialize(&req).expect("serialize GetGenesis in pub fn get_genesis");
+        self.requests_socket
+            .send_to(&data, &self.requests_addr)
+            .expect("buffer error in pub fn get_genesis");
+        let mut done = false;
+        while !done {
+            let resp = self.recv_response().expect("get_genesis response");
+            if let &Response::Genesis { .. } = &resp {
+                done = true;
+            }
+            self.process_response(resp);
+        }
+        self.genesis.expect("some genesis")
+    }
+
+    /// Request the total fees paid by the transactions in the Entry with ID
+    /// `id`. This method blocks until the server sends a response.
+    pub fn get_entry_fees(&mut self, id: &Hash) -> io::Result<i64> {
+        trace!("get_entry_fees");
+        let req = Request::GetEntryFees { id: *id };
+        let data = serialize(&req).expect("serialize GetEntryFees in pub fn get_entry_fees");
+        self.requests_socket
+            .send_to(&data, &self.requests_addr)
+            .expect("buffer error in pub fn get_entry_fees");
+        let mut done = false;
+        while !done {
+            let resp = self.recv_response()?;
+            trace!("recv_response {:?}", resp);
+            if let Response::EntryFees { id: resp_id, .. } = &resp {
+                done = resp_id == id;
+            }
+            self.process_response(resp);
+        }
+        self.entry_fees[id].ok_or(io::Error::new(io::ErrorKind::Other, "noentry"))
+    }
+
+    /// Request the current nonce of `pubkey`, which a transaction may use in
+    /// place of a recent `last_id`. This method blocks until the server sends
+    /// a response.
+    pub fn get_nonce(&mut self, pubkey: &PublicKey) -> io::Result<Hash> {
+        trace!("get_nonce");
+        let req = Request::GetNonce { key: *pubkey };
+        let data = serialize(&req).expect("serialize GetNonce in pub fn get_nonce");
+        self.requests_socket
+            .send_to(&data, &self.requests_addr)
+            .expect("buffer error in pub fn get_nonce");
+        let mut done = false;
+        while !done {
+            let resp = self.recv_response()?;
+            trace!("recv_response {:?}", resp);
+            if let Response::Nonce { key, .. } = &resp {
+                done = key == pubkey;
+            }
+            self.process_response(resp);
+        }
+        self.nonces[pubkey].ok_or(io::Error::new(io::ErrorKind::Other, "nononce"))
+    }
+
+    /// Request the status of the transaction with signature `sig`. This method
+    /// blocks until the server sends a response.
+    pub fn get_signature_status(&mut self, sig: &Signature) -> io::Result<SignatureStatus> {
+        trace!("get_signature_status");
+        let req = Request::GetSignatureStatus { sig: *sig };
+        let data =
+            serialize(&req).expect("serialize GetSignatureStatus in pub fn get_signature_status");
+        self.requests_socket
+            .send_to(&data, &self.requests_addr)
+            .expect("buffer error in pub fn get_signature_status");
+        let mut done = false;
+        while !done {
+            let resp = self.recv_response()?;
+            trace!("recv_response {:?}", resp);
+            if let Response::SignatureStatus { sig: resp_sig, .. } = &resp {
+                done = resp_sig == sig;
+            }
+            self.process_response(resp);
+        }
+        Ok(self.signature_statuses[sig].clone())
+    }
+
+    /// Request the contracts waiting on witnesses that involve `key`. Only as
+    /// many as fit in one response are returned.
+    pub fn get_pending_contracts(
+        &mut self,
+        key: &PublicKey,
+    ) -> io::Result<Vec<(Signature, PendingContract)>> {
+        trace!("get_pending_contracts");
+        let req = Request::GetPendingContracts { key: *key };
+        let data =
+            serialize(&req).expect("serialize GetPendingContracts in pub fn get_pending_contracts");
+        self.requests_socket
+            .send_to(&data, &self.requests_addr)
+            .expect("buffer error in pub fn get_pending_contracts");
+        let mut done = false;
+        while !done {
+            let resp = self.recv_response()?;
+            trace!("recv_response {:?}", resp);
+            if let Response::PendingContracts { key: resp_key, .. } = &resp {
+                done = resp_key == key;
+            }
+            self.process_response(resp);
+        }
+        Ok(self.pending_contracts[key].clone())
+    }
+
+    /// Poll the status of the transaction with signature `sig` until the server
+    /// has processed it, and return the outcome. Fails with `TimedOut` if the
+    /// transaction isn't found within `timeout`, even if the server stops
+    /// responding.
+    pub fn confirm_transaction(
+        &mut self,
+        sig: &Signature,
+        timeout: Duration,
+    ) -> io::Result<SignatureStatus> {
+        let read_timeout = self.requests_socket.read_timeout()?;
+        let status = self.poll_signature_status(sig, Instant::now() + timeout);
+        self.requests_socket.set_read_timeout(read_timeout)?;
+        status
+    }
+
+    /// Request the status of `sig` until it's found or `deadline` passes. The
+    /// read timeout of `requests_socket` is set to the time that's left, so a
+    /// lost response doesn't block past the deadline.
+    fn poll_signature_status(
+        &mut self,
+        sig: &Signature,
+        deadline: Instant,
+    ) -> io::Result<SignatureStatus> {
+        loop {
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "transaction not confirmed",
+                ));
+            }
+            self.requests_socket.set_read_timeout(Some(deadline - now))?;
+            match self.get_signature_status(sig) {
+                Ok(SignatureStatus::NotFound) => {}
+                Ok(status) => return Ok(status),
+                Err(ref e)
+                    if e.kind() == io::ErrorKind::WouldBlock
+                        || e.kind() == io::ErrorKind::TimedOut => {}
+                Err(e) => return Err(e),
+            }
+            let now = Instant::now();
+            if now < deadline {
+                sleep(Duration::from_millis(100).min(deadline - now));
+            }
+        }
+    }
+
     pub fn poll_get_balance(&mut self, pubkey: &PublicKey) -> io::Result<i64> {
         use std::time::Instant;
 
@@ -179,6 +432,8 @@ mod tests {
     use crdt::TestNode;
     use logger;
     use mint::Mint;
+    use packet::PACKET_DATA_SIZE;
+    use payment_plan::Payment;
     use server::Server;
     use signature::{KeyPair, KeyPairUtil};
     use std::io::sink;
@@ -221,18 +476,47 @@ mod tests {
             leader.data.transactions_addr,
             transactions_socket,
         );
+        let genesis = client.get_genesis();
+        assert_eq!(genesis, alice.seed());
         let last_id = client.get_last_id();
-        let _sig = client
-            .transfer(500, &alice.keypair(), bob_pubkey, &last_id)
+        let sig = client
+            .transfer(500, &alice.keypair(), bob_pubkey, &genesis, &last_id)
             .unwrap();
         let balance = client.poll_get_balance(&bob_pubkey);
         assert_eq!(balance.unwrap(), 500);
+        assert_eq!(
+            client.confirm_transaction(&sig, Duration::from_secs(1)).unwrap(),
+            SignatureStatus::Confirmed
+        );
+        assert_eq!(client.get_pending_contracts(&bob_pubkey).unwrap(), vec![]);
         exit.store(true, Ordering::Relaxed);
         for t in server.thread_hdls {
             t.join().unwrap();
         }
     }
 
+    #[test]
+    fn test_confirm_transaction_timeout() {
+        // Nothing answers on `silent`, so every request goes unanswered.
+        let silent = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let requests_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let transactions_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let mut client = ThinClient::new(
+            silent.local_addr().unwrap(),
+            requests_socket,
+            silent.local_addr().unwrap(),
+            transactions_socket,
+        );
+        let sig = Signature::default();
+        let now = Instant::now();
+        let err = client
+            .confirm_transaction(&sig, Duration::from_millis(300))
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert!(now.elapsed() < Duration::from_secs(2));
+        assert_eq!(client.requests_socket.read_timeout().unwrap(), None);
+    }
+
     #[test]
     fn test_bad_sig() {
         logger::setup();
@@ -267,15 +551,16 @@ mod tests {
             leader.data.transactions_addr,
             transactions_socket,
         );
+        let genesis = client.get_genesis();
         let last_id = client.get_last_id();
 
-        let tx = Transaction::new(&alice.keypair(), bob_pubkey, 500, last_id);
+        let tx = Transaction::new(&alice.keypair(), bob_pubkey, 500, genesis, last_id);
 
         let _sig = client.transfer_signed(tx).unwrap();
 
         let last_id = client.get_last_id();
 
-        let mut tr2 = Transaction::new(&alice.keypair(), bob_pubkey, 501, last_id);
+        let mut tr2 = Transaction::new(&alice.keypair(), bob_pubkey, 501, genesis, last_id);
         if let Instruction::NewContract(contract) = &mut tr2.instruction {
             contract.tokens = 502;
             contract.plan = Plan::Budget(Budget::new_payment(502, bob_pubkey));
@@ -289,4 +574,84 @@ mod tests {
             t.join().unwrap();
         }
     }
+
+    #[test]
+    fn test_large_transaction() {
+        logger::setup();
+        let leader = TestNode::new();
+        let alice = Mint::new(10_000);
+        let bank = Bank::new(&alice);
+        let bob_keypair = KeyPair::new();
+        let carol_pubkey = KeyPair::new().pubkey();
+        let exit = Arc::new(AtomicBool::new(false));
+
+        let server = Server::new_leader(
+            bank,
+            Some(Duration::from_millis(30)),
+            leader.data.clone(),
+            leader.sockets.requests,
+            leader.sockets.transaction,
+            leader.sockets.broadcast,
+            leader.sockets.respond,
+            leader.sockets.gossip,
+            exit.clone(),
+            sink(),
+        );
+        sleep(Duration::from_millis(300));
+
+        let requests_socket = UdpSocket::bind("0.0.0.0:0").unwrap();
+        let transactions_socket = UdpSocket::bind("0.0.0.0:0").unwrap();
+        let mut client = ThinClient::new(
+            leader.data.requests_addr,
+            requests_socket,
+            leader.data.transactions_addr,
+            transactions_socket,
+        );
+        let genesis = client.get_genesis();
+        let last_id = client.get_last_id();
+        let bob_pubkey = bob_keypair.pubkey();
+        let _sig = client
+            .transfer(500, &alice.keypair(), bob_pubkey, &genesis, &last_id)
+            .unwrap();
+        let balance = client.poll_get_balance(&bob_pubkey);
+        assert_eq!(balance.unwrap(), 500);
+
+        // A co-signed transaction needs more than 256 bytes.
+        let cosigners = [(&bob_keypair, 200)];
+        let tx = Transaction::new_joint(
+            &alice.keypair(),
+            &cosigners,
+            carol_pubkey,
+            300,
+            genesis,
+            last_id,
+        );
+        assert!(wire::encode(&tx).len() > 256);
+        client.transfer_signed(tx).unwrap();
+        let balance = client.poll_get_balance(&carol_pubkey);
+        assert_eq!(balance.unwrap(), 300);
+
+        // Too large for a packet, so it's sent in fragments.
+        let payments = (0..40)
+            .map(|_| Payment {
+                tokens: 1,
+                to: KeyPair::new().pubkey(),
+            })
+            .collect::<Vec<_>>();
+        let budget = Budget::new_split_payment(payments.clone());
+        let tx = Transaction::new_budget(&alice.keypair(), budget, 40, genesis, last_id);
+        assert!(wire::encode(&tx).len() > PACKET_DATA_SIZE);
+        client.transfer_signed(tx).unwrap();
+        let balance = client.poll_get_balance(&payments[39].to);
+        assert_eq!(balance.unwrap(), 1);
+
+        let witness = Instruction::ApplySignature(Signature::default());
+        let tx = Transaction::new_batch(&alice.keypair(), vec![witness; 256], genesis, last_id, 0);
+        assert!(client.transfer_signed(tx).is_err());
+
+        exit.store(true, Ordering::Relaxed);
+        for t in server.thread_hdls {
+            t.join().unwrap();
+        }
+    }
 }
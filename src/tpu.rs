@@ -3,6 +3,7 @@
 
 use bank::Bank;
 use banking_stage::BankingStage;
+use crdt::Crdt;
 use fetch_stage::FetchStage;
 use packet::{BlobRecycler, PacketRecycler};
 use record_stage::RecordStage;
@@ -10,7 +11,7 @@ use sigverify_stage::SigVerifyStage;
 use std::io::Write;
 use std::net::UdpSocket;
 use std::sync::atomic::AtomicBool;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, RwLock};
 use std::thread::JoinHandle;
 use std::time::Duration;
 use streamer::BlobReceiver;
@@ -24,6 +25,7 @@ pub struct Tpu {
 impl Tpu {
     pub fn new<W: Write + Send + 'static>(
         bank: Arc<Bank>,
+        crdt: Arc<RwLock<Crdt>>,
         tick_duration: Option<Duration>,
         transactions_socket: UdpSocket,
         blob_recycler: BlobRecycler,
@@ -55,17 +57,18 @@ impl Tpu {
 
         let write_stage = WriteStage::new(
             bank.clone(),
+            crdt,
             exit.clone(),
             blob_recycler.clone(),
             Mutex::new(writer),
             record_stage.entry_receiver,
         );
         let mut thread_hdls = vec![
-            fetch_stage.thread_hdl,
             banking_stage.thread_hdl,
             record_stage.thread_hdl,
             write_stage.thread_hdl,
         ];
+        thread_hdls.extend(fetch_stage.thread_hdls.into_iter());
         thread_hdls.extend(sigverify_stage.thread_hdls.into_iter());
         Tpu {
             blob_receiver: write_stage.blob_receiver,
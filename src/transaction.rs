@@ -1,19 +1,17 @@
 //! The `transaction` module provides functionality for creating log transactions.
 
-use bincode::serialize;
 use budget::{Budget, Condition};
 use chrono::prelude::*;
 use hash::Hash;
 use payment_plan::{Payment, PaymentPlan, Witness};
 use signature::{KeyPair, KeyPairUtil, PublicKey, Signature, SignatureUtil};
-
-pub const SIGNED_DATA_OFFSET: usize = 112;
-pub const SIG_OFFSET: usize = 8;
-pub const PUB_KEY_OFFSET: usize = 80;
+use vesting::Vesting;
+use wire;
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
 pub enum Plan {
     Budget(Budget),
+    Vesting(Vesting),
 }
 
 // A proxy for the underlying DSL.
@@ -21,18 +19,56 @@ impl PaymentPlan for Plan {
     fn final_payment(&self) -> Option<Payment> {
         match self {
             Plan::Budget(budget) => budget.final_payment(),
+            Plan::Vesting(vesting) => vesting.final_payment(),
+        }
+    }
+
+    fn final_payments(&self) -> Option<Vec<Payment>> {
+        match self {
+            Plan::Budget(budget) => budget.final_payments(),
+            Plan::Vesting(vesting) => vesting.final_payments(),
         }
     }
 
     fn verify(&self, spendable_tokens: i64) -> bool {
         match self {
             Plan::Budget(budget) => budget.verify(spendable_tokens),
+            Plan::Vesting(vesting) => vesting.verify(spendable_tokens),
+        }
+    }
+
+    fn is_duplicate_witness(&self, witness: &Witness) -> bool {
+        match self {
+            Plan::Budget(budget) => budget.is_duplicate_witness(witness),
+            Plan::Vesting(vesting) => vesting.is_duplicate_witness(witness),
         }
     }
 
     fn apply_witness(&mut self, witness: &Witness) {
         match self {
             Plan::Budget(budget) => budget.apply_witness(witness),
+            Plan::Vesting(vesting) => vesting.apply_witness(witness),
+        }
+    }
+
+    fn next_deadline(&self) -> Option<DateTime<Utc>> {
+        match self {
+            Plan::Budget(budget) => budget.next_deadline(),
+            Plan::Vesting(vesting) => vesting.next_deadline(),
+        }
+    }
+
+    fn involves(&self, pubkey: &PublicKey) -> bool {
+        match self {
+            Plan::Budget(budget) => budget.involves(pubkey),
+            Plan::Vesting(vesting) => vesting.involves(pubkey),
+        }
+    }
+
+    fn take_due_payments(&mut self) -> Vec<Payment> {
+        match self {
+            Plan::Budget(budget) => budget.take_due_payments(),
+            Plan::Vesting(vesting) => vesting.take_due_payments(),
         }
     }
 }
@@ -41,6 +77,9 @@ impl PaymentPlan for Plan {
 pub struct Contract {
     pub tokens: i64,
     pub plan: Plan,
+    /// If the plan hasn't paid out by this time, the tokens it still holds
+    /// are refunded to the sender.
+    pub expires: Option<DateTime<Utc>>,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
@@ -48,13 +87,49 @@ pub enum Instruction {
     NewContract(Contract),
     ApplyTimestamp(DateTime<Utc>),
     ApplySignature(Signature),
+    ApplyPreimage(Signature, Vec<u8>),
+
+    /// Witness that the ledger has reached a tick height, on behalf of the
+    /// contract with the given signature. Anyone may send it, since the bank
+    /// checks the height against its own.
+    ApplyTickHeight(Signature, u64),
+
+    /// A time source's vote to replace the set of trusted time sources.
+    SetTimeSources(Vec<PublicKey>),
+
+    /// Instructions applied in order, either all of them or none of them.
+    Batch(Vec<Instruction>),
+
+    /// Replace the sender's nonce with a new one, creating it if need be.
+    /// Transactions signed with the old nonce as their `last_id` can no
+    /// longer be processed.
+    AdvanceNonce,
+
+    /// The mint's choice of the account that collects fees. Only valid in
+    /// the genesis entry, so every node replaying the ledger agrees on it.
+    SetFeeCollector(PublicKey),
+}
+
+/// An account that signs a transaction along with its sender, and the tokens
+/// it contributes to the contract the transaction creates.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct Cosigner {
+    pub key: PublicKey,
+    pub tokens: i64,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
 pub struct Transaction {
     pub sig: Signature,
     pub from: PublicKey,
+    /// The signatures of the co-signers, in the same order.
+    pub cosigs: Vec<Signature>,
+    pub cosigners: Vec<Cosigner>,
     pub instruction: Instruction,
+    /// The ID of the first entry of the ledger the transaction is meant for.
+    pub genesis: Hash,
+    /// A recent entry ID, or the sender's nonce for transactions that must
+    /// stay valid for longer than the bank remembers entry IDs.
     pub last_id: Hash,
     pub fee: i64,
 }
@@ -63,13 +138,17 @@ impl Transaction {
     fn new_from_instruction(
         from_keypair: &KeyPair,
         instruction: Instruction,
+        genesis: Hash,
         last_id: Hash,
         fee: i64,
     ) -> Self {
         let from = from_keypair.pubkey();
         let mut tx = Transaction {
             sig: Signature::default(),
+            cosigs: vec![],
+            cosigners: vec![],
             instruction,
+            genesis,
             last_id,
             from,
             fee,
@@ -78,12 +157,45 @@ impl Transaction {
         tx
     }
 
+    /// Create a Transaction signed by the sender and every co-signer.
+    fn new_cosigned(
+        from_keypair: &KeyPair,
+        cosigner_keypairs: &[(&KeyPair, i64)],
+        instruction: Instruction,
+        genesis: Hash,
+        last_id: Hash,
+        fee: i64,
+    ) -> Self {
+        let mut tx = Transaction {
+            sig: Signature::default(),
+            from: from_keypair.pubkey(),
+            cosigs: vec![],
+            cosigners: cosigner_keypairs
+                .iter()
+                .map(|(keypair, tokens)| Cosigner {
+                    key: keypair.pubkey(),
+                    tokens: *tokens,
+                })
+                .collect(),
+            instruction,
+            genesis,
+            last_id,
+            fee,
+        };
+        tx.sign(from_keypair);
+        for (keypair, _) in cosigner_keypairs {
+            tx.cosign(keypair);
+        }
+        tx
+    }
+
     /// Create and sign a new Transaction. Used for unit-testing.
     pub fn new_taxed(
         from_keypair: &KeyPair,
         to: PublicKey,
         tokens: i64,
         fee: i64,
+        genesis: Hash,
         last_id: Hash,
     ) -> Self {
         let payment = Payment {
@@ -92,25 +204,246 @@ impl Transaction {
         };
         let budget = Budget::Pay(payment);
         let plan = Plan::Budget(budget);
-        let instruction = Instruction::NewContract(Contract { plan, tokens });
-        Self::new_from_instruction(from_keypair, instruction, last_id, fee)
+        let instruction = Instruction::NewContract(Contract {
+            plan,
+            tokens,
+            expires: None,
+        });
+        Self::new_from_instruction(from_keypair, instruction, genesis, last_id, fee)
     }
 
     /// Create and sign a new Transaction. Used for unit-testing.
-    pub fn new(from_keypair: &KeyPair, to: PublicKey, tokens: i64, last_id: Hash) -> Self {
-        Self::new_taxed(from_keypair, to, tokens, 0, last_id)
+    pub fn new(
+        from_keypair: &KeyPair,
+        to: PublicKey,
+        tokens: i64,
+        genesis: Hash,
+        last_id: Hash,
+    ) -> Self {
+        Self::new_taxed(from_keypair, to, tokens, 0, genesis, last_id)
+    }
+
+    /// Create and sign a Transaction that pays `tokens` to `to`. Each
+    /// co-signer contributes the given number of tokens, and the sender
+    /// contributes the rest.
+    pub fn new_joint(
+        from_keypair: &KeyPair,
+        cosigner_keypairs: &[(&KeyPair, i64)],
+        to: PublicKey,
+        tokens: i64,
+        genesis: Hash,
+        last_id: Hash,
+    ) -> Self {
+        let instruction = Instruction::NewContract(Contract {
+            plan: Plan::Budget(Budget::new_payment(tokens, to)),
+            tokens,
+            expires: None,
+        });
+        Self::new_cosigned(from_keypair, cosigner_keypairs, instruction, genesis, last_id, 0)
+    }
+
+    /// Create and sign a Transaction that pays `tokens` to `to`, and whose
+    /// fee is paid by `payer_keypair` rather than the sender.
+    pub fn new_with_fee_payer(
+        from_keypair: &KeyPair,
+        payer_keypair: &KeyPair,
+        to: PublicKey,
+        tokens: i64,
+        fee: i64,
+        genesis: Hash,
+        last_id: Hash,
+    ) -> Self {
+        let instruction = Instruction::NewContract(Contract {
+            plan: Plan::Budget(Budget::new_payment(tokens, to)),
+            tokens: tokens + fee,
+            expires: None,
+        });
+        let cosigners = [(payer_keypair, fee)];
+        Self::new_cosigned(from_keypair, &cosigners, instruction, genesis, last_id, fee)
+    }
+
+    /// Create and sign a Transaction that splits its tokens across `payments`.
+    pub fn new_split(
+        from_keypair: &KeyPair,
+        payments: Vec<Payment>,
+        genesis: Hash,
+        last_id: Hash,
+    ) -> Self {
+        let tokens = payments.iter().map(|payment| payment.tokens).sum();
+        let budget = Budget::Split(payments);
+        let plan = Plan::Budget(budget);
+        let instruction = Instruction::NewContract(Contract {
+            plan,
+            tokens,
+            expires: None,
+        });
+        Self::new_from_instruction(from_keypair, instruction, genesis, last_id, 0)
+    }
+
+    /// Create and sign a Transaction that locks `tokens` in `budget`.
+    pub fn new_budget(
+        from_keypair: &KeyPair,
+        budget: Budget,
+        tokens: i64,
+        genesis: Hash,
+        last_id: Hash,
+    ) -> Self {
+        Self::new_expiring_budget(from_keypair, budget, tokens, None, genesis, last_id)
+    }
+
+    /// Create and sign a Transaction that locks `tokens` in `budget`, and
+    /// refunds whatever is left to the sender at `expires`.
+    pub fn new_expiring_budget(
+        from_keypair: &KeyPair,
+        budget: Budget,
+        tokens: i64,
+        expires: Option<DateTime<Utc>>,
+        genesis: Hash,
+        last_id: Hash,
+    ) -> Self {
+        let plan = Plan::Budget(budget);
+        let instruction = Instruction::NewContract(Contract {
+            plan,
+            tokens,
+            expires,
+        });
+        Self::new_from_instruction(from_keypair, instruction, genesis, last_id, 0)
+    }
+
+    /// Create and sign a Transaction that releases its tokens on the dates in
+    /// `vesting`'s schedule.
+    pub fn new_vesting(
+        from_keypair: &KeyPair,
+        vesting: Vesting,
+        genesis: Hash,
+        last_id: Hash,
+    ) -> Self {
+        let tokens = vesting.tokens().unwrap_or(0);
+        let plan = Plan::Vesting(vesting);
+        let instruction = Instruction::NewContract(Contract {
+            plan,
+            tokens,
+            expires: None,
+        });
+        Self::new_from_instruction(from_keypair, instruction, genesis, last_id, 0)
     }
 
     /// Create and sign a new Witness Timestamp. Used for unit-testing.
-    pub fn new_timestamp(from_keypair: &KeyPair, dt: DateTime<Utc>, last_id: Hash) -> Self {
+    pub fn new_timestamp(
+        from_keypair: &KeyPair,
+        dt: DateTime<Utc>,
+        genesis: Hash,
+        last_id: Hash,
+    ) -> Self {
         let instruction = Instruction::ApplyTimestamp(dt);
-        Self::new_from_instruction(from_keypair, instruction, last_id, 0)
+        Self::new_from_instruction(from_keypair, instruction, genesis, last_id, 0)
     }
 
     /// Create and sign a new Witness Signature. Used for unit-testing.
-    pub fn new_signature(from_keypair: &KeyPair, tx_sig: Signature, last_id: Hash) -> Self {
+    pub fn new_signature(
+        from_keypair: &KeyPair,
+        tx_sig: Signature,
+        genesis: Hash,
+        last_id: Hash,
+    ) -> Self {
         let instruction = Instruction::ApplySignature(tx_sig);
-        Self::new_from_instruction(from_keypair, instruction, last_id, 0)
+        Self::new_from_instruction(from_keypair, instruction, genesis, last_id, 0)
+    }
+
+    /// Create and sign a Witness that the ledger has reached `height` ticks,
+    /// for the contract with signature `tx_sig`.
+    pub fn new_tick_height(
+        from_keypair: &KeyPair,
+        tx_sig: Signature,
+        height: u64,
+        genesis: Hash,
+        last_id: Hash,
+    ) -> Self {
+        let instruction = Instruction::ApplyTickHeight(tx_sig, height);
+        Self::new_from_instruction(from_keypair, instruction, genesis, last_id, 0)
+    }
+
+    /// Create and sign a vote to replace the set of time sources.
+    pub fn new_time_sources(
+        from_keypair: &KeyPair,
+        sources: Vec<PublicKey>,
+        genesis: Hash,
+        last_id: Hash,
+    ) -> Self {
+        let instruction = Instruction::SetTimeSources(sources);
+        Self::new_from_instruction(from_keypair, instruction, genesis, last_id, 0)
+    }
+
+    /// Create and sign the mint's choice of fee collector.
+    pub fn new_fee_collector(
+        from_keypair: &KeyPair,
+        collector: PublicKey,
+        genesis: Hash,
+        last_id: Hash,
+    ) -> Self {
+        let instruction = Instruction::SetFeeCollector(collector);
+        Self::new_from_instruction(from_keypair, instruction, genesis, last_id, 0)
+    }
+
+    /// Create and sign a Transaction that advances the sender's nonce.
+    pub fn new_advance_nonce(from_keypair: &KeyPair, genesis: Hash, last_id: Hash) -> Self {
+        Self::new_from_instruction(from_keypair, Instruction::AdvanceNonce, genesis, last_id, 0)
+    }
+
+    /// Create and sign a Witness that reveals `preimage` to the contract with
+    /// signature `tx_sig`.
+    pub fn new_preimage(
+        from_keypair: &KeyPair,
+        tx_sig: Signature,
+        preimage: Vec<u8>,
+        genesis: Hash,
+        last_id: Hash,
+    ) -> Self {
+        let instruction = Instruction::ApplyPreimage(tx_sig, preimage);
+        Self::new_from_instruction(from_keypair, instruction, genesis, last_id, 0)
+    }
+
+    /// Create and sign a hash time-locked Transaction, which pays `to` once the
+    /// preimage of `hash` is revealed, or refunds the sender after `dt`.
+    pub fn new_hash_time_locked(
+        from_keypair: &KeyPair,
+        to: PublicKey,
+        hash: Hash,
+        dt: DateTime<Utc>,
+        tokens: i64,
+        genesis: Hash,
+        last_id: Hash,
+    ) -> Self {
+        let from = from_keypair.pubkey();
+        let budget = Budget::new_hash_time_locked_payment(hash, dt, from, tokens, to);
+        let plan = Plan::Budget(budget);
+        let instruction = Instruction::NewContract(Contract {
+            plan,
+            tokens,
+            expires: None,
+        });
+        Self::new_from_instruction(from_keypair, instruction, genesis, last_id, 0)
+    }
+
+    /// Create and sign a Transaction that pays `to` once `m` of the `signers`
+    /// have signed it.
+    pub fn new_multisig(
+        from_keypair: &KeyPair,
+        to: PublicKey,
+        m: u64,
+        signers: Vec<PublicKey>,
+        tokens: i64,
+        genesis: Hash,
+        last_id: Hash,
+    ) -> Self {
+        let budget = Budget::new_multisig_payment(m, signers, tokens, to);
+        let plan = Plan::Budget(budget);
+        let instruction = Instruction::NewContract(Contract {
+            plan,
+            tokens,
+            expires: None,
+        });
+        Self::new_from_instruction(from_keypair, instruction, genesis, last_id, 0)
     }
 
     /// Create and sign a postdated Transaction. Used for unit-testing.
@@ -119,6 +452,7 @@ impl Transaction {
         to: PublicKey,
         dt: DateTime<Utc>,
         tokens: i64,
+        genesis: Hash,
         last_id: Hash,
     ) -> Self {
         let from = from_keypair.pubkey();
@@ -127,19 +461,68 @@ impl Transaction {
             (Condition::Signature(from), Payment { tokens, to: from }),
         );
         let plan = Plan::Budget(budget);
-        let instruction = Instruction::NewContract(Contract { plan, tokens });
-        Self::new_from_instruction(from_keypair, instruction, last_id, 0)
+        let instruction = Instruction::NewContract(Contract {
+            plan,
+            tokens,
+            expires: None,
+        });
+        Self::new_from_instruction(from_keypair, instruction, genesis, last_id, 0)
     }
 
-    fn get_sign_data(&self) -> Vec<u8> {
-        let mut data = serialize(&(&self.instruction)).expect("serialize Contract");
-        let last_id_data = serialize(&(&self.last_id)).expect("serialize last_id");
-        data.extend_from_slice(&last_id_data);
+    /// Create and sign a Transaction that applies every instruction or none
+    /// of them. The fee is paid out of the contract the batch creates.
+    pub fn new_batch(
+        from_keypair: &KeyPair,
+        instructions: Vec<Instruction>,
+        genesis: Hash,
+        last_id: Hash,
+        fee: i64,
+    ) -> Self {
+        let instruction = Instruction::Batch(instructions);
+        Self::new_from_instruction(from_keypair, instruction, genesis, last_id, fee)
+    }
 
-        let fee_data = serialize(&(&self.fee)).expect("serialize last_id");
-        data.extend_from_slice(&fee_data);
+    /// Return the contract the transaction creates, if any.
+    pub fn contract(&self) -> Option<&Contract> {
+        match &self.instruction {
+            Instruction::NewContract(contract) => Some(contract),
+            Instruction::Batch(instructions) => instructions
+                .iter()
+                .filter_map(|instruction| match instruction {
+                    Instruction::NewContract(contract) => Some(contract),
+                    _ => None,
+                })
+                .next(),
+            _ => None,
+        }
+    }
 
-        data
+    /// Return the accounts the transaction debits, and how many tokens it
+    /// takes from each. The sender pays whatever the co-signers don't.
+    /// Returns None if the co-signers' tokens overflow, whether or not the
+    /// plan was verified.
+    pub fn debits(&self) -> Option<Vec<(PublicKey, i64)>> {
+        let contract = match self.contract() {
+            Some(contract) => contract,
+            None => return Some(vec![]),
+        };
+        let mut cosigned = 0i64;
+        for cosigner in &self.cosigners {
+            cosigned = cosigned.checked_add(cosigner.tokens)?;
+        }
+        let mut debits = vec![(self.from, contract.tokens.checked_sub(cosigned)?)];
+        debits.extend(
+            self.cosigners
+                .iter()
+                .map(|cosigner| (cosigner.key, cosigner.tokens)),
+        );
+        Some(debits)
+    }
+
+    /// Return the data that the sender and every co-signer sign, as laid
+    /// out by the `wire` module.
+    pub fn get_sign_data(&self) -> Vec<u8> {
+        wire::encode_signed_data(self)
     }
 
     /// Sign this transaction.
@@ -148,18 +531,89 @@ impl Transaction {
         self.sig = Signature::clone_from_slice(keypair.sign(&sign_data).as_ref());
     }
 
+    /// Add the signature of one of the co-signers. Each signs the same data
+    /// as the sender, in any order.
+    pub fn cosign(&mut self, keypair: &KeyPair) {
+        let pubkey = keypair.pubkey();
+        if let Some(i) = self.cosigners
+            .iter()
+            .position(|cosigner| cosigner.key == pubkey)
+        {
+            let sign_data = self.get_sign_data();
+            self.cosigs
+                .resize(self.cosigners.len(), Signature::default());
+            self.cosigs[i] = Signature::clone_from_slice(keypair.sign(&sign_data).as_ref());
+        }
+    }
+
     pub fn verify_sig(&self) -> bool {
         warn!("transaction signature verification called");
-        self.sig.verify(&self.from, &self.get_sign_data())
+        let sign_data = self.get_sign_data();
+        self.sig.verify(&self.from, &sign_data) && self.cosigs.len() == self.cosigners.len()
+            && self.cosigners
+                .iter()
+                .zip(&self.cosigs)
+                .all(|(cosigner, sig)| sig.verify(&cosigner.key, &sign_data))
     }
 
     pub fn verify_plan(&self) -> bool {
-        if let Instruction::NewContract(contract) = &self.instruction {
-            self.fee >= 0 && self.fee <= contract.tokens
-                && contract.plan.verify(contract.tokens - self.fee)
-        } else {
-            true
+        match &self.instruction {
+            Instruction::SetTimeSources(sources) => return !sources.is_empty() && self.fee == 0,
+            Instruction::Batch(instructions) => if !Self::verify_batch(instructions) {
+                return false;
+            },
+            _ => {}
         }
+        match self.contract() {
+            Some(contract) => {
+                self.fee >= 0 && match contract.tokens.checked_sub(self.fee) {
+                    Some(spendable) => spendable >= 0 && contract.plan.verify(spendable),
+                    None => false,
+                } && self.verify_cosigners(contract.tokens)
+            }
+            // Only a contract can pay a fee.
+            None => self.fee == 0 && self.cosigners.is_empty(),
+        }
+    }
+
+    /// Return true if the co-signers are distinct from each other and the
+    /// sender, and don't contribute more than the contract's `tokens`.
+    fn verify_cosigners(&self, tokens: i64) -> bool {
+        if self.cosigners.len() >= wire::MAX_SIGNERS {
+            return false;
+        }
+        let mut total = 0i64;
+        for (i, cosigner) in self.cosigners.iter().enumerate() {
+            if cosigner.tokens < 0 || cosigner.key == self.from
+                || self.cosigners[..i].iter().any(|c| c.key == cosigner.key)
+            {
+                return false;
+            }
+            total = match total.checked_add(cosigner.tokens) {
+                Some(total) => total,
+                None => return false,
+            };
+        }
+        total <= tokens
+    }
+
+    /// Return true if the bank can apply the instructions atomically. A batch
+    /// may create at most one contract, since pending contracts are known by
+    /// the signature of the transaction that created them. Otherwise it may
+    /// only witness contracts. Timestamps and time source votes move the
+    /// bank's clock, which can't be rolled back.
+    pub fn verify_batch(instructions: &[Instruction]) -> bool {
+        let mut num_contracts = 0;
+        !instructions.is_empty() && instructions.iter().all(|instruction| match instruction {
+            Instruction::NewContract(_) => {
+                num_contracts += 1;
+                num_contracts == 1
+            }
+            Instruction::ApplySignature(_)
+            | Instruction::ApplyPreimage(..)
+            | Instruction::ApplyTickHeight(..) => true,
+            _ => false,
+        })
     }
 }
 
@@ -168,7 +622,7 @@ pub fn test_tx() -> Transaction {
     let keypair1 = KeyPair::new();
     let pubkey1 = keypair1.pubkey();
     let zero = Hash::default();
-    Transaction::new(&keypair1, pubkey1, 42, zero)
+    Transaction::new(&keypair1, pubkey1, 42, zero, zero)
 }
 
 #[cfg(test)]
@@ -187,12 +641,13 @@ pub fn memfind<A: Eq>(a: &[A], b: &[A]) -> Option<usize> {
 mod tests {
     use super::*;
     use bincode::{deserialize, serialize};
+    use hash::hash;
 
     #[test]
     fn test_claim() {
         let keypair = KeyPair::new();
         let zero = Hash::default();
-        let tx0 = Transaction::new(&keypair, keypair.pubkey(), 42, zero);
+        let tx0 = Transaction::new(&keypair, keypair.pubkey(), 42, zero, zero);
         assert!(tx0.verify_plan());
     }
 
@@ -202,7 +657,7 @@ mod tests {
         let keypair0 = KeyPair::new();
         let keypair1 = KeyPair::new();
         let pubkey1 = keypair1.pubkey();
-        let tx0 = Transaction::new(&keypair0, pubkey1, 42, zero);
+        let tx0 = Transaction::new(&keypair0, pubkey1, 42, zero, zero);
         assert!(tx0.verify_plan());
     }
 
@@ -211,9 +666,9 @@ mod tests {
         let zero = Hash::default();
         let keypair0 = KeyPair::new();
         let pubkey1 = KeyPair::new().pubkey();
-        assert!(Transaction::new_taxed(&keypair0, pubkey1, 1, 1, zero).verify_plan());
-        assert!(!Transaction::new_taxed(&keypair0, pubkey1, 1, 2, zero).verify_plan());
-        assert!(!Transaction::new_taxed(&keypair0, pubkey1, 1, -1, zero).verify_plan());
+        assert!(Transaction::new_taxed(&keypair0, pubkey1, 1, 1, zero, zero).verify_plan());
+        assert!(!Transaction::new_taxed(&keypair0, pubkey1, 1, 2, zero, zero).verify_plan());
+        assert!(!Transaction::new_taxed(&keypair0, pubkey1, 1, -1, zero, zero).verify_plan());
     }
 
     #[test]
@@ -223,12 +678,19 @@ mod tests {
             to: Default::default(),
         });
         let plan = Plan::Budget(budget);
-        let instruction = Instruction::NewContract(Contract { plan, tokens: 0 });
+        let instruction = Instruction::NewContract(Contract {
+            plan,
+            tokens: 0,
+            expires: None,
+        });
         let claim0 = Transaction {
             instruction,
             from: Default::default(),
+            genesis: Default::default(),
             last_id: Default::default(),
             sig: Default::default(),
+            cosigs: vec![],
+            cosigners: vec![],
             fee: 0,
         };
         let buf = serialize(&claim0).unwrap();
@@ -241,7 +703,7 @@ mod tests {
         let zero = Hash::default();
         let keypair = KeyPair::new();
         let pubkey = keypair.pubkey();
-        let mut tx = Transaction::new(&keypair, pubkey, 42, zero);
+        let mut tx = Transaction::new(&keypair, pubkey, 42, zero, zero);
         if let Instruction::NewContract(contract) = &mut tx.instruction {
             contract.tokens = 1_000_000; // <-- attack, part 1!
             if let Plan::Budget(Budget::Pay(ref mut payment)) = contract.plan {
@@ -259,7 +721,7 @@ mod tests {
         let thief_keypair = KeyPair::new();
         let pubkey1 = keypair1.pubkey();
         let zero = Hash::default();
-        let mut tx = Transaction::new(&keypair0, pubkey1, 42, zero);
+        let mut tx = Transaction::new(&keypair0, pubkey1, 42, zero, zero);
         if let Instruction::NewContract(contract) = &mut tx.instruction {
             if let Plan::Budget(Budget::Pay(ref mut payment)) = contract.plan {
                 payment.to = thief_keypair.pubkey(); // <-- attack!
@@ -268,14 +730,63 @@ mod tests {
         assert!(tx.verify_plan());
         assert!(!tx.verify_sig());
     }
+
     #[test]
-    fn test_layout() {
-        let tx = test_tx();
-        let sign_data = tx.get_sign_data();
-        let tx_bytes = serialize(&tx).unwrap();
-        assert_matches!(memfind(&tx_bytes, &sign_data), Some(SIGNED_DATA_OFFSET));
-        assert_matches!(memfind(&tx_bytes, &tx.sig), Some(SIG_OFFSET));
-        assert_matches!(memfind(&tx_bytes, &tx.from), Some(PUB_KEY_OFFSET));
+    fn test_replay_attack() {
+        let keypair0 = KeyPair::new();
+        let pubkey1 = KeyPair::new().pubkey();
+        let zero = Hash::default();
+        let mut tx = Transaction::new(&keypair0, pubkey1, 42, zero, zero);
+        tx.genesis = hash(b"another ledger"); // <-- attack!
+        assert!(tx.verify_plan());
+        assert!(!tx.verify_sig());
+    }
+
+    #[test]
+    fn test_cosigners() {
+        let keypair0 = KeyPair::new();
+        let keypair1 = KeyPair::new();
+        let zero = Hash::default();
+        let to = keypair0.pubkey();
+        let tx = Transaction::new_with_fee_payer(&keypair0, &keypair1, to, 42, 2, zero, zero);
+        assert!(tx.verify_plan());
+        assert!(tx.verify_sig());
+        assert_eq!(
+            tx.debits(),
+            Some(vec![(keypair0.pubkey(), 42), (keypair1.pubkey(), 2)])
+        );
+
+        // Every co-signer has to sign.
+        let mut unsigned = tx.clone();
+        unsigned.cosigs.clear();
+        assert!(!unsigned.verify_sig());
+        let mut forged = tx.clone();
+        forged.cosigs[0] = tx.sig; // <-- attack!
+        assert!(!forged.verify_sig());
+
+        // Co-signers can't be dropped, or made to pay more.
+        let mut tx1 = tx.clone();
+        tx1.cosigners.clear();
+        tx1.cosigs.clear();
+        assert!(!tx1.verify_sig());
+        let mut tx1 = tx.clone();
+        tx1.cosigners[0].tokens = 3; // <-- attack!
+        assert!(!tx1.verify_sig());
+
+        // Nor can they contribute more than the contract holds.
+        let cosigners = [(&keypair1, 43)];
+        let tx = Transaction::new_joint(&keypair0, &cosigners, keypair0.pubkey(), 42, zero, zero);
+        assert!(!tx.verify_plan());
+        let cosigners = [(&keypair0, 1)];
+        let tx = Transaction::new_joint(&keypair0, &cosigners, keypair0.pubkey(), 42, zero, zero);
+        assert!(!tx.verify_plan());
+
+        // Debits of an unverified plan mustn't wrap around.
+        let keypair2 = KeyPair::new();
+        let cosigners = [(&keypair1, i64::max_value()), (&keypair2, 1)];
+        let tx = Transaction::new_joint(&keypair0, &cosigners, keypair0.pubkey(), 42, zero, zero);
+        assert!(!tx.verify_plan());
+        assert_eq!(tx.debits(), None);
     }
 
     #[test]
@@ -283,7 +794,7 @@ mod tests {
         let keypair0 = KeyPair::new();
         let keypair1 = KeyPair::new();
         let zero = Hash::default();
-        let mut tx = Transaction::new(&keypair0, keypair1.pubkey(), 1, zero);
+        let mut tx = Transaction::new(&keypair0, keypair1.pubkey(), 1, zero, zero);
         if let Instruction::NewContract(contract) = &mut tx.instruction {
             if let Plan::Budget(Budget::Pay(ref mut payment)) = contract.plan {
                 payment.tokens = 2; // <-- attack!
@@ -299,4 +810,52 @@ mod tests {
         }
         assert!(!tx.verify_plan());
     }
+
+    #[test]
+    fn test_batch_layout() {
+        let keypair = KeyPair::new();
+        let zero = Hash::default();
+        let contract = Transaction::new(&keypair, keypair.pubkey(), 42, zero, zero).instruction;
+        let witness = Instruction::ApplySignature(Signature::default());
+        let mut tx = Transaction::new_batch(&keypair, vec![contract, witness], zero, zero, 0);
+        let sign_data = tx.get_sign_data();
+        let tx_bytes = wire::encode(&tx);
+        assert_matches!(memfind(&tx_bytes, &sign_data), Some(wire::SIGNED_DATA_OFFSET));
+        assert_eq!(tx_bytes.len(), wire::SIGNED_DATA_OFFSET + sign_data.len());
+        assert!(tx.verify_sig());
+
+        if let Instruction::Batch(instructions) = &mut tx.instruction {
+            instructions[1] = Instruction::ApplySignature(tx.sig); // <-- attack!
+        }
+        assert!(!tx.verify_sig());
+    }
+
+    #[test]
+    fn test_verify_batch() {
+        let keypair = KeyPair::new();
+        let zero = Hash::default();
+        let contract = Transaction::new(&keypair, keypair.pubkey(), 42, zero, zero).instruction;
+        let witness = Instruction::ApplySignature(Signature::default());
+
+        let instructions = vec![witness.clone(), contract.clone()];
+        let tx = Transaction::new_batch(&keypair, instructions, zero, zero, 0);
+        assert!(tx.verify_plan());
+        assert_eq!(tx.contract().map(|contract| contract.tokens), Some(42));
+
+        let tx = Transaction::new_batch(&keypair, vec![witness.clone()], zero, zero, 0);
+        assert!(tx.verify_plan());
+        assert_eq!(tx.contract(), None);
+        let tx = Transaction::new_batch(&keypair, vec![witness.clone()], zero, zero, 1);
+        assert!(!tx.verify_plan());
+
+        // Only one contract, and nothing that moves the clock.
+        let tx = Transaction::new_batch(&keypair, vec![contract.clone(), contract], zero, zero, 0);
+        assert!(!tx.verify_plan());
+        let timestamp = Instruction::ApplyTimestamp(Utc::now());
+        let tx = Transaction::new_batch(&keypair, vec![witness.clone(), timestamp], zero, zero, 0);
+        assert!(!tx.verify_plan());
+        let nested = Instruction::Batch(vec![witness]);
+        assert!(!Transaction::new_batch(&keypair, vec![nested], zero, zero, 0).verify_plan());
+        assert!(!Transaction::new_batch(&keypair, vec![], zero, zero, 0).verify_plan());
+    }
 }
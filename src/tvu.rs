@@ -119,6 +119,7 @@ impl Tvu {
 
         let replicate_stage = ReplicateStage::new(
             bank.clone(),
+            crdt.clone(),
             exit.clone(),
             window_receiver,
             blob_recycler.clone(),
@@ -252,6 +253,7 @@ pub mod tests {
                 &mint.keypair(),
                 bob_keypair.pubkey(),
                 transfer_amount,
+                mint.seed(),
                 cur_hash,
             );
             bank.register_entry_id(&cur_hash);
@@ -0,0 +1,190 @@
+//! The `vesting` module provides a payment plan that releases its tokens to a
+//! single recipient over time. Each timestamp witness releases every tranche
+//! whose date has passed. Released tokens are paid out right away, while the
+//! rest of the plan stays pending until the last tranche is released.
+
+use chrono::prelude::*;
+use chrono::Duration;
+use payment_plan::{Payment, PaymentPlan, Witness};
+use signature::PublicKey;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct Vesting {
+    pub to: PublicKey,
+    /// Tokens that are released on or after each date.
+    pub schedule: Vec<(DateTime<Utc>, i64)>,
+    /// Tokens that have been released but not yet paid out.
+    pub released: i64,
+}
+
+impl Vesting {
+    /// Create a Vesting plan that releases `cliff_tokens` at `cliff`, then
+    /// the remaining tokens in `num_steps` equal parts, one every `step`
+    /// after the cliff. The last part includes any rounding remainder.
+    pub fn new(
+        to: PublicKey,
+        tokens: i64,
+        cliff: DateTime<Utc>,
+        cliff_tokens: i64,
+        step: Duration,
+        num_steps: i32,
+    ) -> Self {
+        if num_steps <= 0 {
+            return Vesting {
+                to,
+                schedule: vec![(cliff, tokens)],
+                released: 0,
+            };
+        }
+        let remainder = tokens - cliff_tokens;
+        let part = remainder / i64::from(num_steps);
+        let mut schedule = vec![(cliff, cliff_tokens)];
+        for i in 1..num_steps {
+            schedule.push((cliff + step * i, part));
+        }
+        let last = remainder - part * i64::from(num_steps - 1);
+        schedule.push((cliff + step * num_steps, last));
+        Vesting {
+            to,
+            schedule,
+            released: 0,
+        }
+    }
+
+    /// Return the total number of tokens the plan pays out, or None if it
+    /// overflows.
+    pub fn tokens(&self) -> Option<i64> {
+        let mut total = self.released;
+        for (_, tokens) in &self.schedule {
+            total = total.checked_add(*tokens)?;
+        }
+        Some(total)
+    }
+}
+
+impl PaymentPlan for Vesting {
+    /// Return Payment once the last tranche has been released.
+    fn final_payment(&self) -> Option<Payment> {
+        if self.schedule.is_empty() {
+            Some(Payment {
+                tokens: self.released,
+                to: self.to,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Return true if the schedule spends exactly `spendable_tokens`.
+    fn verify(&self, spendable_tokens: i64) -> bool {
+        // Like a Split, no tranche may be negative, so that a later tranche
+        // can't claw back tokens from an earlier one.
+        !self.schedule.is_empty() && self.released >= 0
+            && self.schedule.iter().all(|(_, tokens)| *tokens >= 0)
+            && self.tokens() == Some(spendable_tokens)
+    }
+
+    /// Vesting only listens to timestamps, which are never duplicates.
+    fn is_duplicate_witness(&self, _witness: &Witness) -> bool {
+        false
+    }
+
+    /// Release every tranche whose date is on or before the witness timestamp.
+    fn apply_witness(&mut self, witness: &Witness) {
+        if let Witness::Timestamp(last_time) = witness {
+            let mut released = 0;
+            self.schedule.retain(|(dt, tokens)| {
+                if dt <= last_time {
+                    released += tokens;
+                    false
+                } else {
+                    true
+                }
+            });
+            self.released += released;
+        }
+    }
+
+    /// Return the date of the next tranche.
+    fn next_deadline(&self) -> Option<DateTime<Utc>> {
+        self.schedule.iter().map(|(dt, _)| *dt).min()
+    }
+
+    fn involves(&self, pubkey: &PublicKey) -> bool {
+        self.to == *pubkey
+    }
+
+    /// Pay out the released tranches while later ones are still pending.
+    fn take_due_payments(&mut self) -> Vec<Payment> {
+        if self.schedule.is_empty() || self.released == 0 {
+            return vec![];
+        }
+        let payment = Payment {
+            tokens: self.released,
+            to: self.to,
+        };
+        self.released = 0;
+        vec![payment]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new_schedule() {
+        let to = PublicKey::default();
+        let cliff = Utc.ymd(2018, 7, 1).and_hms(0, 0, 0);
+        let day = Duration::days(1);
+        let vesting = Vesting::new(to, 10, cliff, 4, day, 4);
+        assert_eq!(
+            vesting.schedule,
+            vec![
+                (cliff, 4),
+                (cliff + day, 1),
+                (cliff + day * 2, 1),
+                (cliff + day * 3, 1),
+                (cliff + day * 4, 3),
+            ]
+        );
+        assert!(vesting.verify(10));
+        assert!(!vesting.verify(9));
+
+        let vesting = Vesting::new(to, 10, cliff, 4, day, 0);
+        assert_eq!(vesting.schedule, vec![(cliff, 10)]);
+        assert!(vesting.verify(10));
+    }
+
+    #[test]
+    fn test_verify_negative_tranche() {
+        let to = PublicKey::default();
+        let cliff = Utc.ymd(2018, 7, 1).and_hms(0, 0, 0);
+        let vesting = Vesting::new(to, 10, cliff, 12, Duration::days(1), 1);
+        assert_eq!(vesting.schedule[1].1, -2);
+        assert!(!vesting.verify(10));
+    }
+
+    #[test]
+    fn test_release_tranches() {
+        let to = PublicKey::default();
+        let cliff = Utc.ymd(2018, 7, 1).and_hms(0, 0, 0);
+        let day = Duration::days(1);
+        let mut vesting = Vesting::new(to, 10, cliff, 4, day, 3);
+
+        vesting.apply_witness(&Witness::Timestamp(cliff - day));
+        assert_eq!(vesting.take_due_payments(), vec![]);
+        assert_eq!(vesting.next_deadline(), Some(cliff));
+
+        vesting.apply_witness(&Witness::Signature(to));
+        vesting.apply_witness(&Witness::Timestamp(cliff + day));
+        assert_eq!(vesting.take_due_payments(), vec![Payment { tokens: 6, to }]);
+        assert_eq!(vesting.take_due_payments(), vec![]);
+        assert_eq!(vesting.final_payment(), None);
+        assert_eq!(vesting.next_deadline(), Some(cliff + day * 2));
+
+        vesting.apply_witness(&Witness::Timestamp(cliff + day * 3));
+        assert_eq!(vesting.take_due_payments(), vec![]);
+        assert_eq!(vesting.final_payment(), Some(Payment { tokens: 4, to }));
+    }
+}
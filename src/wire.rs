@@ -0,0 +1,521 @@
+//! The `wire` module defines the binary layout of the transactions that
+//! clients send to the leader. Unlike the bincode encoding of `Transaction`
+//! that the ledger uses, the layout is versioned, and every field that
+//! signature verification needs is at a documented offset. All integers are
+//! little-endian. Version 2 is laid out as follows, where N is the number of
+//! signers:
+//!
+//! | Offset    | Size      | Field                                              |
+//! |-----------|-----------|----------------------------------------------------|
+//! | 0         | 1         | Version                                            |
+//! | 1         | 1         | N, the sender and its co-signers                   |
+//! | 2         | 64 * N    | Signatures, the sender's first                     |
+//! | 2 + 64N   | 4         | L, the length of the signed data                   |
+//! | 6 + 64N   | L         | Signed data, which runs to the end of the packet   |
+//!
+//! The signed data is what every signer signs:
+//!
+//! | Offset    | Size      | Field                                              |
+//! |-----------|-----------|----------------------------------------------------|
+//! | 0         | 32 * N    | Public keys, in the same order as the signatures   |
+//! | 32N       | 8 (N - 1) | Tokens contributed by each co-signer               |
+//! | 40N - 8   | 32        | Genesis, the ID of the ledger's first entry        |
+//! | 40N + 24  | 32        | `last_id`                                          |
+//! | 40N + 56  | 8         | Fee                                                |
+//! | 40N + 64  | the rest  | Instruction, encoded with bincode                  |
+//!
+//! Version 1 didn't sign the genesis, so its transactions could be replayed
+//! on any ledger that shared a `last_id`. It is no longer accepted.
+//!
+//! An encoded transaction that fits is sent in a single packet. Because the
+//! signed data runs to the end of the packet, a datagram that was truncated
+//! to fit a packet fails the length check. A longer one, of up to
+//! `MAX_TRANSACTION_SIZE` bytes, is split into at most `MAX_FRAGMENTS`
+//! fragments, each sent in its own packet:
+//!
+//! | Offset    | Size      | Field                                              |
+//! |-----------|-----------|----------------------------------------------------|
+//! | 0         | 1         | `FRAGMENT_MARKER`, in place of the version         |
+//! | 1         | 8         | ID, the same in every fragment of a transaction    |
+//! | 9         | 1         | Index of the fragment                              |
+//! | 10        | 1         | Count of the fragments                             |
+//! | 11        | the rest  | The next slice of the encoded transaction          |
+//!
+//! The fetch stage reassembles them, and `signed_data_range` checks the
+//! size of the result before any signature is verified.
+
+use bincode::{deserialize, serialize};
+use byteorder::{ByteOrder, LittleEndian, WriteBytesExt};
+use hash::Hash;
+use packet::PACKET_DATA_SIZE;
+use signature::{PublicKey, Signature};
+use std::ops::Range;
+use std::result;
+use transaction::{Cosigner, Transaction};
+
+pub const VERSION: u8 = 2;
+pub const VERSION_OFFSET: usize = 0;
+pub const NUM_SIGNERS_OFFSET: usize = 1;
+pub const SIGS_OFFSET: usize = 2;
+pub const SIG_SIZE: usize = 64;
+pub const KEY_SIZE: usize = 32;
+pub const TOKENS_SIZE: usize = 8;
+pub const LEN_SIZE: usize = 4;
+pub const HASH_SIZE: usize = 32;
+pub const MAX_SIGNERS: usize = 8;
+pub const FRAGMENT_MARKER: u8 = 0xff;
+pub const FRAGMENT_ID_OFFSET: usize = 1;
+pub const FRAGMENT_INDEX_OFFSET: usize = 9;
+pub const FRAGMENT_COUNT_OFFSET: usize = 10;
+pub const FRAGMENT_HEADER_SIZE: usize = 11;
+pub const FRAGMENT_DATA_SIZE: usize = PACKET_DATA_SIZE - FRAGMENT_HEADER_SIZE;
+pub const MAX_FRAGMENTS: usize = 8;
+pub const MAX_TRANSACTION_SIZE: usize = MAX_FRAGMENTS * FRAGMENT_DATA_SIZE;
+
+/// The offsets of the only signature, public key and signed data of a
+/// transaction without co-signers. The GPU verifier only supports these.
+pub const SIG_OFFSET: usize = SIGS_OFFSET;
+pub const PUB_KEY_OFFSET: usize = SIGS_OFFSET + SIG_SIZE + LEN_SIZE;
+pub const SIGNED_DATA_OFFSET: usize = PUB_KEY_OFFSET;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum WireError {
+    /// The version isn't one this node understands.
+    UnsupportedVersion(u8),
+    /// A transaction has at least one signer, and at most `MAX_SIGNERS`.
+    InvalidSignerCount(u8),
+    /// The data ends before the fields it describes, or continues after them.
+    InvalidLength,
+    /// The instruction isn't a canonical bincode encoding of an `Instruction`.
+    InvalidInstruction,
+    /// The data is longer than `MAX_TRANSACTION_SIZE`.
+    TooLarge,
+    /// The fragment's index isn't below its count, or the count is zero or
+    /// above `MAX_FRAGMENTS`.
+    InvalidFragment,
+}
+
+pub type Result<T> = result::Result<T, WireError>;
+
+/// A slice of a transaction too long to send in one packet.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Fragment<'a> {
+    pub id: u64,
+    pub index: usize,
+    pub count: usize,
+    pub data: &'a [u8],
+}
+
+/// Return the offset of the signature of signer `i`.
+pub fn sig_offset(i: usize) -> usize {
+    SIGS_OFFSET + SIG_SIZE * i
+}
+
+/// Return the offset of the length of the signed data.
+pub fn signed_data_len_offset(num_signers: usize) -> usize {
+    sig_offset(num_signers)
+}
+
+/// Return the offset of the signed data.
+pub fn signed_data_offset(num_signers: usize) -> usize {
+    signed_data_len_offset(num_signers) + LEN_SIZE
+}
+
+/// Return the offset of the public key of signer `i`.
+pub fn key_offset(num_signers: usize, i: usize) -> usize {
+    signed_data_offset(num_signers) + KEY_SIZE * i
+}
+
+/// Check the header of an encoded transaction against its length, and return
+/// the number of signers along with the range of the signed data.
+pub fn signed_data_range(data: &[u8]) -> Result<(usize, Range<usize>)> {
+    if data.len() > MAX_TRANSACTION_SIZE {
+        return Err(WireError::TooLarge);
+    }
+    if data.len() < SIGS_OFFSET {
+        return Err(WireError::InvalidLength);
+    }
+    if data[VERSION_OFFSET] != VERSION {
+        return Err(WireError::UnsupportedVersion(data[VERSION_OFFSET]));
+    }
+    let num_signers = data[NUM_SIGNERS_OFFSET] as usize;
+    if num_signers == 0 || num_signers > MAX_SIGNERS {
+        return Err(WireError::InvalidSignerCount(num_signers as u8));
+    }
+    let start = signed_data_offset(num_signers);
+    if data.len() < start {
+        return Err(WireError::InvalidLength);
+    }
+    let len_offset = signed_data_len_offset(num_signers);
+    let len = LittleEndian::read_u32(&data[len_offset..start]) as usize;
+    // The signed data holds at least the keys, tokens, genesis, last_id and fee.
+    let min_len = (KEY_SIZE + TOKENS_SIZE) * num_signers + 2 * HASH_SIZE;
+    if len < min_len || data.len() != start + len {
+        return Err(WireError::InvalidLength);
+    }
+    Ok((num_signers, start..start + len))
+}
+
+/// Encode the data that every signer of `tx` signs.
+pub fn encode_signed_data(tx: &Transaction) -> Vec<u8> {
+    let mut data = vec![];
+    data.extend_from_slice(&tx.from);
+    for cosigner in &tx.cosigners {
+        data.extend_from_slice(&cosigner.key);
+    }
+    for cosigner in &tx.cosigners {
+        data.write_i64::<LittleEndian>(cosigner.tokens)
+            .expect("write tokens in encode_signed_data");
+    }
+    data.extend_from_slice(&tx.genesis);
+    data.extend_from_slice(&tx.last_id);
+    data.write_i64::<LittleEndian>(tx.fee)
+        .expect("write fee in encode_signed_data");
+    let instruction = serialize(&tx.instruction).expect("serialize instruction");
+    data.extend_from_slice(&instruction);
+    data
+}
+
+/// Encode `tx` in the current version of the layout. Co-signatures that
+/// haven't been added yet are encoded as zeros.
+pub fn encode(tx: &Transaction) -> Vec<u8> {
+    let num_signers = 1 + tx.cosigners.len();
+    let signed_data = encode_signed_data(tx);
+    let mut data = Vec::with_capacity(signed_data_offset(num_signers) + signed_data.len());
+    data.push(VERSION);
+    data.push(num_signers as u8);
+    data.extend_from_slice(&tx.sig);
+    for i in 0..tx.cosigners.len() {
+        match tx.cosigs.get(i) {
+            Some(sig) => data.extend_from_slice(sig),
+            None => data.extend_from_slice(&[0; SIG_SIZE]),
+        }
+    }
+    data.write_u32::<LittleEndian>(signed_data.len() as u32)
+        .expect("write length in encode");
+    data.extend_from_slice(&signed_data);
+    data
+}
+
+/// Return the datagrams that carry the encoded transaction `data`: the
+/// transaction itself if it fits in a packet, or else its fragments. The
+/// fragments are identified by the start of the sender's signature.
+pub fn to_datagrams(data: &[u8]) -> Result<Vec<Vec<u8>>> {
+    if data.len() > MAX_TRANSACTION_SIZE {
+        return Err(WireError::TooLarge);
+    }
+    if data.len() <= PACKET_DATA_SIZE {
+        return Ok(vec![data.to_vec()]);
+    }
+    let id = LittleEndian::read_u64(&data[SIG_OFFSET..SIG_OFFSET + 8]);
+    let chunks: Vec<_> = data.chunks(FRAGMENT_DATA_SIZE).collect();
+    let datagrams = chunks
+        .iter()
+        .enumerate()
+        .map(|(index, chunk)| {
+            let mut datagram = Vec::with_capacity(FRAGMENT_HEADER_SIZE + chunk.len());
+            datagram.push(FRAGMENT_MARKER);
+            datagram
+                .write_u64::<LittleEndian>(id)
+                .expect("write id in to_datagrams");
+            datagram.push(index as u8);
+            datagram.push(chunks.len() as u8);
+            datagram.extend_from_slice(chunk);
+            datagram
+        })
+        .collect();
+    Ok(datagrams)
+}
+
+/// Decode the header of a fragment. Fails with `UnsupportedVersion` if the
+/// datagram isn't a fragment at all.
+pub fn decode_fragment<'a>(data: &'a [u8]) -> Result<Fragment<'a>> {
+    if data.len() > PACKET_DATA_SIZE {
+        return Err(WireError::TooLarge);
+    }
+    if data.is_empty() {
+        return Err(WireError::InvalidLength);
+    }
+    if data[VERSION_OFFSET] != FRAGMENT_MARKER {
+        return Err(WireError::UnsupportedVersion(data[VERSION_OFFSET]));
+    }
+    if data.len() <= FRAGMENT_HEADER_SIZE {
+        return Err(WireError::InvalidLength);
+    }
+    let index = data[FRAGMENT_INDEX_OFFSET] as usize;
+    let count = data[FRAGMENT_COUNT_OFFSET] as usize;
+    if count == 0 || count > MAX_FRAGMENTS || index >= count {
+        return Err(WireError::InvalidFragment);
+    }
+    Ok(Fragment {
+        id: LittleEndian::read_u64(&data[FRAGMENT_ID_OFFSET..FRAGMENT_INDEX_OFFSET]),
+        index,
+        count,
+        data: &data[FRAGMENT_HEADER_SIZE..],
+    })
+}
+
+/// Decode a transaction encoded in any supported version of the layout.
+pub fn decode(data: &[u8]) -> Result<Transaction> {
+    let (num_signers, range) = signed_data_range(data)?;
+    let num_cosigners = num_signers - 1;
+    let sigs: Vec<_> = (0..num_signers)
+        .map(|i| Signature::clone_from_slice(&data[sig_offset(i)..sig_offset(i + 1)]))
+        .collect();
+
+    let signed_data = &data[range];
+    let keys: Vec<_> = signed_data[..KEY_SIZE * num_signers]
+        .chunks(KEY_SIZE)
+        .map(PublicKey::clone_from_slice)
+        .collect();
+    let tokens_start = KEY_SIZE * num_signers;
+    let genesis_start = tokens_start + TOKENS_SIZE * num_cosigners;
+    let last_id_start = genesis_start + HASH_SIZE;
+    let fee_start = last_id_start + HASH_SIZE;
+    let instruction_start = fee_start + TOKENS_SIZE;
+    let cosigners = keys[1..]
+        .iter()
+        .zip(signed_data[tokens_start..genesis_start].chunks(TOKENS_SIZE))
+        .map(|(key, tokens)| Cosigner {
+            key: *key,
+            tokens: LittleEndian::read_i64(tokens),
+        })
+        .collect();
+
+    let instruction_data = &signed_data[instruction_start..];
+    let instruction =
+        deserialize(instruction_data).map_err(|_| WireError::InvalidInstruction)?;
+    // Reject trailing bytes, so that each transaction has exactly one encoding.
+    match serialize(&instruction) {
+        Ok(ref canonical) if canonical[..] == instruction_data[..] => {}
+        _ => return Err(WireError::InvalidInstruction),
+    }
+
+    Ok(Transaction {
+        sig: sigs[0],
+        from: keys[0],
+        cosigs: sigs[1..].to_vec(),
+        cosigners,
+        instruction,
+        genesis: Hash::clone_from_slice(&signed_data[genesis_start..last_id_start]),
+        last_id: Hash::clone_from_slice(&signed_data[last_id_start..fee_start]),
+        fee: LittleEndian::read_i64(&signed_data[fee_start..instruction_start]),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use budget::Budget;
+    use signature::{KeyPair, KeyPairUtil};
+    use transaction::{memfind, test_tx, Contract, Instruction, Plan};
+
+    /// A transaction with fixed keys and signatures, so that its encoding
+    /// doesn't change between runs.
+    fn golden_tx(cosigners: usize) -> Transaction {
+        let instruction = Instruction::NewContract(Contract {
+            tokens: 5,
+            plan: Plan::Budget(Budget::new_payment(5, PublicKey::clone_from_slice(&[6; 32]))),
+            expires: None,
+        });
+        Transaction {
+            sig: Signature::clone_from_slice(&[1; 64]),
+            from: PublicKey::clone_from_slice(&[2; 32]),
+            cosigs: (0..cosigners)
+                .map(|i| Signature::clone_from_slice(&[7 + i as u8; 64]))
+                .collect(),
+            cosigners: (0..cosigners)
+                .map(|i| Cosigner {
+                    key: PublicKey::clone_from_slice(&[9 + i as u8; 32]),
+                    tokens: 1,
+                })
+                .collect(),
+            instruction,
+            genesis: Hash::clone_from_slice(&[5; 32]),
+            last_id: Hash::clone_from_slice(&[3; 32]),
+            fee: 4,
+        }
+    }
+
+    fn hex(data: &[u8]) -> String {
+        data.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    /// The bincode encoding of the golden instruction: NewContract, 5
+    /// tokens, Budget::Pay of 5 tokens to [6; 32], and no expiry. Keys are
+    /// preceded by their length.
+    fn golden_instruction() -> String {
+        [
+            "00000000",
+            "0500000000000000",
+            "00000000",
+            "00000000",
+            "0500000000000000",
+            "2000000000000000",
+            &"06".repeat(32),
+            "00",
+        ].concat()
+    }
+
+    #[test]
+    fn test_golden_bytes() {
+        let tx = golden_tx(0);
+        let golden = [
+            "02",
+            "01",
+            &"01".repeat(64),
+            "ad000000",
+            &"02".repeat(32),
+            &"05".repeat(32),
+            &"03".repeat(32),
+            "0400000000000000",
+            &golden_instruction(),
+        ].concat();
+        assert_eq!(hex(&encode(&tx)), golden);
+        assert_eq!(hex(&encode_signed_data(&tx)), golden[2 * SIGNED_DATA_OFFSET..]);
+        assert_eq!(decode(&encode(&tx)), Ok(tx));
+    }
+
+    #[test]
+    fn test_golden_bytes_cosigned() {
+        let tx = golden_tx(2);
+        let golden = [
+            "02",
+            "03",
+            &"01".repeat(64),
+            &"07".repeat(64),
+            &"08".repeat(64),
+            "fd000000",
+            &"02".repeat(32),
+            &"09".repeat(32),
+            &"0a".repeat(32),
+            "0100000000000000",
+            "0100000000000000",
+            &"05".repeat(32),
+            &"03".repeat(32),
+            "0400000000000000",
+            &golden_instruction(),
+        ].concat();
+        assert_eq!(hex(&encode(&tx)), golden);
+        assert_eq!(decode(&encode(&tx)), Ok(tx));
+    }
+
+    #[test]
+    fn test_offsets() {
+        let tx = test_tx();
+        let data = encode(&tx);
+        let signed_data = tx.get_sign_data();
+        assert_eq!(memfind(&data, &signed_data), Some(SIGNED_DATA_OFFSET));
+        assert_eq!(memfind(&data, &tx.sig), Some(SIG_OFFSET));
+        assert_eq!(memfind(&data, &tx.from), Some(PUB_KEY_OFFSET));
+        assert_eq!(signed_data_range(&data), Ok((1, SIGNED_DATA_OFFSET..data.len())));
+
+        let tx = golden_tx(2);
+        let data = encode(&tx);
+        assert_eq!(memfind(&data, &tx.cosigs[1]), Some(sig_offset(2)));
+        assert_eq!(memfind(&data, &tx.cosigners[1].key), Some(key_offset(3, 2)));
+    }
+
+    #[test]
+    fn test_signatures_survive_encoding() {
+        let keypair0 = KeyPair::new();
+        let keypair1 = KeyPair::new();
+        let to = keypair0.pubkey();
+        let zero = Hash::default();
+        let tx = Transaction::new_joint(&keypair0, &[(&keypair1, 1)], to, 2, zero, zero);
+        let tx = decode(&encode(&tx)).unwrap();
+        assert!(tx.verify_sig());
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        let data = encode(&golden_tx(0));
+        assert_eq!(decode(&[]), Err(WireError::InvalidLength));
+        assert_eq!(decode(&data[..data.len() - 1]), Err(WireError::InvalidLength));
+
+        let mut extended = data.clone();
+        extended.push(0);
+        assert_eq!(decode(&extended), Err(WireError::InvalidLength));
+
+        let mut version = data.clone();
+        version[VERSION_OFFSET] = 1;
+        assert_eq!(decode(&version), Err(WireError::UnsupportedVersion(1)));
+
+        let mut signers = data.clone();
+        signers[NUM_SIGNERS_OFFSET] = 0;
+        assert_eq!(decode(&signers), Err(WireError::InvalidSignerCount(0)));
+
+        // Padding inside the signed data changes what was signed.
+        let mut padded = data.clone();
+        padded.push(0);
+        let len_offset = signed_data_len_offset(1);
+        padded[len_offset] += 1;
+        assert_eq!(decode(&padded), Err(WireError::InvalidInstruction));
+
+        let mut oversized = data.clone();
+        oversized.resize(MAX_TRANSACTION_SIZE + 1, 0);
+        assert_eq!(decode(&oversized), Err(WireError::TooLarge));
+    }
+
+    #[test]
+    fn test_size_limit() {
+        // Larger than the old 256-byte packets, but still fits.
+        let data = encode(&golden_tx(2));
+        assert!(data.len() > 256);
+        assert!(signed_data_range(&data).is_ok());
+
+        let keypair = KeyPair::new();
+        let witness = Instruction::ApplySignature(Signature::default());
+        let zero = Hash::default();
+        let tx = Transaction::new_batch(&keypair, vec![witness; 256], zero, zero, 0);
+        let data = encode(&tx);
+        assert!(data.len() > MAX_TRANSACTION_SIZE);
+        assert_eq!(signed_data_range(&data), Err(WireError::TooLarge));
+        assert_eq!(to_datagrams(&data), Err(WireError::TooLarge));
+
+        // A datagram truncated to fit a packet is rejected too.
+        let truncated = &data[..PACKET_DATA_SIZE];
+        assert_eq!(signed_data_range(truncated), Err(WireError::InvalidLength));
+    }
+
+    #[test]
+    fn test_fragments() {
+        // A transaction that fits in a packet is sent whole.
+        let data = encode(&golden_tx(0));
+        assert_eq!(to_datagrams(&data), Ok(vec![data.clone()]));
+        assert_eq!(
+            decode_fragment(&data),
+            Err(WireError::UnsupportedVersion(VERSION))
+        );
+
+        let keypair = KeyPair::new();
+        let witness = Instruction::ApplySignature(Signature::default());
+        let zero = Hash::default();
+        let tx = Transaction::new_batch(&keypair, vec![witness; 32], zero, zero, 0);
+        let data = encode(&tx);
+        assert!(data.len() > PACKET_DATA_SIZE);
+        let datagrams = to_datagrams(&data).unwrap();
+        let count = (data.len() + FRAGMENT_DATA_SIZE - 1) / FRAGMENT_DATA_SIZE;
+        assert!(count > 1);
+        assert_eq!(datagrams.len(), count);
+        let mut reassembled = vec![];
+        for (i, datagram) in datagrams.iter().enumerate() {
+            assert!(datagram.len() <= PACKET_DATA_SIZE);
+            let fragment = decode_fragment(datagram).unwrap();
+            assert_eq!(fragment.id, LittleEndian::read_u64(&tx.sig[..8]));
+            assert_eq!((fragment.index, fragment.count), (i, count));
+            reassembled.extend_from_slice(fragment.data);
+        }
+        assert_eq!(reassembled, data);
+
+        let mut bad = datagrams[0].clone();
+        bad[FRAGMENT_INDEX_OFFSET] = count as u8;
+        assert_eq!(decode_fragment(&bad), Err(WireError::InvalidFragment));
+        bad[FRAGMENT_COUNT_OFFSET] = MAX_FRAGMENTS as u8 + 1;
+        assert_eq!(decode_fragment(&bad), Err(WireError::InvalidFragment));
+        assert_eq!(
+            decode_fragment(&datagrams[0][..FRAGMENT_HEADER_SIZE]),
+            Err(WireError::InvalidLength)
+        );
+    }
+}
@@ -1,13 +1,14 @@
 //! The `write_stage` module implements write stage of the RPU.
 
 use bank::Bank;
+use crdt::Crdt;
 use entry::Entry;
 use entry_writer::EntryWriter;
 use packet;
 use std::io::Write;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::{channel, Receiver};
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, RwLock};
 use std::thread::{Builder, JoinHandle};
 use streamer;
 
@@ -17,9 +18,11 @@ pub struct WriteStage {
 }
 
 impl WriteStage {
-    /// Create a new Rpu that wraps the given Bank.
+    /// Create a new Rpu that wraps the given Bank. Once entries are written, the
+    /// bank's state hash is published through `crdt`.
     pub fn new<W: Write + Send + 'static>(
         bank: Arc<Bank>,
+        crdt: Arc<RwLock<Crdt>>,
         exit: Arc<AtomicBool>,
         blob_recycler: packet::BlobRecycler,
         writer: Mutex<W>,
@@ -30,12 +33,18 @@ impl WriteStage {
             .name("solana-writer".to_string())
             .spawn(move || loop {
                 let entry_writer = EntryWriter::new(&bank);
-                let _ = entry_writer.write_and_send_entries(
+                let result = entry_writer.write_and_send_entries(
                     &blob_sender,
                     &blob_recycler,
                     &writer,
                     &entry_receiver,
                 );
+                if result.is_ok() {
+                    let (state_hash, count) = bank.state_hash();
+                    crdt.write()
+                        .expect("'crdt' write lock in WriteStage")
+                        .set_last_verified(state_hash, count);
+                }
                 if exit.load(Ordering::Relaxed) {
                     info!("broadcat_service exiting");
                     break;
@@ -166,10 +166,11 @@ fn tx_and_retry_get_balance(
 ) -> io::Result<i64> {
     let mut client = mk_client(leader);
     trace!("getting leader last_id");
+    let genesis = client.get_genesis();
     let last_id = client.get_last_id();
     info!("executing leader transer");
     let _sig = client
-        .transfer(500, &alice.keypair(), *bob_pubkey, &last_id)
+        .transfer(500, &alice.keypair(), *bob_pubkey, &genesis, &last_id)
         .unwrap();
     client.poll_get_balance(bob_pubkey)
 }